@@ -0,0 +1,115 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+#[derive(Debug)]
+pub enum Error {
+    NotFound(camino::Utf8PathBuf),
+    Io(std::io::Error),
+    // The file exists, but its contents couldn't be decoded
+    Decode(Box<dyn std::error::Error + Send + Sync>),
+    UnsupportedFormat(String),
+    MissingIndices,
+    MissingPositions,
+    // The requested gltf scene, or the default one if `None`
+    MissingScene(Option<usize>),
+    // The worker thread loading the asset panicked, with the panic message
+    Panicked(String),
+}
+
+impl Error {
+    /// Returns [`Error::NotFound`] if nothing exists at `path`, so it isn't reported as a decode error.
+    pub fn check_exists(path: impl AsRef<camino::Utf8Path>) -> Result<(), Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            Ok(())
+        } else {
+            Err(Self::NotFound(path.to_path_buf()))
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "asset {path} does not exist"),
+            Error::Io(e) => write!(f, "failed to read asset: {e}"),
+            Error::Decode(e) => write!(f, "failed to decode asset: {e}"),
+            Error::UnsupportedFormat(format) => write!(f, "unsupported asset format: {format}"),
+            Error::MissingIndices => write!(f, "mesh has no indices"),
+            Error::MissingPositions => write!(f, "mesh has no vertex positions"),
+            Error::MissingScene(Some(index)) => write!(f, "gltf file has no scene {index}"),
+            Error::MissingScene(None) => write!(f, "gltf file has no scenes"),
+            Error::Panicked(message) => write!(f, "asset loader panicked: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Decode(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(value: image::ImageError) -> Self {
+        match value {
+            image::ImageError::IoError(e) => Self::Io(e),
+            image::ImageError::Unsupported(e) => Self::UnsupportedFormat(e.to_string()),
+            e => Self::Decode(Box::new(e)),
+        }
+    }
+}
+
+impl From<render::compressed::Error> for Error {
+    fn from(value: render::compressed::Error) -> Self {
+        use render::compressed::Error as E;
+        match value {
+            E::UnsupportedFormat(_)
+            | E::UnsupportedSupercompression(_)
+            | E::UnsupportedDimensions
+            | E::TooLarge { .. } => Self::UnsupportedFormat(value.to_string()),
+            e => Self::Decode(Box::new(e)),
+        }
+    }
+}
+
+impl From<tobj::LoadError> for Error {
+    fn from(value: tobj::LoadError) -> Self {
+        Self::Decode(Box::new(value))
+    }
+}
+
+impl From<gltf::Error> for Error {
+    fn from(value: gltf::Error) -> Self {
+        match value {
+            gltf::Error::Io(e) => Self::Io(e),
+            e => Self::Decode(Box::new(e)),
+        }
+    }
+}
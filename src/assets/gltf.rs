@@ -16,8 +16,12 @@
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 use std::collections::HashMap;
 
+use crate::assets;
+
 pub struct Gltf {
     documents: HashMap<Id, File>,
+    // Also holds files that failed to load, so they can be reloaded once they're fixed
+    paths: HashMap<Id, camino::Utf8PathBuf>,
 }
 
 pub struct File {
@@ -26,6 +30,20 @@ pub struct File {
     pub images: Vec<gltf::image::Data>,
 }
 
+impl File {
+    pub fn open(path: impl AsRef<camino::Utf8Path>) -> Result<Self, assets::Error> {
+        let path = path.as_ref();
+        assets::Error::check_exists(path)?;
+
+        let (document, buffers, images) = gltf::import(path)?;
+        Ok(Self {
+            document,
+            buffers,
+            images,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Id(u64);
 
@@ -55,23 +73,39 @@ impl Gltf {
     pub fn new() -> Self {
         Self {
             documents: HashMap::new(),
+            paths: HashMap::new(),
         }
     }
 
-    pub fn load(&mut self, path: impl AsRef<camino::Utf8Path>) -> Id {
+    pub fn load(&mut self, path: impl AsRef<camino::Utf8Path>) -> Result<Id, assets::Error> {
         let path = path.as_ref();
         let id = Id::from_path(path);
 
-        self.documents.entry(id).or_insert_with(|| {
-            let (document, buffers, images) = gltf::import(path).expect("failed to open gltf file");
-            File {
-                document,
-                buffers,
-                images,
-            }
-        });
+        if !self.documents.contains_key(&id) {
+            let file = File::open(path).inspect_err(|_| self.insert_failed(id, path))?;
+            self.insert_loaded(id, path, file);
+        }
+
+        Ok(id)
+    }
+
+    pub(super) fn insert_loaded(&mut self, id: Id, path: impl AsRef<camino::Utf8Path>, file: File) {
+        self.documents.insert(id, file);
+        self.paths.insert(id, path.as_ref().to_path_buf());
+    }
+
+    /// Remembers the path of a file that failed to load, so it can be loaded again once it's fixed.
+    pub(super) fn insert_failed(&mut self, id: Id, path: impl AsRef<camino::Utf8Path>) {
+        self.paths.insert(id, path.as_ref().to_path_buf());
+    }
+
+    /// Every path gltf files were loaded (or failed to load) from.
+    pub fn paths(&self) -> impl Iterator<Item = &camino::Utf8Path> {
+        self.paths.values().map(camino::Utf8PathBuf::as_path)
+    }
 
-        id
+    pub fn path(&self, id: Id) -> Option<&camino::Utf8Path> {
+        self.paths.get(&id).map(camino::Utf8PathBuf::as_path)
     }
 
     pub fn get_expect(&self, id: Id) -> &File {
@@ -83,6 +117,7 @@ impl Gltf {
     }
 
     pub fn keep_ids(&mut self, ids: &[Id]) {
-        self.documents.retain(|i, _| ids.contains(i))
+        self.documents.retain(|i, _| ids.contains(i));
+        self.paths.retain(|i, _| ids.contains(i));
     }
 }
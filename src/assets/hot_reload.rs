@@ -0,0 +1,189 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::assets;
+use crate::components;
+use crate::render;
+use crate::scene;
+use crate::shaders;
+
+use bevy_ecs::prelude::*;
+use parking_lot::Mutex;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Reloads assets and shaders when their files change on disk.
+pub struct Plugin;
+
+impl scene::Plugin for Plugin {
+    fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+        let watcher = Watcher::new(POLL_INTERVAL);
+        // Edited shaders should replace the embedded copies, even in release builds
+        shaders::use_source_dir();
+
+        // Only exists when running from a checkout of the source
+        if let Ok(entries) = camino::Utf8Path::new(shaders::SOURCE_DIR).read_dir_utf8() {
+            let shader_paths = entries
+                .filter_map(Result::ok)
+                .map(camino::Utf8DirEntry::into_path)
+                .filter(|path| path.extension() == Some("wgsl"));
+            watcher.watch_all(shader_paths);
+        }
+
+        builder
+            .insert_resource(HotReload { watcher })
+            .add_systems(scene::First, hot_reload.after(assets::finish_loads));
+    }
+
+    fn dependencies(&self) -> Vec<scene::PluginId> {
+        vec![scene::PluginId::of::<assets::Plugin>()]
+    }
+}
+
+#[derive(Resource)]
+pub struct HotReload {
+    watcher: Watcher,
+}
+
+/// Polls the modification time of every watched file on a background thread.
+pub struct Watcher {
+    // The last seen modification time of every watched file
+    watched: Arc<Mutex<HashMap<camino::Utf8PathBuf, Option<SystemTime>>>>,
+    changed: crossbeam::channel::Receiver<camino::Utf8PathBuf>,
+}
+
+impl Watcher {
+    pub fn new(interval: Duration) -> Self {
+        let watched: Arc<Mutex<HashMap<camino::Utf8PathBuf, Option<SystemTime>>>> = Arc::default();
+        let (sender, changed) = crossbeam::channel::unbounded::<camino::Utf8PathBuf>();
+
+        let weak_watched = Arc::downgrade(&watched);
+        std::thread::Builder::new()
+            .name("wormhole file watcher".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+
+                // Stop once the watcher is dropped
+                let Some(watched) = weak_watched.upgrade() else {
+                    break;
+                };
+                for (path, last_modified) in watched.lock().iter_mut() {
+                    let modified = modified_time(path);
+                    if modified != *last_modified {
+                        *last_modified = modified;
+                        // Deleted files are reported once they come back
+                        if modified.is_some() && sender.send(path.clone()).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn file watcher thread");
+
+        Self { watched, changed }
+    }
+
+    /// Starts watching files, ignoring any that are already watched.
+    pub fn watch_all<P>(&self, paths: impl IntoIterator<Item = P>)
+    where
+        P: AsRef<camino::Utf8Path>,
+    {
+        let mut watched = self.watched.lock();
+        for path in paths {
+            let path = path.as_ref();
+            if !watched.contains_key(path) {
+                watched.insert(path.to_path_buf(), modified_time(path));
+            }
+        }
+    }
+
+    /// Every watched file that changed since the last call, without duplicates.
+    pub fn changed(&self) -> Vec<camino::Utf8PathBuf> {
+        let mut changed = self.changed.try_iter().collect::<Vec<_>>();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+fn modified_time(path: &camino::Utf8Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+pub fn hot_reload(
+    hot_reload: Res<HotReload>,
+    mut loader: ResMut<assets::Loader>,
+    mut render_state: ResMut<render::State>,
+    mut meshes: ResMut<scene::Meshes>,
+    mut mesh_renderers: Query<&mut components::MeshRenderer>,
+) {
+    let loader = &mut *loader;
+    let watcher = &hot_reload.watcher;
+    watcher.watch_all(
+        loader
+            .textures
+            .paths()
+            .chain(loader.models.paths())
+            .chain(loader.gltf.paths()),
+    );
+    watcher.watch_all(render_state.shaders.paths());
+
+    let mut reload_shaders = false;
+    let mut reloaded_models = Vec::new();
+    for path in watcher.changed() {
+        if path.extension() == Some("wgsl") {
+            reload_shaders = true;
+            continue;
+        }
+
+        log::info!("reloading {path}");
+        match loader.reload_path(&render_state, &path) {
+            Ok(models) => reloaded_models.extend(models),
+            Err(error) => log::error!("failed to reload {path}: {error}"),
+        }
+    }
+
+    if reload_shaders {
+        log::info!("reloading shaders");
+        if let Err(error) = render_state.reload_pipelines() {
+            log::error!("failed to reload shaders, keeping the previous ones:\n{error}");
+        }
+    }
+
+    if reloaded_models.is_empty() {
+        return;
+    }
+
+    // Mesh renderers point at the uploaded meshes of the old model
+    for mut mesh_renderer in &mut mesh_renderers {
+        let Some((model_id, mesh)) = mesh_renderer.source else {
+            continue;
+        };
+        let has_mesh = loader
+            .models
+            .get(model_id)
+            .is_some_and(|model| mesh < model.meshes.len());
+        if reloaded_models.contains(&model_id) && has_mesh {
+            *mesh_renderer =
+                components::MeshRenderer::from_model(&mut meshes, &loader.models, model_id, mesh);
+        }
+    }
+}
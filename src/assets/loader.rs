@@ -14,9 +14,15 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
 use crate::assets;
 use crate::render;
 
+use super::worker::{Finished, Workers};
+
 use bevy_ecs::prelude::*;
 
 #[derive(Resource)]
@@ -25,6 +31,49 @@ pub struct Loader {
     pub models: assets::Models,
     pub materials: assets::Materials,
     pub gltf: assets::Gltf,
+    workers: Workers,
+    // Only holds assets loaded in the background that haven't finished (or have failed)
+    pending: HashMap<Handle, LoadState>,
+}
+
+/// Identifies an asset of any kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Handle {
+    Texture(assets::TextureId),
+    Model(assets::ModelId),
+    Material(assets::MaterialId),
+    Gltf(assets::GltfId),
+}
+
+impl From<assets::TextureId> for Handle {
+    fn from(value: assets::TextureId) -> Self {
+        Self::Texture(value)
+    }
+}
+
+impl From<assets::ModelId> for Handle {
+    fn from(value: assets::ModelId) -> Self {
+        Self::Model(value)
+    }
+}
+
+impl From<assets::MaterialId> for Handle {
+    fn from(value: assets::MaterialId) -> Self {
+        Self::Material(value)
+    }
+}
+
+impl From<assets::GltfId> for Handle {
+    fn from(value: assets::GltfId) -> Self {
+        Self::Gltf(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    Failed(std::sync::Arc<assets::Error>),
 }
 
 impl Loader {
@@ -39,24 +88,65 @@ impl Loader {
             models,
             materials,
             gltf,
+            workers: Workers::new(),
+            pending: HashMap::new(),
         }
     }
 
-    pub fn load_gltf(&mut self, render_state: &render::State, path: impl AsRef<camino::Utf8Path>) {
-        let gltf_id = self.gltf.load(path);
+    pub fn load_gltf(
+        &mut self,
+        render_state: &render::State,
+        path: impl AsRef<camino::Utf8Path>,
+    ) -> Result<assets::GltfId, assets::Error> {
+        let path = path.as_ref();
+        let gltf_id = assets::GltfId::from_path(path);
+        if self.gltf.get(gltf_id).is_none() {
+            let result = assets::GltfFile::open(path)
+                .and_then(|file| self.insert_gltf(render_state, gltf_id, path, file));
+            result.inspect_err(|_| self.gltf.insert_failed(gltf_id, path))?;
+        }
+        Ok(gltf_id)
+    }
 
+    /// Adds a gltf file along with its textures, materials and meshes.
+    /// Nothing is added if any of its meshes are invalid, textures that fail to load are replaced by the null texture.
+    fn insert_gltf(
+        &mut self,
+        render_state: &render::State,
+        gltf_id: assets::GltfId,
+        path: &camino::Utf8Path,
+        file: assets::GltfFile,
+    ) -> Result<(), assets::Error> {
         let assets::GltfFile {
             document,
             buffers,
             images,
-        } = self.gltf.get_expect(gltf_id);
+        } = &file;
+
+        let models: Vec<_> = document
+            .meshes()
+            .map(|mesh| {
+                let model_id = assets::ModelId::Gltf(gltf_id, mesh.index());
+                let model = assets::Model::from_gltf(gltf_id, mesh, buffers)?;
+                Ok::<_, assets::Error>((model_id, model))
+            })
+            .try_collect()?;
 
         // FIXME: avoid recreating images multiple times?
         for texture in document.textures() {
             let texture_id = texture.index();
-            let texture = render::Texture::from_gltf(render_state, texture, images);
-            self.textures
-                .insert(assets::TextureId::Gltf(gltf_id, texture_id), texture);
+            let sampler = render::SamplerSettings::from_gltf(texture.sampler());
+            match render::Texture::from_gltf(render_state, texture, images) {
+                Ok(texture) => {
+                    self.textures.insert_with_sampler(
+                        render_state,
+                        assets::TextureId::Gltf(gltf_id, texture_id),
+                        texture,
+                        sampler,
+                    );
+                }
+                Err(error) => log::warn!("failed to load texture {texture_id} of {path}: {error}"),
+            }
         }
 
         for material in document.materials() {
@@ -66,11 +156,229 @@ impl Loader {
                 .insert(assets::MaterialId::Gltf(gltf_id, material_id), material);
         }
 
-        for mesh in document.meshes() {
-            let mesh_id = mesh.index();
-            let model = assets::Model::from_gltf(gltf_id, mesh, buffers);
-            self.models
-                .insert(assets::ModelId::Gltf(gltf_id, mesh_id), model);
+        for (model_id, model) in models {
+            self.models.insert(model_id, model);
+        }
+
+        self.gltf.insert_loaded(gltf_id, path, file);
+
+        Ok(())
+    }
+
+    /// Reloads every asset that was loaded from `path`.
+    /// Returns the models that were replaced, as meshes uploaded from the old models need to be uploaded again.
+    pub fn reload_path(
+        &mut self,
+        render_state: &render::State,
+        path: &camino::Utf8Path,
+    ) -> Result<Vec<assets::ModelId>, assets::Error> {
+        let mut reloaded_models = Vec::new();
+
+        let texture_id = assets::TextureId::from_path(path);
+        if self.textures.path(texture_id) == Some(path) {
+            self.textures.reload(render_state, texture_id)?;
+            // A texture that previously failed to load was given a new bind group index
+            self.materials.textures_changed();
+            self.pending.remove(&Handle::Texture(texture_id));
+        }
+
+        let model_id = assets::ModelId::from_path(path);
+        if self.models.path(model_id) == Some(path) && self.models.reload(model_id)? {
+            reloaded_models.push(model_id);
+            self.pending.remove(&Handle::Model(model_id));
+        }
+
+        let gltf_id = assets::GltfId::from_path(path);
+        if self.gltf.path(gltf_id) == Some(path) {
+            let file = assets::GltfFile::open(path)?;
+            let mesh_count = file.document.meshes().len();
+            self.insert_gltf(render_state, gltf_id, path, file)?;
+            reloaded_models
+                .extend((0..mesh_count).map(|mesh| assets::ModelId::Gltf(gltf_id, mesh)));
+            self.pending.remove(&Handle::Gltf(gltf_id));
+        }
+
+        Ok(reloaded_models)
+    }
+
+    /// Like [`assets::Textures::load_from_path_with_format`], but decodes the texture on a worker thread.
+    /// The texture is uploaded by [`Self::finish_loads`] once it's ready, until then it is drawn as the null texture.
+    pub fn load_texture_async(
+        &mut self,
+        render_state: &render::State,
+        path: impl AsRef<camino::Utf8Path>,
+        format: render::TextureFormat,
+    ) -> assets::TextureId {
+        let path = path.as_ref().to_path_buf();
+        let id = assets::TextureId::from_path(&path);
+        if self.textures.contains(id) || !self.start_loading(id) {
+            return id;
+        }
+
+        let features = render_state.wgpu.device.features();
+        let max_dimension = render_state.wgpu.device.limits().max_texture_dimension_2d;
+        let load_path = path.clone();
+        self.workers.spawn(
+            move || {
+                assets::textures::Decoded::from_path(&load_path, format, features, max_dimension)
+            },
+            move |result| Finished::Texture {
+                id,
+                path,
+                format,
+                result,
+            },
+        );
+
+        id
+    }
+
+    /// Like [`assets::Models::load_tobj`], but loads the model on a worker thread.
+    pub fn load_tobj_async(
+        &mut self,
+        path: impl AsRef<camino::Utf8Path>,
+        material_id: assets::MaterialId,
+    ) -> assets::ModelId {
+        let path = path.as_ref().to_path_buf();
+        let id = assets::ModelId::from_path(&path);
+        if self.models.contains(id) || !self.start_loading(id) {
+            return id;
         }
+
+        let load_path = path.clone();
+        self.workers.spawn(
+            move || assets::Model::from_tobj(&load_path, &tobj::GPU_LOAD_OPTIONS, material_id),
+            move |result| Finished::Model {
+                id,
+                path,
+                material_id,
+                result,
+            },
+        );
+
+        id
     }
+
+    /// Like [`Self::load_gltf`], but reads the file on a worker thread.
+    /// Its textures, materials and meshes are added all at once when it finishes.
+    pub fn load_gltf_async(&mut self, path: impl AsRef<camino::Utf8Path>) -> assets::GltfId {
+        let path = path.as_ref().to_path_buf();
+        let id = assets::GltfId::from_path(&path);
+        if self.gltf.get(id).is_some() || !self.start_loading(id) {
+            return id;
+        }
+
+        let load_path = path.clone();
+        self.workers.spawn(
+            move || assets::GltfFile::open(&load_path).map(Box::new),
+            move |result| Finished::Gltf { id, path, result },
+        );
+
+        id
+    }
+
+    /// Marks an asset as loading. Returns false if it already was.
+    fn start_loading(&mut self, handle: impl Into<Handle>) -> bool {
+        let handle = handle.into();
+        if matches!(self.pending.get(&handle), Some(LoadState::Loading)) {
+            return false;
+        }
+        self.pending.insert(handle, LoadState::Loading);
+        true
+    }
+
+    /// The state of an asset, or `None` if it was never loaded.
+    /// Assets that come from a gltf file share the state of that file.
+    pub fn state(&self, handle: impl Into<Handle>) -> Option<LoadState> {
+        let handle = match handle.into() {
+            Handle::Texture(assets::TextureId::Gltf(gltf_id, _))
+            | Handle::Model(assets::ModelId::Gltf(gltf_id, _))
+            | Handle::Material(assets::MaterialId::Gltf(gltf_id, _)) => Handle::Gltf(gltf_id),
+            handle => handle,
+        };
+
+        if let Some(state) = self.pending.get(&handle) {
+            return Some(state.clone());
+        }
+
+        let loaded = match handle {
+            Handle::Texture(id) => self.textures.contains(id),
+            Handle::Model(id) => self.models.contains(id),
+            Handle::Material(id) => self.materials.get(id).is_some(),
+            Handle::Gltf(id) => self.gltf.get(id).is_some(),
+        };
+        loaded.then_some(LoadState::Loaded)
+    }
+
+    /// Inserts everything that finished loading in the background, uploading it to the gpu.
+    pub fn finish_loads(&mut self, render_state: &render::State) {
+        let finished = self.workers.finished().collect::<Vec<_>>();
+        for finished in finished {
+            let (handle, result) = match finished {
+                Finished::Texture {
+                    id,
+                    path,
+                    format,
+                    result,
+                } => {
+                    let result = match result {
+                        Ok(decoded) => {
+                            let texture = decoded.upload(render_state);
+                            self.textures.insert_loaded(id, path, format, texture);
+                            self.materials.textures_changed();
+                            Ok(())
+                        }
+                        Err(error) => {
+                            self.textures.insert_failed(id, path, format);
+                            Err(error)
+                        }
+                    };
+                    (Handle::Texture(id), result)
+                }
+                Finished::Model {
+                    id,
+                    path,
+                    material_id,
+                    result,
+                } => {
+                    let result = match result {
+                        Ok(model) => {
+                            self.models.insert_loaded(id, path, model);
+                            Ok(())
+                        }
+                        Err(error) => {
+                            let load_options = &tobj::GPU_LOAD_OPTIONS;
+                            self.models
+                                .insert_failed(id, path, load_options, material_id);
+                            Err(error)
+                        }
+                    };
+                    (Handle::Model(id), result)
+                }
+                Finished::Gltf { id, path, result } => {
+                    let result =
+                        result.and_then(|file| self.insert_gltf(render_state, id, &path, *file));
+                    if result.is_err() {
+                        self.gltf.insert_failed(id, &path);
+                    }
+                    (Handle::Gltf(id), result)
+                }
+            };
+
+            match result {
+                Ok(()) => {
+                    self.pending.remove(&handle);
+                }
+                Err(error) => {
+                    log::error!("failed to load {handle:?}: {error}");
+                    self.pending
+                        .insert(handle, LoadState::Failed(std::sync::Arc::new(error)));
+                }
+            }
+        }
+    }
+}
+
+pub fn finish_loads(mut loader: ResMut<Loader>, render_state: Res<render::State>) {
+    loader.finish_loads(&render_state);
 }
@@ -17,13 +17,22 @@
 
 use crate::assets;
 use crate::render;
+use crate::shaders;
+
+use std::any::TypeId;
+use std::collections::HashMap;
 
 use itertools::Itertools;
 use wgpu::util::DeviceExt;
 
 pub struct Materials {
     pub(super) materials: indexmap::IndexMap<Id, render::Material>,
+    names: HashMap<Id, String>,
     buffer: Option<wgpu::Buffer>,
+
+    // Materials with their own shaders, by material type
+    custom: HashMap<TypeId, Box<dyn render::material::AnySet>>,
+    custom_types: HashMap<Id, TypeId>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -64,15 +73,79 @@ impl Materials {
     pub(super) fn new() -> Self {
         Self {
             materials: indexmap::IndexMap::new(),
+            names: HashMap::new(),
             buffer: None,
+
+            custom: HashMap::new(),
+            custom_types: HashMap::new(),
         }
     }
 
     pub fn insert(&mut self, id: Id, material: render::Material) -> Option<render::Material> {
+        self.remove_custom(id);
         self.buffer.take();
         self.materials.insert(id, material)
     }
 
+    /// Inserts a material with its own shader, replacing any material (of any type) with the same id.
+    pub fn insert_custom<M>(&mut self, id: Id, material: M)
+    where
+        M: render::CustomMaterial,
+    {
+        if self.materials.shift_remove(&id).is_some() {
+            self.buffer.take();
+        }
+        if self.custom_types.get(&id) != Some(&TypeId::of::<M>()) {
+            self.remove_custom(id);
+        }
+
+        self.custom_types.insert(id, TypeId::of::<M>());
+        self.custom
+            .entry(TypeId::of::<M>())
+            .or_insert_with(|| Box::new(render::material::Set::<M>::new()))
+            .as_any_mut()
+            .downcast_mut::<render::material::Set<M>>()
+            .expect("custom material set has the wrong type")
+            .insert(id, material);
+    }
+
+    pub fn get_custom<M>(&self, id: Id) -> Option<&M>
+    where
+        M: render::CustomMaterial,
+    {
+        self.custom
+            .get(&TypeId::of::<M>())?
+            .as_any()
+            .downcast_ref::<render::material::Set<M>>()?
+            .get(id)
+    }
+
+    fn remove_custom(&mut self, id: Id) {
+        let Some(type_id) = self.custom_types.remove(&id) else {
+            return;
+        };
+        if let Some(set) = self.custom.get_mut(&type_id) {
+            set.remove(id);
+        }
+    }
+
+    /// Inserts a material under an id derived from its name, and remembers the name.
+    pub fn insert_named(&mut self, name: impl Into<String>, material: render::Material) -> Id {
+        let name = name.into();
+        let id = Id::from_path(&name);
+        self.insert(id, material);
+        self.names.insert(id, name);
+        id
+    }
+
+    pub fn name(&self, id: Id) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &render::Material)> {
+        self.materials.iter().map(|(id, material)| (*id, material))
+    }
+
     pub fn get_expect(&self, id: Id) -> &render::Material {
         self.get(id).expect("asset id nonexistent")
     }
@@ -83,11 +156,73 @@ impl Materials {
 
     pub fn keep_ids(&mut self, ids: &[Id]) {
         self.buffer.take();
-        self.materials.retain(|i, _| ids.contains(i))
+        self.materials.retain(|i, _| ids.contains(i));
+        self.names.retain(|i, _| ids.contains(i));
+
+        self.custom_types.retain(|i, _| ids.contains(i));
+        for set in self.custom.values_mut() {
+            set.retain(ids);
+        }
+    }
+
+    /// Rebuilds the material buffer next frame, as it bakes in texture bind group indices.
+    pub(super) fn textures_changed(&mut self) {
+        self.buffer.take();
+        for set in self.custom.values_mut() {
+            set.textures_changed();
+        }
     }
 
     pub fn id_to_bindgroup_index(&self, id: Id) -> Option<usize> {
-        self.materials.get_index_of(&id).map(|i| i + 1) // add 1 because 0 is the "null" id
+        match self.custom_types.get(&id) {
+            // Custom materials index into the buffer of their own type
+            Some(type_id) => self.custom.get(type_id)?.index_of(id),
+            None => self.materials.get_index_of(&id).map(|i| i + 1), // add 1 because 0 is the "null" id
+        }
+    }
+
+    /// The pipeline meshes using this material are drawn with.
+    /// Returns `None` for custom materials that haven't been prepared yet.
+    pub fn pipeline(&self, id: Id) -> Option<render::material::Pipeline> {
+        match self.custom_types.get(&id) {
+            Some(&type_id) => {
+                let index = self.custom.get(&type_id)?.pipeline_index(id)?;
+                Some(render::material::Pipeline::Custom(type_id, index))
+            }
+            None => {
+                let permutation = self
+                    .get(id)
+                    .map(shaders::object::Permutation::from_material)
+                    .unwrap_or_default();
+                Some(render::material::Pipeline::Object(permutation))
+            }
+        }
+    }
+
+    pub fn custom_pipeline_key(
+        &self,
+        type_id: TypeId,
+        index: usize,
+    ) -> Option<&shaders::PipelineKey> {
+        self.custom.get(&type_id)?.pipeline_key(index)
+    }
+
+    /// The buffer with the data of every material of a custom material type.
+    pub fn custom_buffer(&self, type_id: TypeId) -> Option<&wgpu::Buffer> {
+        self.custom.get(&type_id)?.buffer()
+    }
+
+    /// Rebuilds the buffers of custom materials and creates their pipelines.
+    pub fn prepare_custom(
+        &mut self,
+        render_state: &mut render::State,
+        textures: &assets::Textures,
+    ) {
+        for set in self.custom.values_mut() {
+            if let Err(err) = set.prepare(render_state, textures) {
+                log::error!("failed to create custom material pipeline:\n{err}");
+            }
+        }
     }
 }
 
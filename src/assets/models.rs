@@ -17,11 +17,17 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use itertools::Itertools;
+
 use crate::assets;
 use crate::render;
 
 pub struct Models {
     pub(super) models: HashMap<Id, Model>,
+    // Also holds models that failed to load, so they can be reloaded once their file is fixed
+    paths: HashMap<Id, camino::Utf8PathBuf>,
+    // How obj models were loaded, so they can be reloaded the same way
+    obj_options: HashMap<Id, (tobj::LoadOptions, assets::MaterialId)>,
 }
 
 pub struct Model {
@@ -34,14 +40,36 @@ impl Model {
         gltf_id: assets::GltfId,
         mesh: gltf::Mesh<'_>,
         buffers: &[gltf::buffer::Data],
-    ) -> Self {
+    ) -> Result<Self, assets::Error> {
         let name = mesh.name().unwrap_or("unamed model").to_string();
         let meshes = mesh
             .primitives()
             .map(|primitive| render::Mesh::from_gltf_primitive(gltf_id, primitive, buffers))
-            .map(Arc::new)
-            .collect();
-        Self { name, meshes }
+            .map_ok(Arc::new)
+            .try_collect()?;
+        Ok(Self { name, meshes })
+    }
+
+    pub fn from_tobj(
+        path: impl AsRef<camino::Utf8Path>,
+        load_options: &tobj::LoadOptions,
+        material_id: assets::MaterialId,
+    ) -> Result<Self, assets::Error> {
+        let path = path.as_ref();
+        assets::Error::check_exists(path)?;
+
+        // FIXME: this behavior is probably wrong.
+        let (meshes, _) = tobj::load_obj(path, load_options)?;
+        let meshes = meshes
+            .into_iter()
+            .map(|m| render::Mesh::from_tobj_mesh(m.mesh, material_id))
+            .map_ok(Arc::new)
+            .try_collect()?;
+
+        Ok(Self {
+            meshes,
+            name: path.to_string(),
+        })
     }
 }
 
@@ -83,6 +111,8 @@ impl Models {
     pub(super) fn new() -> Self {
         Self {
             models: HashMap::new(),
+            paths: HashMap::new(),
+            obj_options: HashMap::new(),
         }
     }
 
@@ -94,7 +124,7 @@ impl Models {
         &mut self,
         path: impl AsRef<camino::Utf8Path>,
         material_id: assets::MaterialId,
-    ) -> Id {
+    ) -> Result<Id, assets::Error> {
         self.load_tobj_with_options(path, &tobj::GPU_LOAD_OPTIONS, material_id)
     }
 
@@ -103,26 +133,69 @@ impl Models {
         path: impl AsRef<camino::Utf8Path>,
         load_options: &tobj::LoadOptions,
         material_id: assets::MaterialId,
-    ) -> Id {
+    ) -> Result<Id, assets::Error> {
         let path = path.as_ref();
         let id = Id::from_path(path);
 
-        self.models.entry(id).or_insert_with(|| {
-            // FIXME: this behavior is probably wrong.
-            let (meshes, _) = tobj::load_obj(path, load_options).expect("failed to load models");
-            let meshes = meshes
-                .into_iter()
-                .map(|m| render::Mesh::from_tobj_mesh(m.mesh, material_id))
-                .map(Arc::new)
-                .collect();
+        if !self.models.contains_key(&id) {
+            let model = Model::from_tobj(path, load_options, material_id)
+                .inspect_err(|_| self.insert_failed(id, path, load_options, material_id))?;
+            self.insert_loaded(id, path, model);
+            self.obj_options.insert(id, (*load_options, material_id));
+        }
+
+        Ok(id)
+    }
+
+    pub(super) fn insert_loaded(
+        &mut self,
+        id: Id,
+        path: impl AsRef<camino::Utf8Path>,
+        model: Model,
+    ) {
+        self.models.insert(id, model);
+        self.paths.insert(id, path.as_ref().to_path_buf());
+    }
 
-            Model {
-                meshes,
-                name: path.to_string(),
-            }
-        });
+    /// Remembers the path of an obj model that failed to load, so [`Self::reload`] can try again.
+    pub(super) fn insert_failed(
+        &mut self,
+        id: Id,
+        path: impl AsRef<camino::Utf8Path>,
+        load_options: &tobj::LoadOptions,
+        material_id: assets::MaterialId,
+    ) {
+        self.paths.insert(id, path.as_ref().to_path_buf());
+        self.obj_options.insert(id, (*load_options, material_id));
+    }
+
+    /// Loads an obj model from its path again, keeping its id.
+    /// Returns false if the model wasn't loaded from an obj file.
+    pub fn reload(&mut self, id: Id) -> Result<bool, assets::Error> {
+        let (Some(path), Some((load_options, material_id))) =
+            (self.paths.get(&id), self.obj_options.get(&id))
+        else {
+            return Ok(false);
+        };
+
+        let model = Model::from_tobj(path, load_options, *material_id)?;
+        self.models.insert(id, model);
+
+        Ok(true)
+    }
+
+    /// Every path models were loaded (or failed to load) from.
+    pub fn paths(&self) -> impl Iterator<Item = &camino::Utf8Path> {
+        self.paths.values().map(camino::Utf8PathBuf::as_path)
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.models.contains_key(&id)
+    }
 
-        id
+    /// The path a model was loaded from, if it was loaded from a path.
+    pub fn path(&self, id: Id) -> Option<&camino::Utf8Path> {
+        self.paths.get(&id).map(camino::Utf8PathBuf::as_path)
     }
 
     pub fn get_expect(&self, id: Id) -> &Model {
@@ -134,6 +207,8 @@ impl Models {
     }
 
     pub fn keep_ids(&mut self, ids: &[Id]) {
-        self.models.retain(|i, _| ids.contains(i))
+        self.models.retain(|i, _| ids.contains(i));
+        self.paths.retain(|i, _| ids.contains(i));
+        self.obj_options.retain(|i, _| ids.contains(i));
     }
 }
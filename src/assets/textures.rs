@@ -15,6 +15,8 @@
 // You should have received a copy of the GNU General Public License
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
+
 use itertools::Itertools;
 
 use crate::assets;
@@ -23,6 +25,14 @@ use crate::render;
 pub struct Textures {
     pub(super) textures: indexmap::IndexMap<Id, render::Texture>,
     null_texture: render::Texture,
+    // Also holds textures that failed to load, so they can be reloaded once their file is fixed
+    paths: HashMap<Id, camino::Utf8PathBuf>,
+    // What textures loaded from a path were loaded as, so they can be reloaded the same way
+    formats: HashMap<Id, render::TextureFormat>,
+    // Index 0 is always the default sampler
+    samplers: indexmap::IndexMap<render::SamplerSettings, wgpu::Sampler>,
+    texture_samplers: HashMap<Id, usize>,
+    sampler_buffer: Option<wgpu::Buffer>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -59,20 +69,93 @@ where
     }
 }
 
+/// A texture file decoded on the cpu, ready to be uploaded.
+pub enum Decoded {
+    Image(image::DynamicImage, render::TextureFormat),
+    // Compressed textures already know their format
+    Ktx2(render::compressed::Ktx2),
+}
+
+impl Decoded {
+    /// Reads and decodes a texture file.
+    /// `features` are the device features, used to decide if compressed textures need decoding on the cpu,
+    /// and `max_dimension` is the device's `max_texture_dimension_2d`.
+    pub fn from_path(
+        path: impl AsRef<camino::Utf8Path>,
+        format: render::TextureFormat,
+        features: wgpu::Features,
+        max_dimension: u32,
+    ) -> Result<Self, assets::Error> {
+        let path = path.as_ref();
+        assets::Error::check_exists(path)?;
+
+        if path.extension() == Some("ktx2") {
+            let bytes = std::fs::read(path)?;
+            let ktx2 = render::compressed::Ktx2::load(&bytes, features, max_dimension)?;
+            return Ok(Self::Ktx2(ktx2));
+        }
+
+        let image = image::open(path)?;
+        Ok(Self::Image(image, format))
+    }
+
+    /// Like [`Self::from_path`], using the features and limits of the render state's device.
+    pub fn for_device(
+        path: impl AsRef<camino::Utf8Path>,
+        format: render::TextureFormat,
+        render_state: &render::State,
+    ) -> Result<Self, assets::Error> {
+        let device = &render_state.wgpu.device;
+        Self::from_path(
+            path,
+            format,
+            device.features(),
+            device.limits().max_texture_dimension_2d,
+        )
+    }
+
+    pub fn upload(self, render_state: &render::State) -> render::Texture {
+        match self {
+            Decoded::Image(image, format) => {
+                render::Texture::from_image(render_state, &image, format)
+            }
+            Decoded::Ktx2(ktx2) => render::Texture::from_ktx2_data(render_state, &ktx2),
+        }
+    }
+}
+
 impl Textures {
     pub(super) fn new(render_state: &render::State) -> Self {
-        let null_texture = render::Texture::new(
+        // A magenta checkerboard, so textures that failed to load stand out
+        let null_image = image::RgbaImage::from_fn(256, 256, |x, y| {
+            if (x / 32 + y / 32) % 2 == 0 {
+                image::Rgba([255, 0, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        });
+        let null_texture = render::Texture::from_bytes(
             render_state,
-            wgpu::Extent3d {
-                width: 256,
-                height: 256,
-                depth_or_array_layers: 1,
-            },
+            &null_image,
+            null_image.width(),
+            null_image.height(),
             render::TextureFormat::GENERIC,
         );
+        let default_settings = render::SamplerSettings::default();
+        let mut samplers = indexmap::IndexMap::new();
+        samplers.insert(
+            default_settings,
+            default_settings.create_sampler(render_state),
+        );
+
         Self {
             textures: indexmap::IndexMap::new(),
             null_texture,
+            paths: HashMap::new(),
+            formats: HashMap::new(),
+            samplers,
+            texture_samplers: HashMap::new(),
+            sampler_buffer: None,
         }
     }
 
@@ -88,9 +171,45 @@ impl Textures {
     }
 
     pub fn insert(&mut self, id: Id, texture: render::Texture) -> Option<render::Texture> {
+        self.sampler_buffer.take();
         self.textures.insert(id, texture)
     }
 
+    pub fn insert_with_sampler(
+        &mut self,
+        render_state: &render::State,
+        id: Id,
+        texture: render::Texture,
+        settings: render::SamplerSettings,
+    ) -> Option<render::Texture> {
+        self.set_sampler(render_state, id, settings);
+        self.insert(id, texture)
+    }
+
+    /// Changes how a texture is sampled.
+    /// Samplers are shared between textures with the same settings, and once [`render::state::MAX_SAMPLER_COUNT`]
+    /// distinct settings are in use any new ones fall back to the default sampler.
+    pub fn set_sampler(
+        &mut self,
+        render_state: &render::State,
+        id: Id,
+        settings: render::SamplerSettings,
+    ) {
+        let index = match self.samplers.get_index_of(&settings) {
+            Some(index) => index,
+            None if self.samplers.len() < render::state::MAX_SAMPLER_COUNT as usize => {
+                let sampler = settings.create_sampler(render_state);
+                self.samplers.insert_full(settings, sampler).0
+            }
+            None => {
+                log::warn!("out of sampler slots, using the default sampler for {id:?}");
+                0
+            }
+        };
+        self.texture_samplers.insert(id, index);
+        self.sampler_buffer.take();
+    }
+
     pub fn id_to_bindgroup_index(&self, id: Id) -> Option<usize> {
         self.textures.get_index_of(&id).map(|i| i + 1) // add 1 because 0 is the "null" id
     }
@@ -99,7 +218,7 @@ impl Textures {
         &mut self,
         render_state: &render::State,
         path: impl AsRef<camino::Utf8Path>,
-    ) -> Id {
+    ) -> Result<Id, assets::Error> {
         self.load_from_path_with_format(render_state, path, render::TextureFormat::GENERIC)
     }
 
@@ -108,16 +227,69 @@ impl Textures {
         render_state: &render::State,
         path: impl AsRef<camino::Utf8Path>,
         format: render::TextureFormat,
-    ) -> Id {
+    ) -> Result<Id, assets::Error> {
         let path = path.as_ref();
         let id = Id::from_path(path);
 
-        self.textures.entry(id).or_insert_with(|| {
-            let image = image::open(path).expect("failed to load texture");
-            render::Texture::from_image(render_state, &image, format)
-        });
+        if !self.textures.contains_key(&id) {
+            let texture = Decoded::for_device(path, format, render_state)
+                .inspect_err(|_| self.insert_failed(id, path, format))?
+                .upload(render_state);
+            self.insert_loaded(id, path, format, texture);
+        }
+
+        Ok(id)
+    }
+
+    pub(super) fn insert_loaded(
+        &mut self,
+        id: Id,
+        path: impl AsRef<camino::Utf8Path>,
+        format: render::TextureFormat,
+        texture: render::Texture,
+    ) {
+        self.insert(id, texture);
+        self.paths.insert(id, path.as_ref().to_path_buf());
+        self.formats.insert(id, format);
+    }
+
+    /// Remembers the path of a texture that failed to load, so [`Self::reload`] can try again.
+    pub(super) fn insert_failed(
+        &mut self,
+        id: Id,
+        path: impl AsRef<camino::Utf8Path>,
+        format: render::TextureFormat,
+    ) {
+        self.paths.insert(id, path.as_ref().to_path_buf());
+        self.formats.insert(id, format);
+    }
+
+    /// Loads a texture from its path again, keeping its id and bind group index.
+    /// Textures that failed to load are added, and get a bind group index.
+    /// Does nothing if the texture wasn't loaded from a path.
+    pub fn reload(&mut self, render_state: &render::State, id: Id) -> Result<(), assets::Error> {
+        let (Some(path), Some(&format)) = (self.paths.get(&id), self.formats.get(&id)) else {
+            return Ok(());
+        };
+
+        let texture = Decoded::for_device(path, format, render_state)?.upload(render_state);
+        self.insert(id, texture);
 
-        id
+        Ok(())
+    }
+
+    /// Every path textures were loaded (or failed to load) from.
+    pub fn paths(&self) -> impl Iterator<Item = &camino::Utf8Path> {
+        self.paths.values().map(camino::Utf8PathBuf::as_path)
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.textures.contains_key(&id)
+    }
+
+    /// The path a texture was loaded from, if it was loaded from a path.
+    pub fn path(&self, id: Id) -> Option<&camino::Utf8Path> {
+        self.paths.get(&id).map(camino::Utf8PathBuf::as_path)
     }
 
     pub fn get_expect(&self, id: Id) -> &render::Texture {
@@ -133,7 +305,11 @@ impl Textures {
     }
 
     pub fn keep_ids(&mut self, ids: &[Id]) {
-        self.textures.retain(|i, _| ids.contains(i))
+        self.textures.retain(|i, _| ids.contains(i));
+        self.paths.retain(|i, _| ids.contains(i));
+        self.formats.retain(|i, _| ids.contains(i));
+        self.texture_samplers.retain(|i, _| ids.contains(i));
+        self.sampler_buffer.take();
     }
 }
 
@@ -143,4 +319,46 @@ impl Textures {
             .chain(self.textures.values().map(|t| &t.view))
             .collect_vec()
     }
+
+    pub fn get_samplers(&self) -> Vec<&wgpu::Sampler> {
+        self.samplers.values().collect_vec()
+    }
+
+    pub fn update_sampler_buffer(&mut self, render_state: &render::State) {
+        if self.sampler_buffer.is_none() {
+            self.sampler_buffer = Some(self.create_sampler_buffer(render_state));
+        }
+    }
+
+    /// Maps every texture bind group index to an index into [`Self::get_samplers`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`Self::update_sampler_buffer`] was not called after the textures last changed.
+    pub fn sampler_buffer(&self) -> &wgpu::Buffer {
+        self.sampler_buffer
+            .as_ref()
+            .expect("sampler buffer is out of date")
+    }
+
+    fn create_sampler_buffer(&self, render_state: &render::State) -> wgpu::Buffer {
+        use wgpu::util::DeviceExt;
+
+        let data = std::iter::once(0)
+            .chain(
+                self.textures
+                    .keys()
+                    .map(|id| self.texture_samplers.get(id).copied().unwrap_or_default() as u32),
+            )
+            .collect_vec();
+
+        render_state
+            .wgpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("wormhole texture sampler buffer"),
+                contents: bytemuck::cast_slice(&data),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+    }
 }
@@ -0,0 +1,143 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::assets;
+use crate::render;
+
+/// An asset decoded on a worker thread, waiting to be inserted (and uploaded) on the main thread.
+pub(super) enum Finished {
+    Texture {
+        id: assets::TextureId,
+        path: camino::Utf8PathBuf,
+        format: render::TextureFormat,
+        result: Result<assets::textures::Decoded, assets::Error>,
+    },
+    Model {
+        id: assets::ModelId,
+        path: camino::Utf8PathBuf,
+        material_id: assets::MaterialId,
+        result: Result<assets::Model, assets::Error>,
+    },
+    Gltf {
+        id: assets::GltfId,
+        path: camino::Utf8PathBuf,
+        result: Result<Box<assets::GltfFile>, assets::Error>,
+    },
+}
+
+type Job = Box<dyn FnOnce() -> Finished + Send>;
+
+/// A pool of threads that decode asset files in the background.
+pub(super) struct Workers {
+    jobs: crossbeam::channel::Sender<Job>,
+    finished: crossbeam::channel::Receiver<Finished>,
+}
+
+impl Workers {
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = crossbeam::channel::unbounded::<Job>();
+        let (finished_sender, finished) = crossbeam::channel::unbounded();
+
+        // Leave a core for the main thread
+        let thread_count = std::thread::available_parallelism()
+            .map_or(1, |n| n.get().saturating_sub(1))
+            .max(1);
+        for i in 0..thread_count {
+            let job_receiver = job_receiver.clone();
+            let finished_sender = finished_sender.clone();
+            std::thread::Builder::new()
+                .name(format!("wormhole asset worker {i}"))
+                .spawn(move || {
+                    // Exits once the job sender is dropped with the loader
+                    for job in job_receiver {
+                        if finished_sender.send(job()).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .expect("failed to spawn asset worker thread");
+        }
+
+        Self { jobs, finished }
+    }
+
+    /// Runs `load` on a worker thread and wraps its result with `finish`.
+    /// A panicking `load` is turned into an error, so the asset is treated like any other failed load.
+    pub fn spawn<T>(
+        &self,
+        load: impl FnOnce() -> Result<T, assets::Error> + Send + 'static,
+        finish: impl FnOnce(Result<T, assets::Error>) -> Finished + Send + 'static,
+    ) {
+        let job: Job = Box::new(move || {
+            // Keep the worker alive and report the asset as failed instead of loading forever
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(load))
+                .unwrap_or_else(|payload| {
+                    Err(assets::Error::Panicked(panic_message(payload.as_ref())))
+                });
+            finish(result)
+        });
+        self.jobs
+            .send(job)
+            .expect("asset worker threads have stopped");
+    }
+
+    /// Every asset that has finished decoding since the last call.
+    pub fn finished(&self) -> impl Iterator<Item = Finished> + '_ {
+        self.finished.try_iter()
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panicking_jobs_finish_with_an_error() {
+        let workers = Workers::new();
+        let id = assets::ModelId::from_path("panics.obj");
+        workers.spawn(
+            || -> Result<assets::Model, assets::Error> { panic!("oh no") },
+            move |result| Finished::Model {
+                id,
+                path: "panics.obj".into(),
+                material_id: assets::MaterialId::Path(0),
+                result,
+            },
+        );
+
+        let finished = workers
+            .finished
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .expect("the worker never finished");
+        let Finished::Model { path, result, .. } = finished else {
+            panic!("the job finished as a different asset");
+        };
+        // The path is kept so the loader can record the failure for hot reloading
+        assert_eq!(path, "panics.obj");
+        assert!(matches!(result, Err(assets::Error::Panicked(message)) if message == "oh no"));
+    }
+}
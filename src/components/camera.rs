@@ -32,10 +32,20 @@ pub struct Data {
     pub view_proj: glam::Mat4,
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Self::from_aspect(16.0 / 9.0)
+    }
+}
+
 impl Camera {
     pub fn new(render_state: &render::State) -> Self {
         let aspect = render_state.wgpu.surface_config.width as f32
             / render_state.wgpu.surface_config.height as f32;
+        Self::from_aspect(aspect)
+    }
+
+    pub fn from_aspect(aspect: f32) -> Self {
         Camera {
             aspect,
             fovy: 70.0,
@@ -0,0 +1,254 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::components;
+use crate::scene;
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::EntityCommands;
+
+/// The entity this entity is attached to.
+///
+/// Use [`HierarchyCommands`] (or [`set_parent`] and friends) to change it, so [`Children`] stays in sync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Component)]
+pub struct Parent(pub Entity);
+
+/// Entities attached to this entity.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Component)]
+pub struct Children(pub Vec<Entity>);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+impl Children {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+#[derive(SystemSet, Clone, Copy, Hash, Debug, PartialEq, Eq)]
+pub struct TransformPropagation;
+
+/// Keeps [`components::GlobalTransform`] up to date for every entity with a [`components::Transform`].
+pub struct Plugin;
+
+impl scene::Plugin for Plugin {
+    fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+        builder.add_systems(
+            scene::PostUpdate,
+            (
+                remove_dangling_relations,
+                insert_global_transforms,
+                apply_deferred,
+                propagate_transforms,
+            )
+                .chain()
+                .in_set(TransformPropagation),
+        );
+    }
+
+    fn dependencies(&self) -> Vec<scene::PluginId> {
+        vec![scene::PluginId::of::<scene::MainSchedulePlugin>()]
+    }
+}
+
+/// Attaches `child` to `parent`, detaching it from its previous parent.
+/// Does nothing if that would make an entity its own ancestor.
+pub fn set_parent(world: &mut World, child: Entity, parent: Entity) {
+    let mut ancestor = Some(parent);
+    while let Some(entity) = ancestor {
+        if entity == child {
+            log::warn!(
+                "unable to attach {child:?} to {parent:?}: it would become its own ancestor"
+            );
+            return;
+        }
+        ancestor = world.get::<Parent>(entity).map(Parent::get);
+    }
+
+    if world.get_entity(child).is_none() || world.get_entity(parent).is_none() {
+        return;
+    }
+
+    remove_parent(world, child);
+    world.entity_mut(child).insert(Parent(parent));
+    let mut parent = world.entity_mut(parent);
+    match parent.get_mut::<Children>() {
+        Some(mut children) => children.0.push(child),
+        None => {
+            parent.insert(Children(vec![child]));
+        }
+    }
+}
+
+/// Detaches `child` from its parent, making it a root entity.
+pub fn remove_parent(world: &mut World, child: Entity) {
+    let Some(mut child_entity) = world.get_entity_mut(child) else {
+        return;
+    };
+    let Some(Parent(parent)) = child_entity.take::<Parent>() else {
+        return;
+    };
+
+    let Some(mut parent) = world.get_entity_mut(parent) else {
+        return;
+    };
+    if let Some(mut children) = parent.get_mut::<Children>() {
+        children.0.retain(|&c| c != child);
+        if children.0.is_empty() {
+            parent.remove::<Children>();
+        }
+    }
+}
+
+/// Despawns `entity` and all of its descendants, and detaches it from its parent.
+pub fn despawn_recursive(world: &mut World, entity: Entity) {
+    remove_parent(world, entity);
+
+    let mut stack = vec![entity];
+    while let Some(entity) = stack.pop() {
+        let Some(entity) = world.get_entity_mut(entity) else {
+            continue;
+        };
+        if let Some(children) = entity.get::<Children>() {
+            stack.extend(children.iter());
+        }
+        entity.despawn();
+    }
+}
+
+/// Hierarchy changes that keep [`Parent`] and [`Children`] consistent, applied with the other commands.
+pub trait HierarchyCommands {
+    fn set_parent(&mut self, parent: Entity) -> &mut Self;
+
+    fn remove_parent(&mut self) -> &mut Self;
+
+    fn despawn_recursive(self);
+}
+
+impl HierarchyCommands for EntityCommands<'_> {
+    fn set_parent(&mut self, parent: Entity) -> &mut Self {
+        let child = self.id();
+        self.commands()
+            .add(move |world: &mut World| set_parent(world, child, parent));
+        self
+    }
+
+    fn remove_parent(&mut self) -> &mut Self {
+        let child = self.id();
+        self.commands()
+            .add(move |world: &mut World| remove_parent(world, child));
+        self
+    }
+
+    fn despawn_recursive(mut self) {
+        let entity = self.id();
+        self.commands()
+            .add(move |world: &mut World| despawn_recursive(world, entity));
+    }
+}
+
+/// Cleans up after entities in a hierarchy that were despawned without [`despawn_recursive`].
+/// Children of a despawned entity become root entities.
+pub fn remove_dangling_relations(
+    mut commands: Commands,
+    entities: &bevy_ecs::entity::Entities,
+    mut children_query: Query<(Entity, &mut Children)>,
+    parent_query: Query<(Entity, &Parent)>,
+) {
+    for (entity, mut children) in &mut children_query {
+        if children.iter().all(|c| entities.contains(c)) {
+            continue;
+        }
+        children.0.retain(|&c| entities.contains(c));
+        if children.0.is_empty() {
+            commands.entity(entity).remove::<Children>();
+        }
+    }
+
+    for (entity, parent) in &parent_query {
+        if !entities.contains(parent.get()) {
+            commands.entity(entity).remove::<Parent>();
+        }
+    }
+}
+
+pub fn insert_global_transforms(
+    mut commands: Commands,
+    query: Query<(Entity, &components::Transform), Without<components::GlobalTransform>>,
+) {
+    for (entity, transform) in &query {
+        commands
+            .entity(entity)
+            .insert(components::GlobalTransform::from(*transform));
+    }
+}
+
+pub fn propagate_transforms(
+    mut root_query: Query<
+        (
+            &components::Transform,
+            &mut components::GlobalTransform,
+            Option<&Children>,
+        ),
+        Without<Parent>,
+    >,
+    mut child_query: Query<
+        (
+            &components::Transform,
+            &mut components::GlobalTransform,
+            Option<&Children>,
+        ),
+        With<Parent>,
+    >,
+) {
+    let mut stack = Vec::new();
+
+    for (transform, mut global_transform, children) in &mut root_query {
+        let global = components::GlobalTransform::from(*transform);
+        *global_transform = global;
+
+        stack.extend(
+            children
+                .into_iter()
+                .flat_map(Children::iter)
+                .map(|c| (c, global)),
+        );
+
+        while let Some((entity, parent_global)) = stack.pop() {
+            let Ok((transform, mut global_transform, children)) = child_query.get_mut(entity)
+            else {
+                continue;
+            };
+
+            let global = parent_global.mul_transform(*transform);
+            *global_transform = global;
+
+            stack.extend(
+                children
+                    .into_iter()
+                    .flat_map(Children::iter)
+                    .map(|c| (c, global)),
+            );
+        }
+    }
+}
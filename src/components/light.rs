@@ -15,6 +15,7 @@
 // You should have received a copy of the GNU General Public License
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 use crate::assets;
+use crate::components;
 use crate::render;
 use crate::scene;
 
@@ -24,18 +25,42 @@ use bevy_ecs::prelude::*;
 pub struct Light {
     mesh_index: scene::MeshIndex,
 
-    constant: f32,
-    linear: f32,
-    quadratic: f32,
+    pub light_type: LightType,
+    // Spot lights only, in radians from the light's direction.
+    // Full intensity inside the inner cone, fading out towards the outer cone.
+    pub inner_cone_angle: f32,
+    pub outer_cone_angle: f32,
 
-    ambient: render::Color,
-    diffuse: render::Color,
-    specular: render::Color,
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+
+    pub diffuse: render::Color,
+    pub specular: render::Color,
+
+    // The light doesn't cast shadows when this is None.
+    // Only point and spot lights cast shadows.
+    pub shadows: Option<ShadowSettings>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    // Width and height of every face of the shadow cube map
+    pub resolution: u32,
+    pub near: f32,
+    // Nothing past this distance from the light is shadowed
+    pub far: f32,
+    pub bias: f32,
 }
 
+// Directional and spot lights shine along the light's forward (-Z) axis
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum LightType {
+    #[default]
     Point = 1,
+    Directional = 2,
+    Spot = 3,
 }
 
 pub struct PreparedObject {
@@ -50,39 +75,66 @@ pub struct PreparedLight {
     linear: f32,
     quadratic: f32,
 
-    ambient: render::Color,
     diffuse: render::Color,
     specular: render::Color,
 
     position: glam::Vec3,
+    // -1 if the light has no shadow map
+    shadow_index: i32,
+    shadow_far: f32,
+    shadow_bias: f32,
+
+    direction: glam::Vec3,
+    light_type: u32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    range: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            near: 0.1,
+            far: 100.0,
+            bias: 0.05,
+        }
+    }
 }
 
 impl Light {
     pub fn new(assets: &mut assets::Loader, scene_models: &mut scene::Meshes) -> Self {
         let id = assets
             .models
-            .load_tobj("assets/meshes/ico_sphere.obj", assets::MaterialId::Path(0));
+            .load_tobj("assets/meshes/ico_sphere.obj", assets::MaterialId::Path(0))
+            .expect("failed to load the light mesh");
         let model = assets.models.get_expect(id);
         let model_index = scene_models.upload_mesh(model.meshes[0].clone());
+        // Lights aren't reference counted like mesh renderers, so keep their mesh around for good
+        scene_models.retain_mesh(model_index);
 
         let constant = 1.0;
         let linear = 0.022;
         let quadratic = 0.0019;
 
-        let ambient = render::Color::from_rgb_normalized(glam::vec3(1.0, 1.0, 1.0));
         let diffuse = render::Color::from_rgb_normalized(glam::vec3(1.0, 1.0, 1.0));
         let specular = render::Color::from_rgb_normalized(glam::vec3(1.0, 1.0, 1.0));
 
         Light {
             mesh_index: model_index,
 
+            light_type: LightType::Point,
+            inner_cone_angle: 20_f32.to_radians(),
+            outer_cone_angle: 30_f32.to_radians(),
+
             constant,
             linear,
             quadratic,
 
-            ambient,
             diffuse,
             specular,
+
+            shadows: None,
         }
     }
 
@@ -106,15 +158,62 @@ impl Light {
         }
     }
 
-    pub fn prepare_light(&self, position: glam::Vec3, resources: &mut scene::PrepareResources<'_>) {
+    /// How far the light reaches before attenuation makes it too dim to matter.
+    /// Used to cull lights per screen tile.
+    pub fn range(&self) -> f32 {
+        // Anything under 1/256th of the light's brightest channel can't show up in an 8 bit color
+        let intensity = glam::Vec3::from(self.diffuse).max_element();
+        let c = self.constant - intensity * 256.0;
+        let range = if self.quadratic > 0.0 {
+            let discriminant = self.linear * self.linear - 4.0 * self.quadratic * c;
+            (-self.linear + discriminant.max(0.0).sqrt()) / (2.0 * self.quadratic)
+        } else if self.linear > 0.0 {
+            -c / self.linear
+        } else {
+            f32::INFINITY
+        };
+        // Squared in the shader, so keep it from overflowing
+        range.clamp(0.0, f32::MAX.sqrt())
+    }
+
+    /// The shadow settings the light is rendered with.
+    /// Directional lights have no shadow maps, so their settings are ignored (with a warning, once).
+    pub fn shadow_settings(&self) -> Option<ShadowSettings> {
+        static WARN_DIRECTIONAL: std::sync::Once = std::sync::Once::new();
+
+        let shadows = self.shadows?;
+        if self.light_type == LightType::Directional {
+            WARN_DIRECTIONAL.call_once(|| {
+                log::warn!("directional lights can't cast shadows, ignoring their shadow settings");
+            });
+            return None;
+        }
+        Some(shadows)
+    }
+
+    pub fn prepare_light(
+        &self,
+        transform: &components::GlobalTransform,
+        shadow_index: Option<u32>,
+        resources: &mut scene::PrepareResources<'_>,
+    ) {
+        let shadows = self.shadows.unwrap_or_default();
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
         let prepared_light = PreparedLight {
             constant: self.constant,
             linear: self.linear,
             quadratic: self.quadratic,
-            ambient: self.ambient,
             diffuse: self.diffuse,
             specular: self.specular,
             position,
+            shadow_index: shadow_index.map_or(-1, |i| i as i32),
+            shadow_far: shadows.far,
+            shadow_bias: shadows.bias,
+            direction: rotation * glam::Vec3::NEG_Z,
+            light_type: self.light_type as u32,
+            inner_cone_cos: self.inner_cone_angle.cos(),
+            outer_cone_cos: self.outer_cone_angle.cos(),
+            range: self.range(),
         };
         resources.lights.push(&prepared_light);
     }
@@ -15,61 +15,159 @@
 // You should have received a copy of the GNU General Public License
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::assets;
+use crate::components;
 use crate::render;
 use crate::scene;
 
 use bevy_ecs::prelude::*;
 
+use itertools::Itertools;
+
 #[derive(Debug)]
 #[derive(Component)]
 pub struct MeshRenderer {
     pub mesh_index: scene::MeshIndex,
+    // The model (and mesh of that model) this was created from, if any
+    pub source: Option<(assets::ModelId, usize)>,
 }
 
 pub struct PreparedMesh {
-    instance_index: u32,
-
-    index_count: u32,
-    index_offset: u32,
+    mesh_index: scene::MeshIndex,
+    transform_index: u32,
+    // World space position, used to sort transparent meshes
+    position: glam::Vec3,
 }
 
 impl MeshRenderer {
     pub fn new(meshes: &mut scene::Meshes, mesh: std::sync::Arc<render::Mesh>) -> Self {
         let mesh_index = meshes.upload_mesh(mesh);
-        Self { mesh_index }
+        Self {
+            mesh_index,
+            source: None,
+        }
+    }
+
+    pub fn from_model(
+        meshes: &mut scene::Meshes,
+        models: &assets::Models,
+        model_id: assets::ModelId,
+        mesh: usize,
+    ) -> Self {
+        let model = models.get_expect(model_id);
+        let mesh_index = meshes.upload_mesh(model.meshes[mesh].clone());
+        Self {
+            mesh_index,
+            source: Some((model_id, mesh)),
+        }
     }
 
     pub fn prepare(
         &self,
+        transform: &components::GlobalTransform,
         transform_index: u32,
-        resources: &mut scene::PrepareResources<'_>,
     ) -> PreparedMesh {
-        let instance = render::MeshInstance::from_mesh_transform_indices_with_materials(
-            self.mesh_index,
-            transform_index,
-            &resources.assets.materials,
-        );
-        let instance_index = resources.instances.push(instance) as u32;
         PreparedMesh {
-            instance_index,
-            index_count: self.mesh_index.index_count as u32,
-            index_offset: self.mesh_index.index_offset as u32,
+            mesh_index: self.mesh_index,
+            transform_index,
+            position: transform.translation(),
         }
     }
 }
 
 impl PreparedMesh {
-    pub fn draw(self, render_pass: &mut wgpu::RenderPass<'_>) {
-        render_pass.push_debug_group("wormhole object draw");
+    /// Pushes draws for every prepared mesh.
+    ///
+    /// Opaque meshes are sorted by pipeline, mesh and material, and one instanced indirect draw is pushed
+    /// for every run of identical meshes so they end up in a contiguous range of instances.
+    /// Alpha blended meshes can't be batched, as they have to be drawn back to front from `view_pos`.
+    ///
+    /// Returns the range of opaque draws that use each pipeline.
+    pub fn push_batched(
+        prepared: Vec<PreparedMesh>,
+        view_pos: glam::Vec3,
+        resources: &mut scene::PrepareResources<'_>,
+    ) -> Vec<(render::material::Pipeline, std::ops::Range<u32>)> {
+        let assets = resources.assets;
+        let materials = &assets.materials;
+        let (mut transparent, opaque): (Vec<_>, Vec<_>) = prepared.into_iter().partition(|p| {
+            materials
+                .get(p.mesh_index.material_id)
+                .is_some_and(|m| m.alpha_mode == render::AlphaMode::Blend)
+        });
+
+        // Meshes whose material has no pipeline yet are skipped
+        let mut opaque = opaque
+            .into_iter()
+            .filter_map(|p| Some((materials.pipeline(p.mesh_index.material_id)?, p)))
+            .collect_vec();
+        opaque.sort_unstable_by_key(|(pipeline, p)| (*pipeline, p.mesh_index, p.transform_index));
 
-        let index_start = self.index_offset / std::mem::size_of::<u32>() as u32;
-        let index_end = index_start + self.index_count;
+        let mut pipeline_draws: Vec<(render::material::Pipeline, std::ops::Range<u32>)> =
+            Vec::new();
+        for ((pipeline, mesh_index), batch) in &opaque
+            .into_iter()
+            .group_by(|(pipeline, p)| (*pipeline, p.mesh_index))
+        {
+            let mut first_instance = None;
+            let mut instance_count = 0;
 
-        let instance_start = self.instance_index;
-        let instance_end = self.instance_index + 1;
+            for (_, prepared) in batch {
+                let instance_index = prepared.push_instance(resources);
+                first_instance.get_or_insert(instance_index);
+                instance_count += 1;
+            }
 
-        render_pass.draw_indexed(index_start..index_end, 0, instance_start..instance_end);
+            let Some(first_instance) = first_instance else {
+                continue;
+            };
+
+            let draw_index =
+                resources
+                    .object_draws
+                    .push(draw_args(mesh_index, first_instance, instance_count));
+            match pipeline_draws.last_mut() {
+                Some((last, draws)) if *last == pipeline => draws.end = draw_index + 1,
+                _ => pipeline_draws.push((pipeline, draw_index..draw_index + 1)),
+            }
+        }
+
+        transparent.sort_by(|a, b| {
+            let a = a.position.distance_squared(view_pos);
+            let b = b.position.distance_squared(view_pos);
+            b.total_cmp(&a)
+        });
+
+        for prepared in transparent {
+            let instance_index = prepared.push_instance(resources);
+            resources
+                .transparent_draws
+                .push(draw_args(prepared.mesh_index, instance_index, 1));
+        }
+
+        pipeline_draws
+    }
+
+    fn push_instance(&self, resources: &mut scene::PrepareResources<'_>) -> u32 {
+        let instance = render::MeshInstance::from_mesh_transform_indices_with_materials(
+            self.mesh_index,
+            self.transform_index,
+            &resources.assets.materials,
+        );
+        resources.instances.push(instance) as u32
+    }
+}
 
-        render_pass.pop_debug_group();
+fn draw_args(
+    mesh_index: scene::MeshIndex,
+    first_instance: u32,
+    instance_count: u32,
+) -> wgpu::util::DrawIndexedIndirectArgs {
+    wgpu::util::DrawIndexedIndirectArgs {
+        index_count: mesh_index.index_count as u32,
+        instance_count,
+        first_index: mesh_index.index_offset as u32 / std::mem::size_of::<u32>() as u32,
+        base_vertex: 0,
+        first_instance,
     }
 }
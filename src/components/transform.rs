@@ -35,6 +35,13 @@ impl Default for Transform {
     }
 }
 
+/// The world space transform of an entity, computed from its [`Transform`] and the transforms of its parents.
+///
+/// Updated by [`super::hierarchy::propagate_transforms`] in `PostUpdate`, so changes to [`Transform`] show up here one schedule later.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Component)]
+pub struct GlobalTransform(glam::Affine3A);
+
 #[derive(encase::ShaderType, Debug)]
 pub struct Data {
     obj_proj: glam::Mat4,
@@ -168,3 +175,75 @@ impl encase::internal::WriteInto for Transform {
         data.write_into(writer)
     }
 }
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self(glam::Affine3A::IDENTITY)
+    }
+}
+
+impl From<Transform> for GlobalTransform {
+    fn from(value: Transform) -> Self {
+        Self(glam::Affine3A::from_scale_rotation_translation(
+            value.scale,
+            value.rotation,
+            value.position,
+        ))
+    }
+}
+
+impl GlobalTransform {
+    pub fn affine(&self) -> glam::Affine3A {
+        self.0
+    }
+
+    pub fn compute_matrix(&self) -> glam::Mat4 {
+        glam::Mat4::from(self.0)
+    }
+
+    pub fn translation(&self) -> glam::Vec3 {
+        self.0.translation.into()
+    }
+
+    pub fn to_scale_rotation_translation(&self) -> (glam::Vec3, glam::Quat, glam::Vec3) {
+        self.0.to_scale_rotation_translation()
+    }
+
+    /// Applies a local transform on top of this one, like a parent transform would be applied to a child.
+    pub fn mul_transform(&self, transform: Transform) -> Self {
+        Self(self.0 * GlobalTransform::from(transform).0)
+    }
+
+    /// Computes the local transform that would place an entity at this global transform when attached to `parent`.
+    pub fn reparented_to(&self, parent: &GlobalTransform) -> Transform {
+        let relative = parent.0.inverse() * self.0;
+        let (scale, rotation, position) = relative.to_scale_rotation_translation();
+        Transform {
+            position,
+            rotation,
+            scale,
+        }
+    }
+}
+
+impl encase::ShaderSize for GlobalTransform {}
+
+impl encase::ShaderType for GlobalTransform {
+    type ExtraMetadata = <Data as encase::ShaderType>::ExtraMetadata;
+    const METADATA: encase::private::Metadata<Self::ExtraMetadata> =
+        <Data as encase::ShaderType>::METADATA;
+}
+
+impl encase::internal::WriteInto for GlobalTransform {
+    fn write_into<B>(&self, writer: &mut encase::internal::Writer<B>)
+    where
+        B: encase::internal::BufferMut,
+    {
+        let (_, rotation, _) = self.to_scale_rotation_translation();
+        let data = Data {
+            obj_proj: self.compute_matrix(),
+            normal_proj: glam::Mat4::from_quat(rotation),
+        };
+        data.write_into(writer)
+    }
+}
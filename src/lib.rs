@@ -19,7 +19,14 @@
 
 pub mod assets {
     mod loader;
-    pub use loader::Loader;
+    pub use loader::{finish_loads, Handle, LoadState, Loader};
+
+    mod worker;
+
+    mod error;
+    pub use error::Error;
+
+    pub mod hot_reload;
 
     mod gltf;
     pub use gltf::File as GltfFile;
@@ -42,14 +49,31 @@ pub mod assets {
     use crate::render;
     use crate::scene;
 
-    pub fn init_into(render_state: &render::State, builder: &mut scene::WorldBuilder) {
-        builder.insert_resource(Loader::new(render_state));
+    pub struct Plugin;
+
+    impl scene::Plugin for Plugin {
+        fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+            let loader = Loader::new(builder.resource::<render::State>());
+            builder
+                .insert_resource(loader)
+                .add_systems(scene::First, finish_loads);
+        }
+
+        fn dependencies(&self) -> Vec<scene::PluginId> {
+            vec![
+                scene::PluginId::of::<scene::MainSchedulePlugin>(),
+                scene::PluginId::of::<render::Plugin>(),
+            ]
+        }
     }
 }
 
 pub mod components {
     mod transform;
-    pub use transform::Transform;
+    pub use transform::{GlobalTransform, Transform};
+
+    pub mod hierarchy;
+    pub use hierarchy::{Children, HierarchyCommands, Parent};
 
     pub mod light;
     pub use light::Light;
@@ -78,11 +102,24 @@ pub mod input {
     pub use state::State;
 
     mod systems;
+    pub use systems::InputSystem;
 
     use crate::scene;
     use bevy_ecs::prelude::*;
 
-    pub fn init_into(builder: &mut scene::WorldBuilder) {
+    pub struct Plugin;
+
+    impl scene::Plugin for Plugin {
+        fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+            init_into(builder);
+        }
+
+        fn dependencies(&self) -> Vec<scene::PluginId> {
+            vec![scene::PluginId::of::<scene::MainSchedulePlugin>()]
+        }
+    }
+
+    fn init_into(builder: &mut scene::WorldBuilder) {
         builder
             .insert_resource(State::new())
             .add_event::<KeyboardEvent>()
@@ -121,7 +158,22 @@ pub mod physics {
     use crate::scene;
     use bevy_ecs::prelude::*;
 
-    pub fn init_into(builder: &mut scene::WorldBuilder) {
+    pub struct Plugin;
+
+    impl scene::Plugin for Plugin {
+        fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+            init_into(builder);
+        }
+
+        fn dependencies(&self) -> Vec<scene::PluginId> {
+            vec![
+                scene::PluginId::of::<scene::MainSchedulePlugin>(),
+                scene::PluginId::of::<crate::time::Plugin>(),
+            ]
+        }
+    }
+
+    fn init_into(builder: &mut scene::WorldBuilder) {
         builder
             .insert_resource(State::new())
             .configure_sets(
@@ -145,11 +197,33 @@ pub mod render {
         pub mod geometry;
 
         pub mod instances;
+
+        pub mod indirect;
+
+        pub mod light_tiles;
+
+        pub mod hdr;
     }
 
     pub mod binding_helpers;
     pub use binding_helpers::{BindGroupBuilder, BindGroupLayoutBuilder};
 
+    pub mod capture;
+
+    pub mod shadow;
+
+    pub mod tonemap;
+
+    pub mod post;
+
+    pub mod environment;
+
+    pub mod skybox;
+
+    pub mod mipmap;
+
+    pub mod compressed;
+
     mod color;
     pub use color::Color;
 
@@ -157,37 +231,102 @@ pub mod render {
     pub use instance::MeshInstance;
 
     mod mesh;
-    pub use mesh::Mesh;
     pub use mesh::VertexFormat;
+    pub use mesh::{Mesh, MeshParts};
 
     pub mod state;
     pub use state::State;
 
     pub mod texture;
+    pub use texture::SamplerSettings;
     pub use texture::Texture;
     pub use texture::TextureFormat;
 
     pub mod traits;
 
     pub mod material;
-    pub use material::Material;
+    pub use material::{AlphaMode, CustomMaterial, Hologram, Material};
 
     pub mod system;
 
     use crate::scene;
+    use bevy_ecs::prelude::*;
 
-    pub fn init_into(render_state: State, builder: &mut scene::WorldBuilder) {
-        builder
-            .insert_resource(render_state)
-            .add_systems(scene::Update, system::render);
+    /// Owns the render state, and sets up the scene gpu buffers and the render system.
+    pub struct Plugin {
+        render_state: State,
+    }
+
+    impl Plugin {
+        pub fn new(render_state: State) -> Self {
+            Self { render_state }
+        }
+    }
+
+    impl scene::Plugin for Plugin {
+        fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+            let render_state = self.render_state;
+
+            builder
+                .insert_resource(scene::Meshes::new(&render_state))
+                .insert_resource(scene::Buffers::new(&render_state))
+                // Roughly the flat ambient light used before environments existed
+                .insert_resource(environment::Environment::uniform(
+                    &render_state,
+                    glam::Vec3::splat(0.03),
+                ))
+                .insert_resource(render_state)
+                .init_resource::<tonemap::Settings>()
+                .init_resource::<post::Settings>()
+                // Input is read in FixedUpdate, so window resizes are only known there
+                .add_systems(
+                    scene::FixedUpdate,
+                    system::resize
+                        .after(crate::input::InputSystem)
+                        .run_if(resource_exists::<crate::input::State>),
+                )
+                // Rendering happens in Last so it sees the transforms propagated during PostUpdate.
+                // It also needs assets and a camera, both of which come from other (optional) plugins
+                .add_systems(
+                    scene::Last,
+                    (
+                        scene::track_mesh_renderers,
+                        system::prepare_pipelines,
+                        system::render.run_if(
+                            resource_exists::<crate::assets::Loader>
+                                .and_then(resource_exists::<crate::player::Player>),
+                        ),
+                    )
+                        .chain(),
+                );
+        }
+
+        fn dependencies(&self) -> Vec<scene::PluginId> {
+            vec![
+                scene::PluginId::of::<scene::MainSchedulePlugin>(),
+                scene::PluginId::of::<crate::components::hierarchy::Plugin>(),
+            ]
+        }
     }
 }
 
 pub mod scene;
 
 pub mod shaders {
+    pub mod environment;
     pub mod light;
+    pub mod mipmap;
     pub mod object;
+    pub mod post;
+    pub mod shadow;
+    pub mod skybox;
+    pub mod tonemap;
+
+    pub mod registry;
+    pub use registry::{PipelineKey, Registry, ShaderDefs};
+
+    mod source;
+    pub use source::{source, use_source_dir, uses_source_dir, SOURCE_DIR};
 }
 
 pub mod time;
@@ -28,6 +28,7 @@ fn main() {
     let render_state = pollster::block_on(wormhole::render::State::new(window.clone()));
 
     let mut scene = wormhole::scene::Scene::new(render_state);
+    spawn_hologram(&mut scene.world);
 
     let mut event_writers_system_state: SystemState<wormhole::input::EventWriters<'_>> =
         SystemState::from_world(&mut scene.world);
@@ -110,3 +111,35 @@ fn main() {
         eprintln!("event loop error {e}");
     }
 }
+
+/// Spawns a copy of the demo cube drawn with a custom material.
+fn spawn_hologram(world: &mut World) {
+    let mut system_state = SystemState::<(
+        ResMut<'_, wormhole::assets::Loader>,
+        ResMut<'_, wormhole::scene::Meshes>,
+        Commands<'_, '_>,
+    )>::from_world(world);
+    let (mut assets, mut meshes, mut commands) = system_state.get_mut(world);
+
+    let material_id = wormhole::assets::MaterialId::from_path("hologram");
+    assets
+        .materials
+        .insert_custom(material_id, wormhole::render::Hologram::default());
+
+    let model_id = wormhole::assets::ModelId::from_path("assets/meshes/cube.obj");
+    let Some(cube) = assets.models.get(model_id).and_then(|m| m.meshes.first()) else {
+        log::warn!("the demo cube isn't loaded, not spawning a hologram");
+        return;
+    };
+    let mut mesh = wormhole::render::Mesh::clone(cube);
+    mesh.material_id = material_id;
+
+    commands.spawn((
+        wormhole::components::Transform {
+            position: glam::vec3(4.0, -2.0, 0.0),
+            ..Default::default()
+        },
+        wormhole::components::MeshRenderer::new(&mut meshes, std::sync::Arc::new(mesh)),
+    ));
+    system_state.apply(world);
+}
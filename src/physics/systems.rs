@@ -37,15 +37,37 @@ pub fn step(mut physics_state: ResMut<'_, physics::State>, time: Res<'_, time::T
 
 pub fn write_back_rigid_bodies(
     physics_state: ResMut<'_, physics::State>,
-    mut query: Query<&mut components::Transform, With<physics::RigidBody>>,
+    mut query: Query<
+        (
+            &mut components::Transform,
+            Option<&components::hierarchy::Parent>,
+        ),
+        With<physics::RigidBody>,
+    >,
+    global_query: Query<&components::GlobalTransform>,
 ) {
     for (_, rigid_body) in physics_state.rigid_body_set.iter() {
         let entity = Entity::from_bits(rigid_body.user_data as u64);
-        if let Ok(mut transform) = query.get_mut(entity) {
+        if let Ok((mut transform, parent)) = query.get_mut(entity) {
             let translation = rigid_body.translation();
             let rotation = rigid_body.rotation();
-            transform.position = glam::vec3(translation.x, translation.y, translation.z);
-            transform.rotation = glam::quat(rotation.i, rotation.j, rotation.k, rotation.w);
+            let position = glam::vec3(translation.x, translation.y, translation.z);
+            let rotation = glam::quat(rotation.i, rotation.j, rotation.k, rotation.w);
+
+            // Rigid bodies are simulated in world space, so attached bodies need to be moved back into their parent's space
+            let parent_global = parent.and_then(|p| global_query.get(p.get()).ok());
+            if let Some(parent_global) = parent_global {
+                let (parent_scale, _, _) = parent_global.to_scale_rotation_translation();
+                let global = components::GlobalTransform::from(components::Transform {
+                    position,
+                    rotation,
+                    scale: parent_scale * transform.scale,
+                });
+                *transform = global.reparented_to(parent_global);
+            } else {
+                transform.position = position;
+                transform.rotation = rotation;
+            }
         }
     }
 }
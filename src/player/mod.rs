@@ -31,8 +31,7 @@ pub struct Player {
 }
 
 impl Player {
-    pub fn new(render_state: &render::State) -> Self {
-        let camera = components::Camera::new(render_state);
+    pub fn new(camera: components::Camera) -> Self {
         let transform = components::Transform::from_position_rotation(
             glam::vec3(0.0, 0.0, 10.0),
             glam::Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0),
@@ -42,8 +41,26 @@ impl Player {
     }
 }
 
-pub fn init_into(render_state: &render::State, builder: &mut scene::WorldBuilder) {
-    builder
-        .insert_resource(Player::new(render_state))
-        .add_systems(FixedUpdate, systems::movement);
+pub struct Plugin;
+
+impl scene::Plugin for Plugin {
+    fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+        // The render plugin is optional, so fall back to a default aspect ratio if it's not around (yet).
+        // The render plugin's resize system picks up the real aspect ratio once it is
+        let camera = builder
+            .get_resource::<render::State>()
+            .map_or_else(components::Camera::default, components::Camera::new);
+
+        builder
+            .insert_resource(Player::new(camera))
+            .add_systems(FixedUpdate, systems::movement);
+    }
+
+    fn dependencies(&self) -> Vec<scene::PluginId> {
+        vec![
+            scene::PluginId::of::<scene::MainSchedulePlugin>(),
+            scene::PluginId::of::<crate::time::Plugin>(),
+            scene::PluginId::of::<crate::input::Plugin>(),
+        ]
+    }
 }
@@ -17,8 +17,6 @@
 
 use crate::input;
 use crate::player;
-use crate::render;
-use crate::scene;
 use crate::time;
 
 use bevy_ecs::prelude::*;
@@ -28,20 +26,9 @@ use winit::keyboard::KeyCode;
 pub fn movement(
     input_state: Res<input::State>,
     time: Res<time::Time<time::Fixed>>,
-    mut render_state: ResMut<render::State>,
-    mut scene_buffers: ResMut<scene::Buffers>,
     mut exit_event_writer: EventWriter<input::Exit>,
     mut player: ResMut<player::Player>,
 ) {
-    if let Some(size) = input_state.new_window_size() {
-        render_state.resize(size);
-    }
-
-    if let Some(size) = input_state.new_window_size() {
-        scene_buffers.gbuffer.resize_to_screen(&render_state);
-        player.camera.aspect = size.width as f32 / size.height as f32;
-    }
-
     let forward = player.transform.forward();
     let left = player.transform.left();
 
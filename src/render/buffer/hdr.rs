@@ -0,0 +1,102 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+/// The HDR target lighting is rendered into, along with the state auto exposure keeps between frames.
+pub struct Buffer {
+    pub sampler: wgpu::Sampler,
+    pub color: render::Texture,
+    // Adapted luminance and the resulting exposure, only written to by the gpu
+    pub exposure_state: wgpu::Buffer,
+
+    pub tonemap_bind_group: wgpu::BindGroup,
+    pub exposure_bind_group: wgpu::BindGroup,
+}
+
+impl Buffer {
+    pub fn new(render_state: &render::State) -> Self {
+        let sampler = render_state
+            .wgpu
+            .device
+            .create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("hdr sampler"),
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+        let color = render::Texture::new_screen_size(render_state, render::TextureFormat::HDR);
+        let exposure_state = render_state
+            .wgpu
+            .device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some("wormhole exposure state buffer"),
+                size: 16,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            });
+
+        let (tonemap_bind_group, exposure_bind_group) =
+            create_bind_groups(render_state, &sampler, &color, &exposure_state);
+
+        Self {
+            sampler,
+            color,
+            exposure_state,
+
+            tonemap_bind_group,
+            exposure_bind_group,
+        }
+    }
+
+    pub fn resize_to_screen(&mut self, render_state: &render::State) {
+        self.color.resize_to_screen(render_state);
+
+        (self.tonemap_bind_group, self.exposure_bind_group) = create_bind_groups(
+            render_state,
+            &self.sampler,
+            &self.color,
+            &self.exposure_state,
+        );
+    }
+}
+
+fn create_bind_groups(
+    render_state: &render::State,
+    sampler: &wgpu::Sampler,
+    color: &render::Texture,
+    exposure_state: &wgpu::Buffer,
+) -> (wgpu::BindGroup, wgpu::BindGroup) {
+    let tonemap = render::BindGroupBuilder::new()
+        .append_sampler(sampler)
+        .append_texture_view(&color.view)
+        .append_buffer(exposure_state)
+        .build(
+            &render_state.wgpu.device,
+            Some("wormhole tonemap bind group"),
+            &render_state.bind_groups.tonemap,
+        );
+    let exposure = render::BindGroupBuilder::new()
+        .append_texture_view(&color.view)
+        .append_buffer(exposure_state)
+        .build(
+            &render_state.wgpu.device,
+            Some("wormhole exposure bind group"),
+            &render_state.bind_groups.exposure,
+        );
+    (tonemap, exposure)
+}
@@ -0,0 +1,103 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+/// Holds one [`wgpu::util::DrawIndexedIndirectArgs`] per draw, to be submitted with `multi_draw_indexed_indirect`.
+pub struct Buffer {
+    indirect_buffer: wgpu::Buffer,
+}
+
+pub struct Writer<'buf> {
+    cpu_indirect_buffer: Vec<u8>,
+    draw_count: u32,
+
+    internal: &'buf mut Buffer,
+}
+
+impl Buffer {
+    pub fn new(render_state: &render::State, usage: wgpu::BufferUsages) -> Self {
+        let indirect_buffer_size =
+            std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() * 1024;
+
+        let indirect_buffer = render_state
+            .wgpu
+            .device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some("wormhole scene indirect buffer"),
+                size: indirect_buffer_size as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDIRECT | usage,
+                mapped_at_creation: false,
+            });
+
+        Self { indirect_buffer }
+    }
+
+    pub fn start_write(&mut self) -> Writer<'_> {
+        Writer {
+            cpu_indirect_buffer: Vec::with_capacity(self.indirect_buffer.size() as usize),
+            draw_count: 0,
+
+            internal: self,
+        }
+    }
+}
+
+impl<'buf> Writer<'buf> {
+    pub fn push(&mut self, args: wgpu::util::DrawIndexedIndirectArgs) -> u32 {
+        let draw_index = self.draw_count;
+
+        self.cpu_indirect_buffer.extend(args.as_bytes());
+        self.draw_count += 1;
+
+        draw_index
+    }
+
+    pub fn draw_count(&self) -> u32 {
+        self.draw_count
+    }
+
+    pub fn finish(self, render_state: &render::State) -> &'buf wgpu::Buffer {
+        if self.internal.indirect_buffer.size()
+            < self.cpu_indirect_buffer.len() as wgpu::BufferAddress
+        {
+            let size = self.cpu_indirect_buffer.len();
+            let size = (size / 2 + size) as wgpu::BufferAddress; // Multiply by 1.5
+
+            let new_indirect_buffer =
+                render_state
+                    .wgpu
+                    .device
+                    .create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("wormhole scene indirect buffer"),
+                        size,
+                        usage: self.internal.indirect_buffer.usage(),
+                        mapped_at_creation: false,
+                    });
+
+            self.internal.indirect_buffer = new_indirect_buffer;
+        }
+
+        render_state.wgpu.queue.write_buffer(
+            &self.internal.indirect_buffer,
+            0,
+            &self.cpu_indirect_buffer,
+        );
+
+        &self.internal.indirect_buffer
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+// Must match the constants in lights.wgsl
+pub const TILE_SIZE: u32 = 16;
+pub const TILE_STRIDE: u32 = 256;
+
+/// Per screen tile lists of the lights affecting that tile, filled in by the light culling pass.
+/// Every tile takes up [`TILE_STRIDE`] u32s: the light count followed by the light indices.
+pub struct Buffer {
+    pub tile_light_indices: wgpu::Buffer,
+}
+
+impl Buffer {
+    pub fn new(render_state: &render::State) -> Self {
+        Self {
+            tile_light_indices: create_tile_buffer(render_state),
+        }
+    }
+
+    pub fn tile_count(render_state: &render::State) -> (u32, u32) {
+        let config = &render_state.wgpu.surface_config;
+        (
+            config.width.div_ceil(TILE_SIZE),
+            config.height.div_ceil(TILE_SIZE),
+        )
+    }
+
+    // Only ever grows the buffer, shrinking the window keeps using the old one
+    pub fn resize_to_screen(&mut self, render_state: &render::State) {
+        if self.tile_light_indices.size() < tile_buffer_size(render_state) {
+            self.tile_light_indices = create_tile_buffer(render_state);
+        }
+    }
+}
+
+fn tile_buffer_size(render_state: &render::State) -> wgpu::BufferAddress {
+    let (x, y) = Buffer::tile_count(render_state);
+    // Make sure there's always room for at least one tile
+    let tiles = (x * y).max(1) as wgpu::BufferAddress;
+    tiles * TILE_STRIDE as wgpu::BufferAddress * std::mem::size_of::<u32>() as wgpu::BufferAddress
+}
+
+fn create_tile_buffer(render_state: &render::State) -> wgpu::Buffer {
+    render_state
+        .wgpu
+        .device
+        .create_buffer(&wgpu::BufferDescriptor {
+            label: Some("wormhole light tile buffer"),
+            size: tile_buffer_size(render_state),
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+}
@@ -0,0 +1,124 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+/// Copies a texture back to the cpu and converts it into an image.
+/// Blocks until the gpu has finished all submitted work.
+///
+/// Only 8 bit rgba/bgra textures are supported, and the texture must have been created with `COPY_SRC`.
+pub fn read_texture(render_state: &render::State, texture: &wgpu::Texture) -> image::RgbaImage {
+    let format = texture.format();
+    assert!(
+        matches!(
+            format,
+            wgpu::TextureFormat::Rgba8Unorm
+                | wgpu::TextureFormat::Rgba8UnormSrgb
+                | wgpu::TextureFormat::Bgra8Unorm
+                | wgpu::TextureFormat::Bgra8UnormSrgb
+        ),
+        "unsupported texture format for readback {format:?}"
+    );
+
+    let width = texture.width();
+    let height = texture.height();
+
+    // Rows copied out of a texture need to be padded to COPY_BYTES_PER_ROW_ALIGNMENT
+    let unpadded_bytes_per_row = width * 4;
+    let padded_bytes_per_row =
+        wgpu::util::align_to(unpadded_bytes_per_row, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let readback_buffer = render_state
+        .wgpu
+        .device
+        .create_buffer(&wgpu::BufferDescriptor {
+            label: Some("wormhole texture readback buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+    let mut encoder =
+        render_state
+            .wgpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("texture readback encoder"),
+            });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback_buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_state
+        .wgpu
+        .queue
+        .submit(std::iter::once(encoder.finish()));
+
+    let slice = readback_buffer.slice(..);
+    let (sender, receiver) = crossbeam::channel::bounded(1);
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    render_state.wgpu.device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .expect("readback buffer map callback dropped")
+        .expect("failed to map readback buffer");
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let mapped = slice.get_mapped_range();
+        for row in mapped.chunks_exact(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    readback_buffer.unmap();
+
+    if matches!(
+        format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    ) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    image::RgbaImage::from_raw(width, height, pixels).expect("readback buffer too small")
+}
+
+/// Reads back the offscreen render target of a headless render state.
+/// Returns `None` if the render state is presenting to a window surface.
+pub fn read_render_target(render_state: &render::State) -> Option<image::RgbaImage> {
+    match &render_state.wgpu.target {
+        render::state::RenderTarget::Offscreen(texture) => {
+            Some(read_texture(render_state, texture))
+        }
+        render::state::RenderTarget::Surface(_) => None,
+    }
+}
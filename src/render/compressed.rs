@@ -0,0 +1,306 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Read;
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ktx2::ParseError),
+    UnsupportedFormat(Option<ktx2::Format>),
+    UnsupportedSupercompression(ktx2::SupercompressionScheme),
+    // Only plain 2d textures are supported (no arrays, cubemaps, or 3d textures)
+    UnsupportedDimensions,
+    // Zero sized, or a block compressed texture that isn't a whole number of blocks
+    InvalidSize(wgpu::Extent3d),
+    // Larger than the device's max_texture_dimension_2d
+    TooLarge {
+        size: wgpu::Extent3d,
+        max: u32,
+    },
+    TooManyLevels(usize),
+    // The level's byte length doesn't match its format and extent
+    InvalidLevelLength {
+        level: usize,
+        expected: usize,
+        actual: usize,
+    },
+    Zstd(std::io::Error),
+    Decode(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "failed to parse ktx2 file: {e}"),
+            Error::UnsupportedFormat(format) => write!(f, "unsupported ktx2 format {format:?}"),
+            Error::UnsupportedSupercompression(scheme) => {
+                write!(f, "unsupported ktx2 supercompression scheme {scheme:?}")
+            }
+            Error::UnsupportedDimensions => write!(f, "only 2d ktx2 textures are supported"),
+            Error::InvalidSize(size) => {
+                write!(f, "invalid ktx2 size {}x{}", size.width, size.height)
+            }
+            Error::TooLarge { size, max } => write!(
+                f,
+                "ktx2 texture is {}x{}, larger than the maximum of {max}",
+                size.width, size.height
+            ),
+            Error::TooManyLevels(count) => write!(f, "ktx2 file has too many mip levels ({count})"),
+            Error::InvalidLevelLength {
+                level,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "ktx2 level {level} is {actual} bytes long, expected {expected}"
+            ),
+            Error::Zstd(e) => write!(f, "failed to decompress ktx2 level: {e}"),
+            Error::Decode(e) => write!(f, "failed to decode compressed texture: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::Zstd(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ktx2::ParseError> for Error {
+    fn from(value: ktx2::ParseError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+/// The texture format and level data of a ktx2 file, with any supercompression undone.
+pub struct Ktx2 {
+    pub format: wgpu::TextureFormat,
+    pub size: wgpu::Extent3d,
+    // Ordered from the largest level to the smallest
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2 {
+    /// Parses a ktx2 file, decoding it on the cpu if `features` don't allow sampling its format directly.
+    /// `max_dimension` is the device's `max_texture_dimension_2d`.
+    pub fn load(bytes: &[u8], features: wgpu::Features, max_dimension: u32) -> Result<Self, Error> {
+        let mut ktx2 = Self::parse(bytes, max_dimension)?;
+        if !features.contains(ktx2.format.required_features()) {
+            ktx2.decompress()?;
+        }
+        Ok(ktx2)
+    }
+
+    /// Parses a ktx2 file, checking its size and level lengths so it can be uploaded as is.
+    // u32::is_multiple_of needs rust 1.87
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    pub fn parse(bytes: &[u8], max_dimension: u32) -> Result<Self, Error> {
+        let reader = ktx2::Reader::new(bytes)?;
+        let header = reader.header();
+
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            return Err(Error::UnsupportedDimensions);
+        }
+
+        let format = header
+            .format
+            .and_then(wgpu_format)
+            .ok_or(Error::UnsupportedFormat(header.format))?;
+
+        let size = wgpu::Extent3d {
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let (block_width, block_height) = format.block_dimensions();
+        if size.width == 0 || size.width % block_width != 0 || size.height % block_height != 0 {
+            return Err(Error::InvalidSize(size));
+        }
+        if size.width > max_dimension || size.height > max_dimension {
+            return Err(Error::TooLarge {
+                size,
+                max: max_dimension,
+            });
+        }
+
+        let levels = reader
+            .levels()
+            .map(|level| match header.supercompression_scheme {
+                None => Ok(level.to_vec()),
+                Some(ktx2::SupercompressionScheme::Zstandard) => {
+                    let mut decoder = ruzstd::StreamingDecoder::new(level)
+                        .map_err(|e| Error::Zstd(std::io::Error::other(e)))?;
+                    let mut data = Vec::new();
+                    decoder.read_to_end(&mut data).map_err(Error::Zstd)?;
+                    Ok(data)
+                }
+                Some(scheme) => Err(Error::UnsupportedSupercompression(scheme)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if levels.len() > size.max_mips(wgpu::TextureDimension::D2) as usize {
+            return Err(Error::TooManyLevels(levels.len()));
+        }
+        for (mip_level, level) in levels.iter().enumerate() {
+            let expected = level_length(format, size, mip_level as u32);
+            if level.len() != expected {
+                return Err(Error::InvalidLevelLength {
+                    level: mip_level,
+                    expected,
+                    actual: level.len(),
+                });
+            }
+        }
+
+        Ok(Self {
+            format,
+            size,
+            levels,
+        })
+    }
+
+    /// Decodes every level to rgba8 on the cpu, for devices without support for the compressed format.
+    /// Returns the uncompressed format the levels should be uploaded as.
+    ///
+    /// HDR and signed formats lose their range, as everything is decoded to unsigned 8 bit channels.
+    pub fn decompress(&mut self) -> Result<wgpu::TextureFormat, Error> {
+        use wgpu::TextureFormat as F;
+
+        let decode = match self.format {
+            F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => texture2ddecoder::decode_bc1a,
+            F::Bc2RgbaUnorm | F::Bc2RgbaUnormSrgb => decode_bc2,
+            F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => texture2ddecoder::decode_bc3,
+            F::Bc4RUnorm | F::Bc4RSnorm => texture2ddecoder::decode_bc4,
+            F::Bc5RgUnorm | F::Bc5RgSnorm => texture2ddecoder::decode_bc5,
+            F::Bc6hRgbUfloat => texture2ddecoder::decode_bc6_unsigned,
+            F::Bc6hRgbFloat => texture2ddecoder::decode_bc6_signed,
+            F::Bc7RgbaUnorm | F::Bc7RgbaUnormSrgb => texture2ddecoder::decode_bc7,
+            // Already uncompressed
+            format => return Ok(format),
+        };
+
+        for (mip_level, level) in self.levels.iter_mut().enumerate() {
+            let size = self
+                .size
+                .mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+            let (width, height) = (size.width as usize, size.height as usize);
+
+            let mut pixels = vec![0; width * height];
+            decode(level, width, height, &mut pixels).map_err(Error::Decode)?;
+
+            // texture2ddecoder outputs bgra
+            *level = pixels
+                .into_iter()
+                .flat_map(|pixel| {
+                    let [b, g, r, a] = pixel.to_le_bytes();
+                    [r, g, b, a]
+                })
+                .collect();
+        }
+
+        let format = if self.format.is_srgb() {
+            F::Rgba8UnormSrgb
+        } else {
+            F::Rgba8Unorm
+        };
+        self.format = format;
+
+        Ok(format)
+    }
+}
+
+/// texture2ddecoder has no bc2 decoder, so this decodes the color half of each block as bc1
+/// and replaces the alpha with the block's explicit 4 bit alpha. Outputs bgra like texture2ddecoder.
+fn decode_bc2(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    image: &mut [u32],
+) -> Result<(), &'static str> {
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    if data.len() < blocks_x * blocks_y * 16 {
+        return Err("bc2 data is too short");
+    }
+    if image.len() < width * height {
+        return Err("bc2 image buffer is too small");
+    }
+
+    let mut block_pixels = [0; 16];
+    for (block_index, block) in data.chunks_exact(16).take(blocks_x * blocks_y).enumerate() {
+        let (alpha, color) = block.split_at(8);
+        texture2ddecoder::decode_bc1(color, 4, 4, &mut block_pixels)?;
+        let alpha = u64::from_le_bytes(alpha.try_into().unwrap());
+
+        let (block_x, block_y) = (block_index % blocks_x * 4, block_index / blocks_x * 4);
+        for (i, pixel) in block_pixels.iter().enumerate() {
+            let (x, y) = (block_x + i % 4, block_y + i / 4);
+            // Blocks of the smallest mip levels hang over the edge of the image
+            if x >= width || y >= height {
+                continue;
+            }
+            let a = ((alpha >> (i * 4)) & 0xF) as u32 * 0x11;
+            image[y * width + x] = (pixel & 0x00FF_FFFF) | (a << 24);
+        }
+    }
+
+    Ok(())
+}
+
+/// The number of bytes a mip level of a 2d texture takes up, rounded up to whole blocks.
+fn level_length(format: wgpu::TextureFormat, size: wgpu::Extent3d, mip_level: u32) -> usize {
+    let size = size
+        .mip_level_size(mip_level, wgpu::TextureDimension::D2)
+        .physical_size(format);
+    let (block_width, block_height) = format.block_dimensions();
+    // Only color formats are supported, so there's always a block size
+    let block_size = format.block_copy_size(None).unwrap_or(0);
+
+    (size.width / block_width) as usize
+        * (size.height / block_height) as usize
+        * block_size as usize
+}
+
+fn wgpu_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat as F;
+
+    Some(match format {
+        K::R8G8B8A8_UNORM => F::Rgba8Unorm,
+        K::R8G8B8A8_SRGB => F::Rgba8UnormSrgb,
+        // wgpu has no separate format for bc1 without alpha
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => F::Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => F::Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => F::Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => F::Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => F::Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => F::Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => F::Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => F::Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => F::Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => F::Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => F::Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => F::Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => F::Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => F::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
@@ -0,0 +1,369 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+use bevy_ecs::prelude::*;
+
+pub const ENVIRONMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+const CUBE_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+// The last mip level is used for fully rough surfaces
+const PREFILTERED_MIP_COUNT: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+
+// Matches the workgroup size of the environment compute shaders
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Light arriving from the surroundings of the scene, used for ambient lighting and reflections.
+#[derive(Resource)]
+pub struct Environment {
+    pub sampler: wgpu::Sampler,
+    // The environment as seen from the center of the scene
+    pub cube: Cubemap,
+    // Diffuse light arriving at a surface facing each direction
+    pub irradiance: Cubemap,
+    // Specular reflections, every mip level is blurred for a rougher surface than the last
+    pub prefiltered: Cubemap,
+    // Split sum scale and bias, by n_dot_v and roughness. Doesn't depend on the environment
+    pub brdf_lut: render::Texture,
+}
+
+pub struct Cubemap {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl Cubemap {
+    fn new(render_state: &render::State, label: &str, size: u32, mip_level_count: u32) -> Self {
+        let texture = render_state
+            .wgpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: ENVIRONMENT_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::STORAGE_BINDING
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        Self { texture, view }
+    }
+
+    // All 6 faces of one mip level, for compute shaders to write to
+    fn storage_view(&self, mip_level: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("wormhole environment storage view"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            base_mip_level: mip_level,
+            mip_level_count: Some(1),
+            ..Default::default()
+        })
+    }
+
+    fn clear(&self, encoder: &mut wgpu::CommandEncoder, color: glam::Vec3) {
+        for face in 0..6 {
+            let view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("wormhole environment face view"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face,
+                array_layer_count: Some(1),
+                ..Default::default()
+            });
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("wormhole environment clear pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: color.x as f64,
+                            g: color.y as f64,
+                            b: color.z as f64,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        }
+    }
+}
+
+impl Environment {
+    /// An environment with the same radiance in every direction. Useful as a flat ambient light.
+    pub fn uniform(render_state: &render::State, color: glam::Vec3) -> Self {
+        let mut encoder =
+            render_state
+                .wgpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("wormhole environment encoder"),
+                });
+
+        // Convolving a uniform environment doesn't change it
+        let cube = Cubemap::new(render_state, "wormhole environment cube", 1, 1);
+        let irradiance = Cubemap::new(render_state, "wormhole environment irradiance", 1, 1);
+        let prefiltered = Cubemap::new(render_state, "wormhole environment prefiltered", 1, 1);
+        cube.clear(&mut encoder, color);
+        irradiance.clear(&mut encoder, color);
+        prefiltered.clear(&mut encoder, color);
+
+        let brdf_lut = encode_brdf_lut(render_state, &mut encoder);
+
+        render_state
+            .wgpu
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        Self {
+            sampler: create_sampler(render_state),
+            cube,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        }
+    }
+
+    /// Loads an equirectangular (latitude/longitude) image, usually a `.hdr` file.
+    pub fn from_hdr(
+        render_state: &render::State,
+        path: impl AsRef<camino::Utf8Path>,
+    ) -> image::ImageResult<Self> {
+        let image = image::open(path.as_ref())?.into_rgba32f();
+        Ok(Self::from_equirectangular(render_state, &image))
+    }
+
+    pub fn from_equirectangular(render_state: &render::State, image: &image::Rgba32FImage) -> Self {
+        let device = &render_state.wgpu.device;
+        let sampler = create_sampler(render_state);
+
+        let equirect = render_state
+            .wgpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("wormhole environment equirect"),
+                size: wgpu::Extent3d {
+                    width: image.width(),
+                    height: image.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+        render_state.wgpu.queue.write_texture(
+            equirect.as_image_copy(),
+            bytemuck::cast_slice(image.as_raw()),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(16 * image.width()),
+                rows_per_image: Some(image.height()),
+            },
+            equirect.size(),
+        );
+        let equirect_view = equirect.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let cube = Cubemap::new(render_state, "wormhole environment cube", CUBE_SIZE, 1);
+        let irradiance = Cubemap::new(
+            render_state,
+            "wormhole environment irradiance",
+            IRRADIANCE_SIZE,
+            1,
+        );
+        let prefiltered = Cubemap::new(
+            render_state,
+            "wormhole environment prefiltered",
+            PREFILTERED_SIZE,
+            PREFILTERED_MIP_COUNT,
+        );
+
+        let source = render::BindGroupBuilder::new()
+            .append_sampler(&sampler)
+            .append_texture_view(&cube.view)
+            .build(
+                device,
+                Some("wormhole environment source bind group"),
+                &render_state.bind_groups.environment_source,
+            );
+        let equirect_source = render::BindGroupBuilder::new()
+            .append_texture_view(&equirect_view)
+            .build(
+                device,
+                Some("wormhole environment equirect bind group"),
+                &render_state.bind_groups.environment_equirect,
+            );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("wormhole environment encoder"),
+        });
+
+        let cube_target = create_target_bind_group(render_state, &cube.storage_view(0));
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("wormhole equirect to cube pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&render_state.pipelines.equirect_to_cube);
+        compute_pass.set_bind_group(0, &cube_target, &[]);
+        compute_pass.set_bind_group(1, &equirect_source, &[]);
+        compute_pass.dispatch_workgroups(workgroup_count(CUBE_SIZE), workgroup_count(CUBE_SIZE), 6);
+        drop(compute_pass);
+
+        let irradiance_target = create_target_bind_group(render_state, &irradiance.storage_view(0));
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("wormhole environment irradiance pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&render_state.pipelines.environment_irradiance);
+        compute_pass.set_bind_group(0, &irradiance_target, &[]);
+        compute_pass.set_bind_group(1, &source, &[]);
+        compute_pass.dispatch_workgroups(
+            workgroup_count(IRRADIANCE_SIZE),
+            workgroup_count(IRRADIANCE_SIZE),
+            6,
+        );
+        drop(compute_pass);
+
+        for mip_level in 0..PREFILTERED_MIP_COUNT {
+            let size = PREFILTERED_SIZE >> mip_level;
+            let roughness = mip_level as f32 / (PREFILTERED_MIP_COUNT - 1) as f32;
+
+            let prefiltered_target =
+                create_target_bind_group(render_state, &prefiltered.storage_view(mip_level));
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("wormhole environment prefilter pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&render_state.pipelines.environment_prefilter);
+            compute_pass.set_bind_group(0, &prefiltered_target, &[]);
+            compute_pass.set_bind_group(1, &source, &[]);
+            compute_pass.set_push_constants(0, bytemuck::bytes_of(&roughness));
+            compute_pass.dispatch_workgroups(workgroup_count(size), workgroup_count(size), 6);
+        }
+
+        let brdf_lut = encode_brdf_lut(render_state, &mut encoder);
+
+        render_state
+            .wgpu
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        Self {
+            sampler,
+            cube,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        }
+    }
+}
+
+fn encode_brdf_lut(
+    render_state: &render::State,
+    encoder: &mut wgpu::CommandEncoder,
+) -> render::Texture {
+    let brdf_lut = render::Texture::new(
+        render_state,
+        wgpu::Extent3d {
+            width: BRDF_LUT_SIZE,
+            height: BRDF_LUT_SIZE,
+            depth_or_array_layers: 1,
+        },
+        render::TextureFormat {
+            format: ENVIRONMENT_FORMAT,
+            filtering: wgpu::FilterMode::Linear,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            compare: None,
+        },
+    );
+    let storage_view = brdf_lut.texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("wormhole brdf lut storage view"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let target = create_target_bind_group(render_state, &storage_view);
+
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("wormhole brdf lut pass"),
+        timestamp_writes: None,
+    });
+    compute_pass.set_pipeline(&render_state.pipelines.environment_brdf_lut);
+    compute_pass.set_bind_group(0, &target, &[]);
+    compute_pass.dispatch_workgroups(
+        workgroup_count(BRDF_LUT_SIZE),
+        workgroup_count(BRDF_LUT_SIZE),
+        1,
+    );
+    drop(compute_pass);
+
+    brdf_lut
+}
+
+fn create_target_bind_group(
+    render_state: &render::State,
+    view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    render::BindGroupBuilder::new()
+        .append_texture_view(view)
+        .build(
+            &render_state.wgpu.device,
+            Some("wormhole environment target bind group"),
+            &render_state.bind_groups.environment_target,
+        )
+}
+
+fn create_sampler(render_state: &render::State) -> wgpu::Sampler {
+    render_state
+        .wgpu
+        .device
+        .create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("wormhole environment sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        })
+}
+
+fn workgroup_count(size: u32) -> u32 {
+    size.div_ceil(WORKGROUP_SIZE)
+}
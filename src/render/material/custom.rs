@@ -0,0 +1,205 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::any::Any;
+
+use crate::assets;
+use crate::render;
+use crate::shaders;
+
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use wgpu::util::DeviceExt;
+
+/// A material type with its own shader, drawn into the gbuffer alongside the built in [`render::Material`].
+///
+/// The shader is looked up in [`shaders::Registry`] by [`CustomMaterial::SHADER`], and needs a `vs_main` and `fs_main`.
+/// `wormhole::mesh` provides the vertex stage (`Mesh::vertex`) and the gbuffer output (`Mesh::FragmentOutput`).
+/// The data of every material of a type is bound as `array<Data>` at `@group(2) @binding(0)`, indexed by `material_index`.
+/// The built in material bind group (textures and samplers) is bound at group 1 as it is for the object shader.
+/// [`render::material::Hologram`] is an example.
+pub trait CustomMaterial: Send + Sync + 'static {
+    /// Must match the layout of the shader's material struct.
+    type Data: bytemuck::Pod;
+
+    /// The file path of the shader in [`shaders::Registry`].
+    /// Shaders added with [`shaders::Registry::load_file`] are hot reloaded, unlike [`shaders::Registry::add_shader`].
+    const SHADER: &'static str;
+
+    fn data(&self, textures: &assets::Textures) -> Self::Data;
+
+    /// Materials with different shader defs are drawn with different pipelines.
+    fn shader_defs(&self) -> shaders::ShaderDefs {
+        shaders::ShaderDefs::new()
+    }
+}
+
+/// Every material of one custom material type.
+pub(crate) struct Set<M> {
+    materials: IndexMap<assets::MaterialId, M>,
+    buffer: Option<wgpu::Buffer>,
+    // Rebuilt with the buffer
+    pipeline_keys: IndexSet<shaders::PipelineKey>,
+    material_pipelines: Vec<usize>,
+}
+
+/// A [`Set`] with its material type erased, so sets of every type can be stored together.
+pub(crate) trait AnySet: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn index_of(&self, id: assets::MaterialId) -> Option<usize>;
+
+    /// The index of the pipeline the material is drawn with. `None` until the set is prepared.
+    fn pipeline_index(&self, id: assets::MaterialId) -> Option<usize>;
+
+    fn pipeline_key(&self, index: usize) -> Option<&shaders::PipelineKey>;
+
+    fn remove(&mut self, id: assets::MaterialId);
+
+    fn retain(&mut self, ids: &[assets::MaterialId]);
+
+    fn textures_changed(&mut self);
+
+    /// Rebuilds the material buffer if it changed, and creates any pipelines that don't exist yet.
+    fn prepare(
+        &mut self,
+        render_state: &mut render::State,
+        textures: &assets::Textures,
+    ) -> Result<(), shaders::registry::Error>;
+
+    fn buffer(&self) -> Option<&wgpu::Buffer>;
+}
+
+impl<M> Set<M> {
+    pub fn new() -> Self {
+        Self {
+            materials: IndexMap::new(),
+            buffer: None,
+            pipeline_keys: IndexSet::new(),
+            material_pipelines: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, id: assets::MaterialId, material: M) -> Option<M> {
+        self.buffer.take();
+        self.materials.insert(id, material)
+    }
+
+    pub fn get(&self, id: assets::MaterialId) -> Option<&M> {
+        self.materials.get(&id)
+    }
+}
+
+impl<M> AnySet for Set<M>
+where
+    M: CustomMaterial,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn index_of(&self, id: assets::MaterialId) -> Option<usize> {
+        self.materials.get_index_of(&id)
+    }
+
+    fn pipeline_index(&self, id: assets::MaterialId) -> Option<usize> {
+        self.buffer.as_ref()?;
+        let index = self.materials.get_index_of(&id)?;
+        self.material_pipelines.get(index).copied()
+    }
+
+    fn pipeline_key(&self, index: usize) -> Option<&shaders::PipelineKey> {
+        self.pipeline_keys.get_index(index)
+    }
+
+    fn remove(&mut self, id: assets::MaterialId) {
+        if self.materials.shift_remove(&id).is_some() {
+            self.buffer.take();
+        }
+    }
+
+    fn retain(&mut self, ids: &[assets::MaterialId]) {
+        self.buffer.take();
+        self.materials.retain(|id, _| ids.contains(id));
+    }
+
+    fn textures_changed(&mut self) {
+        self.buffer.take();
+    }
+
+    fn prepare(
+        &mut self,
+        render_state: &mut render::State,
+        textures: &assets::Textures,
+    ) -> Result<(), shaders::registry::Error> {
+        if self.buffer.is_none() {
+            // Storage bindings can't be empty
+            let data = if self.materials.is_empty() {
+                vec![bytemuck::Zeroable::zeroed()]
+            } else {
+                self.materials
+                    .values()
+                    .map(|m| m.data(textures))
+                    .collect_vec()
+            };
+            self.buffer = Some(render_state.wgpu.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("wormhole custom material buffer"),
+                    contents: bytemuck::cast_slice::<M::Data, u8>(&data),
+                    usage: wgpu::BufferUsages::STORAGE,
+                },
+            ));
+
+            self.pipeline_keys.clear();
+            self.material_pipelines = self
+                .materials
+                .values()
+                .map(|m| {
+                    let key = shaders::PipelineKey {
+                        name: M::SHADER.into(),
+                        shader_defs: m.shader_defs(),
+                    };
+                    self.pipeline_keys.insert_full(key).0
+                })
+                .collect();
+        }
+
+        let render::State {
+            wgpu,
+            bind_groups,
+            shaders,
+            ..
+        } = render_state;
+        for key in &self.pipeline_keys {
+            shaders.prepare_pipeline(key, |registry| {
+                shaders::object::create_custom_material_pipeline(registry, wgpu, bind_groups, key)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn buffer(&self) -> Option<&wgpu::Buffer> {
+        self.buffer.as_ref()
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::assets;
+use crate::render;
+use crate::shaders;
+
+use naga_oil::compose::ShaderDefValue;
+
+/// An unlit material that glows in horizontal stripes, drawn by `shaders/hologram.wgsl`.
+pub struct Hologram {
+    pub color: render::Color,
+    // Multiplied with the color
+    pub texture: Option<assets::TextureId>,
+    /// The distance between stripes in world space.
+    pub line_spacing: f32,
+    /// How much of the space between stripes is lit, from 0 to 1.
+    pub line_width: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HologramData {
+    pub color: render::Color,
+    pub texture: u32,
+    pub line_spacing: f32,
+    pub line_width: f32,
+
+    _pad: [u8; 4],
+}
+
+impl Default for Hologram {
+    fn default() -> Self {
+        Self {
+            color: render::Color::from([0.2, 0.8, 1.0]),
+            texture: None,
+            line_spacing: 0.1,
+            line_width: 0.5,
+        }
+    }
+}
+
+impl render::CustomMaterial for Hologram {
+    type Data = HologramData;
+
+    const SHADER: &'static str = "shaders/hologram.wgsl";
+
+    fn data(&self, textures: &assets::Textures) -> Self::Data {
+        HologramData {
+            color: self.color,
+            texture: self
+                .texture
+                .and_then(|i| textures.id_to_bindgroup_index(i))
+                .unwrap_or_default() as u32,
+            line_spacing: self.line_spacing,
+            line_width: self.line_width,
+
+            _pad: [0; 4],
+        }
+    }
+
+    fn shader_defs(&self) -> shaders::ShaderDefs {
+        let mut shader_defs = shaders::ShaderDefs::new();
+        if self.texture.is_some() {
+            shader_defs.insert("HAS_TEXTURE".to_string(), ShaderDefValue::Bool(true));
+        }
+        shader_defs
+    }
+}
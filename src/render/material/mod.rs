@@ -16,6 +16,14 @@
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 use crate::assets;
 use crate::render;
+use crate::shaders;
+
+mod custom;
+pub use custom::CustomMaterial;
+pub(crate) use custom::{AnySet, Set};
+
+mod hologram;
+pub use hologram::{Hologram, HologramData};
 
 pub struct Material {
     pub base_color: render::Color,
@@ -32,6 +40,27 @@ pub struct Material {
     pub occlusion_texture: Option<assets::TextureId>,
 
     pub alpha_cutoff: Option<f32>,
+    pub alpha_mode: AlphaMode,
+}
+
+/// How the alpha of a material's base color is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Alpha is ignored.
+    #[default]
+    Opaque,
+    /// Fragments with an alpha below `alpha_cutoff` are discarded.
+    Mask,
+    /// Rendered after lighting and blended over what is behind it.
+    Blend,
+}
+
+/// The pipeline an opaque mesh is drawn with, decided by its material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pipeline {
+    Object(shaders::object::Permutation),
+    // The custom material type, and the index of the pipeline in its set
+    Custom(std::any::TypeId, usize),
 }
 
 #[repr(C)]
@@ -89,6 +118,7 @@ impl Default for Material {
             occlusion_texture: None,
 
             alpha_cutoff: None,
+            alpha_mode: AlphaMode::Opaque,
         }
     }
 }
@@ -128,7 +158,14 @@ impl Material {
             assets::TextureId::Gltf(gltf_id, i.texture().index())
         });
 
-        let alpha_cutoff = material.alpha_cutoff();
+        let alpha_mode = match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        };
+        // glTF only uses the cutoff when masking, and it defaults to 0.5 there
+        let alpha_cutoff =
+            (alpha_mode == AlphaMode::Mask).then(|| material.alpha_cutoff().unwrap_or(0.5));
 
         Self {
             base_color,
@@ -145,42 +182,48 @@ impl Material {
             occlusion_texture,
 
             alpha_cutoff,
+            alpha_mode,
         }
     }
 
     pub fn as_data(&self, textures: &assets::Textures) -> Data {
+        let index =
+            |id: Option<assets::TextureId>| id.and_then(|i| textures.id_to_bindgroup_index(i));
+
+        let metallic_roughness_texture = index(self.metallic_roughness_texture);
+        let normal_texture = index(self.normal_texture);
+        let occlusion_texture = index(self.occlusion_texture);
+
+        // Missing color textures show up as the null texture (index 0) on purpose,
+        // but sampling it as normals, roughness or occlusion just looks broken, so those fall back to the factors
+        let mut flags = self.calculate_flags();
+        if metallic_roughness_texture.is_none() {
+            flags.remove(MaterialFlags::HAS_METALLIC_ROUGHNESS_TEXTURE);
+        }
+        if normal_texture.is_none() {
+            flags.remove(MaterialFlags::HAS_NORMAL_TEXTURE);
+        }
+        if occlusion_texture.is_none() {
+            flags.remove(MaterialFlags::HAS_OCCLUSION_TEXTURE);
+        }
+
         Data {
             base_color: self.base_color,
-            base_color_texture: self
-                .base_color_texture
-                .and_then(|i| textures.id_to_bindgroup_index(i))
-                .unwrap_or_default() as u32,
+            base_color_texture: index(self.base_color_texture).unwrap_or_default() as u32,
 
             metallic: self.metallic,
             roughness: self.roughness,
-            metallic_roughness_texture: self
-                .metallic_roughness_texture
-                .and_then(|i| textures.id_to_bindgroup_index(i))
-                .unwrap_or_default() as u32,
+            metallic_roughness_texture: metallic_roughness_texture.unwrap_or_default() as u32,
 
             emissive: self.emissive,
-            emissive_texture: self
-                .emissive_texture
-                .and_then(|i| textures.id_to_bindgroup_index(i))
-                .unwrap_or_default() as u32,
-
-            normal_texture: self
-                .normal_texture
-                .and_then(|i| textures.id_to_bindgroup_index(i))
-                .unwrap_or_default() as u32,
-            occlusion_texture: self
-                .occlusion_texture
-                .and_then(|i| textures.id_to_bindgroup_index(i))
-                .unwrap_or_default() as u32,
+            emissive_texture: index(self.emissive_texture).unwrap_or_default() as u32,
+
+            normal_texture: normal_texture.unwrap_or_default() as u32,
+            occlusion_texture: occlusion_texture.unwrap_or_default() as u32,
 
             alpha_cutoff: self.alpha_cutoff.unwrap_or_default(),
 
-            flags: self.calculate_flags(),
+            flags,
 
             _pad: [0; 12],
         }
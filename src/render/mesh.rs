@@ -70,13 +70,15 @@ impl Mesh {
 }
 
 impl MeshParts {
-    pub fn from_gltf_reader<'reader, F>(reader: gltf::mesh::Reader<'reader, 'reader, F>) -> Self
+    pub fn from_gltf_reader<'reader, F>(
+        reader: gltf::mesh::Reader<'reader, 'reader, F>,
+    ) -> Result<Self, assets::Error>
     where
         F: Clone + Fn(gltf::Buffer<'reader>) -> Option<&'reader [u8]>,
     {
         let positions = reader
             .read_positions()
-            .expect("no positions provided")
+            .ok_or(assets::Error::MissingPositions)?
             .map(glam::Vec3::from_array)
             .collect_vec();
 
@@ -96,13 +98,13 @@ impl MeshParts {
             .read_tangents()
             .map(|t| t.map(glam::Vec4::from_array).collect_vec());
 
-        Self {
+        Ok(Self {
             positions,
             normals,
             tex_coords,
             colors,
             tangents,
-        }
+        })
     }
 
     pub fn approximate_tangents(&mut self, indices: &[u32]) {
@@ -157,7 +159,17 @@ impl MeshParts {
 
 impl Mesh {
     /// The mesh must be loaded with `triangluate` and `single_index` set to true.
-    pub fn from_tobj_mesh(mut mesh: tobj::Mesh, material_id: assets::MaterialId) -> Self {
+    pub fn from_tobj_mesh(
+        mut mesh: tobj::Mesh,
+        material_id: assets::MaterialId,
+    ) -> Result<Self, assets::Error> {
+        if mesh.positions.is_empty() {
+            return Err(assets::Error::MissingPositions);
+        }
+        if mesh.indices.is_empty() {
+            return Err(assets::Error::MissingIndices);
+        }
+
         mesh.positions.shrink_to_fit();
         let positions = bytemuck::cast_vec(mesh.positions);
 
@@ -191,38 +203,44 @@ impl Mesh {
         };
         parts.approximate_tangents(&mesh.indices);
 
-        Self {
+        Ok(Self {
             parts,
             indices: mesh.indices,
             material_id,
-        }
+        })
     }
 
     pub fn from_gltf_primitive(
         gltf_id: assets::GltfId,
         primitive: gltf::Primitive<'_>,
         buffers: &[gltf::buffer::Data],
-    ) -> Self {
+    ) -> Result<Self, assets::Error> {
         let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
-        let indices = reader.read_indices().unwrap().into_u32().collect_vec();
+        let indices = reader
+            .read_indices()
+            .ok_or(assets::Error::MissingIndices)?
+            .into_u32()
+            .collect_vec();
 
-        let mut parts = MeshParts::from_gltf_reader(reader);
+        let mut parts = MeshParts::from_gltf_reader(reader)?;
         parts.approximate_tangents(&indices);
 
-        Self {
+        Ok(Self {
             parts,
             indices,
             material_id: assets::MaterialId::Gltf(
                 gltf_id,
                 primitive.material().index().unwrap_or_default(),
             ),
-        }
+        })
     }
 }
 
-impl From<tobj::Mesh> for Mesh {
-    fn from(value: tobj::Mesh) -> Self {
+impl TryFrom<tobj::Mesh> for Mesh {
+    type Error = assets::Error;
+
+    fn try_from(value: tobj::Mesh) -> Result<Self, Self::Error> {
         Self::from_tobj_mesh(value, assets::MaterialId::Path(0)) // FIXME
     }
 }
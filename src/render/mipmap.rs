@@ -0,0 +1,99 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+/// Formats mip levels can be generated for. They need to be renderable and filterable.
+pub const MIPMAP_FORMATS: [wgpu::TextureFormat; 2] = [
+    wgpu::TextureFormat::Rgba8UnormSrgb,
+    wgpu::TextureFormat::Rgba8Unorm,
+];
+
+/// The number of mip levels in a full chain down to 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// Fills every mip level of `texture` past the first by repeatedly downsampling the previous level.
+/// Does nothing if the format isn't in [`MIPMAP_FORMATS`].
+pub fn generate(render_state: &render::State, texture: &wgpu::Texture) {
+    let Some(pipeline) = MIPMAP_FORMATS
+        .iter()
+        .position(|&f| f == texture.format())
+        .map(|i| &render_state.pipelines.mipmap[i])
+    else {
+        return;
+    };
+
+    let device = &render_state.wgpu.device;
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("wormhole mipmap sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let mip_views = (0..texture.mip_level_count())
+        .map(|mip_level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("wormhole mipmap view"),
+                base_mip_level: mip_level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("wormhole mipmap encoder"),
+    });
+
+    for views in mip_views.windows(2) {
+        let source = render::BindGroupBuilder::new()
+            .append_sampler(&sampler)
+            .append_texture_view(&views[0])
+            .build(
+                device,
+                Some("wormhole mipmap bind group"),
+                &render_state.bind_groups.post_process,
+            );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("wormhole mipmap pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &views[1],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &source, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    render_state
+        .wgpu
+        .queue
+        .submit(std::iter::once(encoder.finish()));
+}
@@ -0,0 +1,496 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+use std::sync::Arc;
+
+use bevy_ecs::prelude::*;
+
+/// Passes run on the lit image before it reaches the screen.
+/// Bloom works on the HDR image before tonemapping, the rest on the tonemapped result.
+#[derive(Resource)]
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    pub bloom: Bloom,
+    pub fxaa: Fxaa,
+    pub color_grading: ColorGrading,
+}
+
+/// Blurs the gbuffer emissive target and adds it on top of the lit image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    pub enabled: bool,
+    pub intensity: f32,
+    // Distance between blur taps, in (half resolution) texels
+    pub radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fxaa {
+    pub enabled: bool,
+    // The furthest (in texels) FXAA will search along an edge
+    pub span_max: f32,
+    pub reduce_mul: f32,
+    pub reduce_min: f32,
+}
+
+/// Remaps colors through a 3D lookup table. Does nothing without a LUT.
+#[derive(Clone, Debug, Default)]
+pub struct ColorGrading {
+    pub enabled: bool,
+    // 0.0 is the ungraded image, 1.0 is fully graded
+    pub strength: f32,
+    pub lut: Option<Arc<Lut>>,
+}
+
+#[derive(Debug)]
+pub struct Lut {
+    pub size: u32,
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+#[derive(Debug)]
+pub enum LutError {
+    /// The image isn't a strip of square slices, one per row.
+    InvalidDimensions {
+        width: u32,
+        height: u32,
+    },
+    TooLarge {
+        size: u32,
+        max: u32,
+    },
+}
+
+impl std::fmt::Display for LutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LutError::InvalidDimensions { width, height } => write!(
+                f,
+                "LUT image is {width}x{height} pixels, but should be {height} slices of {height}x{height} pixels"
+            ),
+            LutError::TooLarge { size, max } => {
+                write!(f, "LUT size {size} is larger than the maximum of {max}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LutError {}
+
+// Render targets used between the post processing passes
+pub struct Buffer {
+    sampler: wgpu::Sampler,
+    // Half resolution, ping-ponged between while blurring
+    bloom: [render::Texture; 2],
+    // Tonemapped images, ping-ponged between by the passes after tonemapping
+    ldr: [render::Texture; 2],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LdrPass {
+    ColorGrading,
+    Fxaa,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 1.0,
+            radius: 1.0,
+        }
+    }
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            span_max: 8.0,
+            reduce_mul: 1.0 / 8.0,
+            reduce_min: 1.0 / 128.0,
+        }
+    }
+}
+
+impl Lut {
+    /// Loads a LUT laid out as a horizontal strip of square slices, like most color grading tools export.
+    /// Red increases along x inside a slice, green along y, and blue with every slice.
+    pub fn from_image(
+        render_state: &render::State,
+        image: &image::RgbaImage,
+    ) -> Result<Self, LutError> {
+        let size = image.height();
+        if size == 0 || size.checked_mul(size) != Some(image.width()) {
+            return Err(LutError::InvalidDimensions {
+                width: image.width(),
+                height: size,
+            });
+        }
+        let max = render_state.wgpu.device.limits().max_texture_dimension_3d;
+        if size > max {
+            return Err(LutError::TooLarge { size, max });
+        }
+
+        let mut data = Vec::with_capacity(image.as_raw().len());
+        for blue in 0..size {
+            for green in 0..size {
+                for red in 0..size {
+                    data.extend(image.get_pixel(blue * size + red, green).0);
+                }
+            }
+        }
+
+        let texture = render_state
+            .wgpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("wormhole color grading lut"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: size,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+        render_state.wgpu.queue.write_texture(
+            texture.as_image_copy(),
+            &data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size),
+                rows_per_image: Some(size),
+            },
+            texture.size(),
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Ok(Self {
+            size,
+            texture,
+            view,
+        })
+    }
+}
+
+impl Settings {
+    /// The enabled passes that run after tonemapping, in order.
+    pub fn ldr_passes(&self) -> Vec<LdrPass> {
+        let mut passes = Vec::new();
+        if self.color_grading.enabled && self.color_grading.lut.is_some() {
+            passes.push(LdrPass::ColorGrading);
+        }
+        if self.fxaa.enabled {
+            passes.push(LdrPass::Fxaa);
+        }
+        passes
+    }
+}
+
+impl Buffer {
+    pub fn new(render_state: &render::State) -> Self {
+        let sampler = render_state
+            .wgpu
+            .device
+            .create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("post process sampler"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            });
+
+        Self {
+            sampler,
+            bloom: [
+                create_bloom_texture(render_state),
+                create_bloom_texture(render_state),
+            ],
+            ldr: [
+                create_ldr_texture(render_state),
+                create_ldr_texture(render_state),
+            ],
+        }
+    }
+
+    pub fn resize_to_screen(&mut self, render_state: &render::State) {
+        self.bloom = [
+            create_bloom_texture(render_state),
+            create_bloom_texture(render_state),
+        ];
+        for texture in &mut self.ldr {
+            texture.resize_to_screen(render_state);
+        }
+    }
+
+    /// Where tonemapping should render to so the passes in `ldr_passes` can pick it up.
+    pub fn tonemap_target<'a>(
+        &'a self,
+        ldr_passes: &[LdrPass],
+        output: &'a wgpu::TextureView,
+    ) -> &'a wgpu::TextureView {
+        if ldr_passes.is_empty() {
+            output
+        } else {
+            &self.ldr[0].view
+        }
+    }
+
+    fn source_bind_group(
+        &self,
+        render_state: &render::State,
+        source: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        render::BindGroupBuilder::new()
+            .append_sampler(&self.sampler)
+            .append_texture_view(source)
+            .build(
+                &render_state.wgpu.device,
+                Some("wormhole post process source bind group"),
+                &render_state.bind_groups.post_process,
+            )
+    }
+
+    pub fn encode_bloom(
+        &self,
+        render_state: &render::State,
+        encoder: &mut wgpu::CommandEncoder,
+        screen_vertices: &wgpu::Buffer,
+        bloom: &Bloom,
+        emissive: &wgpu::TextureView,
+        hdr: &wgpu::TextureView,
+    ) {
+        encoder.push_debug_group("wormhole bloom pass");
+
+        let size = self.bloom[0].texture.size();
+        let texel_size = glam::vec2(1.0 / size.width as f32, 1.0 / size.height as f32);
+        let passes = [
+            (
+                &render_state.pipelines.bloom_downsample,
+                emissive,
+                &self.bloom[0].view,
+                glam::Vec2::ZERO,
+            ),
+            (
+                &render_state.pipelines.bloom_blur,
+                &self.bloom[0].view,
+                &self.bloom[1].view,
+                glam::vec2(texel_size.x, 0.0) * bloom.radius,
+            ),
+            (
+                &render_state.pipelines.bloom_blur,
+                &self.bloom[1].view,
+                &self.bloom[0].view,
+                glam::vec2(0.0, texel_size.y) * bloom.radius,
+            ),
+            (
+                &render_state.pipelines.bloom_composite,
+                &self.bloom[0].view,
+                hdr,
+                glam::Vec2::ZERO,
+            ),
+        ];
+
+        for (pipeline, source, target, direction) in passes {
+            let source = self.source_bind_group(render_state, source);
+            let mut render_pass = begin_fullscreen_pass(encoder, target, wgpu::LoadOp::Load);
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_vertex_buffer(0, screen_vertices.slice(..));
+            render_pass.set_bind_group(0, &source, &[]);
+
+            #[repr(C)]
+            #[derive(Clone, Copy)]
+            #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+            struct BloomPushConstants {
+                direction: glam::Vec2,
+                intensity: f32,
+                _padding: f32,
+            }
+            render_pass.set_push_constants(
+                wgpu::ShaderStages::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&BloomPushConstants {
+                    direction,
+                    intensity: bloom.intensity,
+                    _padding: 0.0,
+                }),
+            );
+
+            render_pass.draw(0..6, 0..1);
+        }
+
+        encoder.pop_debug_group();
+    }
+
+    /// Runs `ldr_passes` on the tonemapped image, with the last one rendering to `output`.
+    pub fn encode_ldr_passes(
+        &self,
+        render_state: &render::State,
+        encoder: &mut wgpu::CommandEncoder,
+        screen_vertices: &wgpu::Buffer,
+        settings: &Settings,
+        ldr_passes: &[LdrPass],
+        output: &wgpu::TextureView,
+    ) {
+        encoder.push_debug_group("wormhole post process passes");
+
+        let lut_bind_group = settings.color_grading.lut.as_ref().map(|lut| {
+            render::BindGroupBuilder::new()
+                .append_sampler(&self.sampler)
+                .append_texture_view(&lut.view)
+                .build(
+                    &render_state.wgpu.device,
+                    Some("wormhole color grading lut bind group"),
+                    &render_state.bind_groups.color_grading_lut,
+                )
+        });
+
+        for (i, &pass) in ldr_passes.iter().enumerate() {
+            let source = self.source_bind_group(render_state, &self.ldr[i % 2].view);
+            let target = if i + 1 == ldr_passes.len() {
+                output
+            } else {
+                &self.ldr[(i + 1) % 2].view
+            };
+
+            let mut render_pass =
+                begin_fullscreen_pass(encoder, target, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+            render_pass.set_vertex_buffer(0, screen_vertices.slice(..));
+            render_pass.set_bind_group(0, &source, &[]);
+
+            match pass {
+                LdrPass::ColorGrading => {
+                    // ldr_passes only includes color grading when there is a LUT
+                    let (Some(lut), Some(lut_bind_group)) =
+                        (&settings.color_grading.lut, &lut_bind_group)
+                    else {
+                        unreachable!()
+                    };
+
+                    render_pass.set_pipeline(&render_state.pipelines.color_grade);
+                    render_pass.set_bind_group(1, lut_bind_group, &[]);
+
+                    #[repr(C)]
+                    #[derive(Clone, Copy)]
+                    #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+                    struct ColorGradePushConstants {
+                        strength: f32,
+                        lut_size: f32,
+                    }
+                    render_pass.set_push_constants(
+                        wgpu::ShaderStages::FRAGMENT,
+                        0,
+                        bytemuck::bytes_of(&ColorGradePushConstants {
+                            strength: settings.color_grading.strength,
+                            lut_size: lut.size as f32,
+                        }),
+                    );
+                }
+                LdrPass::Fxaa => {
+                    render_pass.set_pipeline(&render_state.pipelines.fxaa);
+
+                    let size = self.ldr[0].texture.size();
+
+                    #[repr(C)]
+                    #[derive(Clone, Copy)]
+                    #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+                    struct FxaaPushConstants {
+                        texel_size: glam::Vec2,
+                        span_max: f32,
+                        reduce_mul: f32,
+                        reduce_min: f32,
+                        _padding: f32,
+                    }
+                    render_pass.set_push_constants(
+                        wgpu::ShaderStages::FRAGMENT,
+                        0,
+                        bytemuck::bytes_of(&FxaaPushConstants {
+                            texel_size: glam::vec2(
+                                1.0 / size.width as f32,
+                                1.0 / size.height as f32,
+                            ),
+                            span_max: settings.fxaa.span_max,
+                            reduce_mul: settings.fxaa.reduce_mul,
+                            reduce_min: settings.fxaa.reduce_min,
+                            _padding: 0.0,
+                        }),
+                    );
+                }
+            }
+
+            render_pass.draw(0..6, 0..1);
+        }
+
+        encoder.pop_debug_group();
+    }
+}
+
+fn begin_fullscreen_pass<'pass>(
+    encoder: &'pass mut wgpu::CommandEncoder,
+    target: &'pass wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPass<'pass> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("wormhole post process pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    })
+}
+
+fn create_bloom_texture(render_state: &render::State) -> render::Texture {
+    let config = &render_state.wgpu.surface_config;
+    render::Texture::new(
+        render_state,
+        wgpu::Extent3d {
+            width: (config.width / 2).max(1),
+            height: (config.height / 2).max(1),
+            depth_or_array_layers: 1,
+        },
+        render::TextureFormat::HDR,
+    )
+}
+
+// Same format as the screen, so every pass after tonemapping can render to either
+fn create_ldr_texture(render_state: &render::State) -> render::Texture {
+    render::Texture::new_screen_size(
+        render_state,
+        render::TextureFormat {
+            format: render_state.wgpu.surface_config.format,
+            ..render::TextureFormat::HDR
+        },
+    )
+}
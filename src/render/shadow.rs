@@ -0,0 +1,234 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+
+/// The most shadow casting lights the lighting pass can sample at once.
+pub const MAX_SHADOW_MAPS: u32 = 16;
+
+pub const SHADOW_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+const SHADOW_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Omnidirectional shadow maps for point lights.
+/// Every face stores the distance from the light to the closest surface, divided by the light's far plane.
+pub struct ShadowMaps {
+    sampler: wgpu::Sampler,
+    maps: Vec<ShadowMap>,
+    used: usize,
+    // Bound when there are no shadow casting lights, since binding arrays can't be empty
+    placeholder: ShadowMap,
+}
+
+pub struct ShadowMap {
+    pub resolution: u32,
+    pub texture: wgpu::Texture,
+    pub cube_view: wgpu::TextureView,
+    pub face_views: [wgpu::TextureView; 6],
+    pub depth_view: wgpu::TextureView,
+}
+
+// Matches the push constants in shadow.wgsl
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowConstants {
+    pub view_proj: glam::Mat4,
+    pub light_position: glam::Vec3,
+    pub far: f32,
+}
+
+impl ShadowMap {
+    pub fn new(render_state: &render::State, resolution: u32) -> Self {
+        let size = wgpu::Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 6,
+        };
+        let texture = render_state
+            .wgpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("wormhole shadow map"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: SHADOW_MAP_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+        let depth = render_state
+            .wgpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("wormhole shadow map depth"),
+                size: wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: SHADOW_DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+
+        let cube_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("wormhole shadow map cube view"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let face_views = std::array::from_fn(|face| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("wormhole shadow map face view"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_array_layer: face as u32,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        });
+        let depth_view = depth.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            resolution,
+            texture,
+            cube_view,
+            face_views,
+            depth_view,
+        }
+    }
+
+    /// The view projection matrix of each cube face, in the order wgpu expects them (+X, -X, +Y, -Y, +Z, -Z).
+    pub fn face_view_projs(position: glam::Vec3, near: f32, far: f32) -> [glam::Mat4; 6] {
+        // Cube maps are sampled with the texture origin at the top left, so clip space y has to be flipped
+        let projection = glam::Mat4::from_scale(glam::vec3(1.0, -1.0, 1.0))
+            * glam::Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, near, far);
+
+        [
+            (glam::Vec3::X, glam::Vec3::NEG_Y),
+            (glam::Vec3::NEG_X, glam::Vec3::NEG_Y),
+            (glam::Vec3::Y, glam::Vec3::Z),
+            (glam::Vec3::NEG_Y, glam::Vec3::NEG_Z),
+            (glam::Vec3::Z, glam::Vec3::NEG_Y),
+            (glam::Vec3::NEG_Z, glam::Vec3::NEG_Y),
+        ]
+        .map(|(direction, up)| projection * glam::Mat4::look_to_rh(position, direction, up))
+    }
+}
+
+/// Keeps shadow map resolutions within what the device can create, warning once if one isn't.
+fn clamp_resolution(resolution: u32, max_resolution: u32) -> u32 {
+    static WARN_RESOLUTION: std::sync::Once = std::sync::Once::new();
+
+    let clamped = resolution.clamp(1, max_resolution);
+    if clamped != resolution {
+        WARN_RESOLUTION.call_once(|| {
+            log::warn!(
+                "shadow map resolution {resolution} is not supported, using {clamped} instead"
+            );
+        });
+    }
+    clamped
+}
+
+impl ShadowMaps {
+    pub fn new(render_state: &render::State) -> Self {
+        let sampler = render_state
+            .wgpu
+            .device
+            .create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("wormhole shadow map sampler"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+        Self {
+            sampler,
+            maps: Vec::with_capacity(MAX_SHADOW_MAPS as usize),
+            used: 0,
+            placeholder: ShadowMap::new(render_state, 1),
+        }
+    }
+
+    pub fn start_frame(&mut self) {
+        self.used = 0;
+    }
+
+    /// Hands out a shadow map for this frame, reusing last frame's maps where the resolution matches.
+    /// Returns `None` once [`MAX_SHADOW_MAPS`] are in use.
+    pub fn allocate(&mut self, render_state: &render::State, resolution: u32) -> Option<u32> {
+        if self.used >= MAX_SHADOW_MAPS as usize {
+            return None;
+        }
+
+        let max_resolution = render_state.wgpu.device.limits().max_texture_dimension_2d;
+        let resolution = clamp_resolution(resolution, max_resolution);
+
+        let index = self.used;
+        match self.maps.get(index) {
+            Some(map) if map.resolution == resolution => {}
+            Some(_) => self.maps[index] = ShadowMap::new(render_state, resolution),
+            None => self.maps.push(ShadowMap::new(render_state, resolution)),
+        }
+        self.used += 1;
+
+        Some(index as u32)
+    }
+
+    pub fn get(&self, index: u32) -> &ShadowMap {
+        &self.maps[index as usize]
+    }
+
+    /// Drops the maps that weren't used this frame and builds the bind group sampled by the lighting pass.
+    pub fn finish(&mut self, render_state: &render::State) -> wgpu::BindGroup {
+        self.maps.truncate(self.used);
+
+        let views = if self.maps.is_empty() {
+            vec![&self.placeholder.cube_view]
+        } else {
+            self.maps.iter().map(|map| &map.cube_view).collect()
+        };
+
+        render::BindGroupBuilder::new()
+            .append_sampler(&self.sampler)
+            .append_texture_view_array(&views)
+            .build(
+                &render_state.wgpu.device,
+                Some("wormhole shadow map bind group"),
+                &render_state.bind_groups.shadow_maps,
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clamp_resolution;
+
+    #[test]
+    fn resolution_is_clamped_to_device_limits() {
+        assert_eq!(clamp_resolution(1024, 8192), 1024);
+        assert_eq!(clamp_resolution(0, 8192), 1);
+        assert_eq!(clamp_resolution(16384, 8192), 8192);
+        assert_eq!(clamp_resolution(8192, 8192), 8192);
+    }
+}
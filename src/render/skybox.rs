@@ -0,0 +1,85 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::assets;
+
+use bevy_ecs::prelude::*;
+
+/// What is drawn where there is no geometry. Without this resource, those pixels stay black.
+#[derive(Resource)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Skybox {
+    pub source: Source,
+    // Multiplies the sky color, low dynamic range textures usually need brightening to match the lights
+    pub intensity: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// The environment cube map used for image based lighting.
+    Environment,
+    /// A single latitude/longitude texture.
+    Equirectangular(assets::TextureId),
+    /// One texture per face, in +X, -X, +Y, -Y, +Z, -Z order.
+    Cubemap([assets::TextureId; 6]),
+}
+
+// Matches the push constants in skybox.wgsl
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyboxConstants {
+    pub inv_view_proj: glam::Mat4,
+    pub view_pos: glam::Vec3,
+    pub source: u32,
+    pub intensity: f32,
+    pub faces: [u32; 6],
+    _padding: [u32; 1],
+}
+
+impl Skybox {
+    pub fn new(source: Source) -> Self {
+        Self {
+            source,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn constants(
+        &self,
+        view_proj: glam::Mat4,
+        view_pos: glam::Vec3,
+        textures: &assets::Textures,
+    ) -> SkyboxConstants {
+        let index =
+            |id: assets::TextureId| textures.id_to_bindgroup_index(id).unwrap_or_default() as u32;
+        let (source, faces) = match self.source {
+            Source::Environment => (0, [0; 6]),
+            Source::Equirectangular(id) => (1, [index(id), 0, 0, 0, 0, 0]),
+            Source::Cubemap(ids) => (2, ids.map(index)),
+        };
+
+        SkyboxConstants {
+            inv_view_proj: view_proj.inverse(),
+            view_pos,
+            source,
+            intensity: self.intensity,
+            faces,
+            _padding: [0; 1],
+        }
+    }
+}
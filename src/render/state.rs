@@ -25,45 +25,81 @@ pub struct State {
     pub wgpu: GpuState,
     pub bind_groups: BindGroups,
     pub pipelines: RenderPipelines,
+    pub shaders: shaders::Registry,
 }
 
 pub const MAX_TEXTURE_COUNT: u32 = 1 << 17;
+pub const MAX_SAMPLER_COUNT: u32 = 8;
 const BGL_DIVISOR: u32 = 4;
 
 #[derive(Debug)]
 pub struct GpuState {
     pub instance: wgpu::Instance,
-    // this 'static is probably wrong
-    pub surface: wgpu::Surface<'static>,
+    pub target: RenderTarget,
+    // Describes the render target even when there is no surface (size & format)
     pub surface_config: wgpu::SurfaceConfiguration,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
 }
 
+#[derive(Debug)]
+pub enum RenderTarget {
+    // this 'static is probably wrong
+    Surface(wgpu::Surface<'static>),
+    // Used when running headless (tests, CI, dedicated servers)
+    Offscreen(wgpu::Texture),
+}
+
+pub enum Frame<'tex> {
+    Surface(wgpu::SurfaceTexture),
+    Offscreen(&'tex wgpu::Texture),
+}
+
 #[derive(Debug)]
 pub struct BindGroups {
     pub object_data: wgpu::BindGroupLayout,
     pub materials: wgpu::BindGroupLayout,
+    pub custom_material: wgpu::BindGroupLayout,
     pub gbuffer: wgpu::BindGroupLayout,
     pub light_data: wgpu::BindGroupLayout,
+    pub shadow_maps: wgpu::BindGroupLayout,
+    pub light_culling: wgpu::BindGroupLayout,
+    pub tonemap: wgpu::BindGroupLayout,
+    pub exposure: wgpu::BindGroupLayout,
+    pub post_process: wgpu::BindGroupLayout,
+    pub color_grading_lut: wgpu::BindGroupLayout,
+    pub environment_target: wgpu::BindGroupLayout,
+    pub environment_source: wgpu::BindGroupLayout,
+    pub environment_equirect: wgpu::BindGroupLayout,
+    pub skybox: wgpu::BindGroupLayout,
 }
 
 #[derive(Debug)]
 pub struct RenderPipelines {
-    pub object: wgpu::RenderPipeline,
+    pub transparent: wgpu::RenderPipeline,
     pub light: wgpu::RenderPipeline,
     pub light_object: wgpu::RenderPipeline,
+    pub skybox: wgpu::RenderPipeline,
+    pub mipmap: Vec<wgpu::RenderPipeline>,
+    pub shadow: wgpu::RenderPipeline,
+    pub light_culling: wgpu::ComputePipeline,
+    pub tonemap: wgpu::RenderPipeline,
+    pub exposure: wgpu::ComputePipeline,
+    pub bloom_downsample: wgpu::RenderPipeline,
+    pub bloom_blur: wgpu::RenderPipeline,
+    pub bloom_composite: wgpu::RenderPipeline,
+    pub fxaa: wgpu::RenderPipeline,
+    pub color_grade: wgpu::RenderPipeline,
+    pub equirect_to_cube: wgpu::ComputePipeline,
+    pub environment_irradiance: wgpu::ComputePipeline,
+    pub environment_prefilter: wgpu::ComputePipeline,
+    pub environment_brdf_lut: wgpu::ComputePipeline,
 }
 
 impl GpuState {
     async fn new(window: std::sync::Arc<winit::window::Window>) -> Self {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
-            dx12_shader_compiler: wgpu::Dx12Compiler::default(), // FIXME: support up-to-date DX12 compiler
-            flags: wgpu::InstanceFlags::from_build_config(),
-            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
-        });
+        let instance = create_instance();
 
         let surface = instance
             .create_surface(window.clone())
@@ -79,42 +115,7 @@ impl GpuState {
             .await
             .expect("failed to create adapter");
 
-        let info = adapter.get_info();
-        let adapter_limits = adapter.limits();
-        log::info!("Backend : {:?}", info.backend);
-        log::info!("Device  : {}", info.name);
-        log::info!("Driver  : {} {}", info.driver, info.driver_info);
-        log::info!("-- Limits --");
-        log::info!(
-            "Max buffer size (MB) : {}",
-            adapter_limits.max_buffer_size / 1048576
-        );
-        log::info!(
-            "Max sampled textures : {}",
-            adapter_limits.max_sampled_textures_per_shader_stage
-        );
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("wgpu device"),
-                    required_limits: wgpu::Limits {
-                        max_push_constant_size: 128,
-                        max_sampled_textures_per_shader_stage: adapter_limits
-                            .max_sampled_textures_per_shader_stage,
-                        ..Default::default()
-                    },
-                    required_features: wgpu::Features::PUSH_CONSTANTS
-                        | wgpu::Features::TEXTURE_BINDING_ARRAY
-                        | wgpu::Features::INDIRECT_FIRST_INSTANCE
-                        | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING // TODO: do we need this?
-                        | wgpu::Features::PARTIALLY_BOUND_BINDING_ARRAY
-                        | wgpu::Features::MULTI_DRAW_INDIRECT,
-                },
-                None,
-            )
-            .await
-            .expect("failed to request device");
+        let (device, queue) = request_device(&adapter).await;
 
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps
@@ -137,13 +138,168 @@ impl GpuState {
 
         Self {
             instance,
-            surface,
+            target: RenderTarget::Surface(surface),
             surface_config,
             adapter,
             device,
             queue,
         }
     }
+
+    async fn new_headless(width: u32, height: u32) -> Self {
+        let instance = create_instance();
+
+        let power_preference = wgpu::util::power_preference_from_env()
+            .unwrap_or(wgpu::PowerPreference::HighPerformance);
+        let adapter = match instance
+            .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                power_preference,
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+        {
+            Some(adapter) => adapter,
+            // No hardware adapter, try a software one instead (lavapipe, WARP, etc)
+            None => instance
+                .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                    power_preference,
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                })
+                .await
+                .expect("failed to create adapter"),
+        };
+
+        let (device, queue) = request_device(&adapter).await;
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Immediate,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let texture = create_offscreen_texture(&device, &surface_config);
+
+        Self {
+            instance,
+            target: RenderTarget::Offscreen(texture),
+            surface_config,
+            adapter,
+            device,
+            queue,
+        }
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen(_))
+    }
+
+    pub fn current_frame(&self) -> Result<Frame<'_>, wgpu::SurfaceError> {
+        match &self.target {
+            RenderTarget::Surface(surface) => surface.get_current_texture().map(Frame::Surface),
+            RenderTarget::Offscreen(texture) => Ok(Frame::Offscreen(texture)),
+        }
+    }
+
+    pub fn configure_target(&mut self) {
+        match &mut self.target {
+            RenderTarget::Surface(surface) => surface.configure(&self.device, &self.surface_config),
+            RenderTarget::Offscreen(texture) => {
+                *texture = create_offscreen_texture(&self.device, &self.surface_config);
+            }
+        }
+    }
+}
+
+impl Frame<'_> {
+    pub fn texture(&self) -> &wgpu::Texture {
+        match self {
+            Frame::Surface(surface_texture) => &surface_texture.texture,
+            Frame::Offscreen(texture) => texture,
+        }
+    }
+
+    pub fn present(self) {
+        // Offscreen frames don't need presenting, they're just left in the texture
+        if let Frame::Surface(surface_texture) = self {
+            surface_texture.present();
+        }
+    }
+}
+
+fn create_instance() -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+        dx12_shader_compiler: wgpu::Dx12Compiler::default(), // FIXME: support up-to-date DX12 compiler
+        flags: wgpu::InstanceFlags::from_build_config(),
+        gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+    })
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+    let info = adapter.get_info();
+    let adapter_limits = adapter.limits();
+    log::info!("Backend : {:?}", info.backend);
+    log::info!("Device  : {}", info.name);
+    log::info!("Driver  : {} {}", info.driver, info.driver_info);
+    log::info!("-- Limits --");
+    log::info!(
+        "Max buffer size (MB) : {}",
+        adapter_limits.max_buffer_size / 1048576
+    );
+    log::info!(
+        "Max sampled textures : {}",
+        adapter_limits.max_sampled_textures_per_shader_stage
+    );
+
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("wgpu device"),
+                required_limits: wgpu::Limits {
+                    max_push_constant_size: 128,
+                    max_sampled_textures_per_shader_stage: adapter_limits
+                        .max_sampled_textures_per_shader_stage,
+                    ..Default::default()
+                },
+                required_features: wgpu::Features::PUSH_CONSTANTS
+                    | wgpu::Features::TEXTURE_BINDING_ARRAY
+                    | wgpu::Features::INDIRECT_FIRST_INSTANCE
+                    | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING // TODO: do we need this?
+                    | wgpu::Features::PARTIALLY_BOUND_BINDING_ARRAY
+                    | wgpu::Features::MULTI_DRAW_INDIRECT
+                    // Optional, compressed textures are decoded on the cpu without it
+                    | (adapter.features() & wgpu::Features::TEXTURE_COMPRESSION_BC),
+            },
+            None,
+        )
+        .await
+        .expect("failed to request device")
+}
+
+fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("wormhole offscreen render target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
 }
 
 fn initialize_bind_group_layouts(gpu_state: &GpuState) -> BindGroups {
@@ -159,6 +315,11 @@ fn initialize_bind_group_layouts(gpu_state: &GpuState) -> BindGroups {
     };
     const GENERIC_SAMPLER: wgpu::BindingType =
         wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering);
+    const CUBE_TEXTURE: wgpu::BindingType = wgpu::BindingType::Texture {
+        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        view_dimension: wgpu::TextureViewDimension::Cube,
+        multisampled: false,
+    };
 
     let object_data = render::BindGroupLayoutBuilder::new()
         // transforms
@@ -183,8 +344,12 @@ fn initialize_bind_group_layouts(gpu_state: &GpuState) -> BindGroups {
         (limits.max_sampled_textures_per_shader_stage / BGL_DIVISOR).min(MAX_TEXTURE_COUNT);
 
     let materials = render::BindGroupLayoutBuilder::new()
-        // Sampler
-        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_SAMPLER, None)
+        // Samplers
+        .append(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            std::num::NonZeroU32::new(MAX_SAMPLER_COUNT),
+        )
         // Textures
         .append(
             wgpu::ShaderStages::FRAGMENT,
@@ -194,11 +359,21 @@ fn initialize_bind_group_layouts(gpu_state: &GpuState) -> BindGroups {
         )
         // Material data
         .append(wgpu::ShaderStages::FRAGMENT, GENERIC_STORAGE, None)
+        // Texture sampler indices
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_STORAGE, None)
         .build(
             &gpu_state.device,
             Some("wormhole material data bind group layout"),
         );
 
+    let custom_material = render::BindGroupLayoutBuilder::new()
+        // Data of every material of one custom material type
+        .append(wgpu::ShaderStages::VERTEX_FRAGMENT, GENERIC_STORAGE, None)
+        .build(
+            &gpu_state.device,
+            Some("wormhole custom material data bind group layout"),
+        );
+
     let gbuffer = render::BindGroupLayoutBuilder::new()
         // Sampler
         .append(wgpu::ShaderStages::FRAGMENT, GENERIC_SAMPLER, None)
@@ -216,17 +391,208 @@ fn initialize_bind_group_layouts(gpu_state: &GpuState) -> BindGroups {
         );
 
     let light_data = render::BindGroupLayoutBuilder::new()
+        // Lights
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_STORAGE, None)
+        // Light indices per tile
         .append(wgpu::ShaderStages::FRAGMENT, GENERIC_STORAGE, None)
+        // Environment sampler
+        .append(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            None,
+        )
+        // Irradiance
+        .append(wgpu::ShaderStages::FRAGMENT, CUBE_TEXTURE, None)
+        // Prefiltered specular
+        .append(wgpu::ShaderStages::FRAGMENT, CUBE_TEXTURE, None)
+        // BRDF lookup table
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_TEXTURE, None)
         .build(
             &gpu_state.device,
             Some("wormhole light data bind group layout"),
         );
 
+    let shadow_maps = render::BindGroupLayoutBuilder::new()
+        // Sampler
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_SAMPLER, None)
+        // Cube maps, one per shadow casting light
+        .append(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::Cube,
+                multisampled: false,
+            },
+            std::num::NonZeroU32::new(render::shadow::MAX_SHADOW_MAPS),
+        )
+        .build(
+            &gpu_state.device,
+            Some("wormhole shadow map bind group layout"),
+        );
+
+    let light_culling = render::BindGroupLayoutBuilder::new()
+        // Lights
+        .append(wgpu::ShaderStages::COMPUTE, GENERIC_STORAGE, None)
+        // Light indices per tile
+        .append(
+            wgpu::ShaderStages::COMPUTE,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            None,
+        )
+        // Depth
+        .append(
+            wgpu::ShaderStages::COMPUTE,
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Depth,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            None,
+        )
+        .build(
+            &gpu_state.device,
+            Some("wormhole light culling bind group layout"),
+        );
+
+    let tonemap = render::BindGroupLayoutBuilder::new()
+        // Sampler
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_SAMPLER, None)
+        // HDR color
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_TEXTURE, None)
+        // Exposure state
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_STORAGE, None)
+        .build(
+            &gpu_state.device,
+            Some("wormhole tonemap bind group layout"),
+        );
+
+    let exposure = render::BindGroupLayoutBuilder::new()
+        // HDR color
+        .append(wgpu::ShaderStages::COMPUTE, GENERIC_TEXTURE, None)
+        // Exposure state
+        .append(
+            wgpu::ShaderStages::COMPUTE,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            None,
+        )
+        .build(
+            &gpu_state.device,
+            Some("wormhole exposure bind group layout"),
+        );
+
+    // Post processing passes filter their source, unlike the passes reading the gbuffer
+    const FILTERING_SAMPLER: wgpu::BindingType =
+        wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering);
+
+    let post_process = render::BindGroupLayoutBuilder::new()
+        // Sampler
+        .append(wgpu::ShaderStages::FRAGMENT, FILTERING_SAMPLER, None)
+        // Source
+        .append(wgpu::ShaderStages::FRAGMENT, GENERIC_TEXTURE, None)
+        .build(
+            &gpu_state.device,
+            Some("wormhole post process bind group layout"),
+        );
+
+    let color_grading_lut = render::BindGroupLayoutBuilder::new()
+        // Sampler
+        .append(wgpu::ShaderStages::FRAGMENT, FILTERING_SAMPLER, None)
+        // LUT
+        .append(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D3,
+                multisampled: false,
+            },
+            None,
+        )
+        .build(
+            &gpu_state.device,
+            Some("wormhole color grading lut bind group layout"),
+        );
+
+    let environment_target = render::BindGroupLayoutBuilder::new()
+        // Faces (or the layers of any other texture) being written to
+        .append(
+            wgpu::ShaderStages::COMPUTE,
+            wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: render::environment::ENVIRONMENT_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+            },
+            None,
+        )
+        .build(
+            &gpu_state.device,
+            Some("wormhole environment target bind group layout"),
+        );
+
+    let environment_source = render::BindGroupLayoutBuilder::new()
+        // Sampler
+        .append(
+            wgpu::ShaderStages::COMPUTE,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            None,
+        )
+        // Environment cube map
+        .append(wgpu::ShaderStages::COMPUTE, CUBE_TEXTURE, None)
+        .build(
+            &gpu_state.device,
+            Some("wormhole environment source bind group layout"),
+        );
+
+    let environment_equirect = render::BindGroupLayoutBuilder::new()
+        // Equirectangular image
+        .append(
+            wgpu::ShaderStages::COMPUTE,
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            None,
+        )
+        .build(
+            &gpu_state.device,
+            Some("wormhole environment equirect bind group layout"),
+        );
+
+    let skybox = render::BindGroupLayoutBuilder::new()
+        // Environment sampler
+        .append(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            None,
+        )
+        // Environment cube map
+        .append(wgpu::ShaderStages::FRAGMENT, CUBE_TEXTURE, None)
+        .build(&gpu_state.device, Some("wormhole skybox bind group layout"));
+
     BindGroups {
         object_data,
         materials,
+        custom_material,
         gbuffer,
         light_data,
+        shadow_maps,
+        light_culling,
+        tonemap,
+        exposure,
+        post_process,
+        color_grading_lut,
+        environment_target,
+        environment_source,
+        environment_equirect,
+        skybox,
     }
 }
 
@@ -235,65 +601,181 @@ fn initialize_bind_group_layouts(gpu_state: &GpuState) -> BindGroups {
 pub fn initialize_render_pipelines(
     gpu_state: &GpuState,
     bind_groups: &BindGroups,
+    registry: &mut shaders::Registry,
 ) -> RenderPipelines {
-    let mut composer = naga_oil::compose::Composer::default()
-            .with_capabilities(wgpu::naga::valid::Capabilities::PUSH_CONSTANT | wgpu::naga::valid::Capabilities::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
-    let object =
-        match shaders::object::create_render_pipeline(&mut composer, gpu_state, bind_groups) {
-            Ok(p) => p,
-            Err(err) => {
-                let err = err.emit_to_string(&composer);
-                panic!("Error creating object render pipeline:\n{err}")
-            }
-        };
-    let light =
-        match shaders::light::create_light_render_pipeline(&mut composer, gpu_state, bind_groups) {
-            Ok(p) => p,
-            Err(err) => {
-                let err = err.emit_to_string(&composer);
-                panic!("Error creating light render pipeline:\n{err}")
-            }
-        };
-    let light_object = match shaders::light::create_light_object_render_pipeline(
-        &mut composer,
-        gpu_state,
-        bind_groups,
-    ) {
-        Ok(p) => p,
-        Err(err) => {
-            let err = err.emit_to_string(&composer);
-            panic!("Error creating light object render pipeline:\n{err}")
-        }
-    };
+    try_initialize_render_pipelines(gpu_state, bind_groups, registry)
+        .unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Like [`initialize_render_pipelines`], but returns shader compile errors instead of panicking.
+pub fn try_initialize_render_pipelines(
+    gpu_state: &GpuState,
+    bind_groups: &BindGroups,
+    registry: &mut shaders::Registry,
+) -> Result<RenderPipelines, String> {
+    let transparent =
+        shaders::object::create_transparent_render_pipeline(registry, gpu_state, bind_groups)
+            .map_err(|err| format!("Error creating transparent render pipeline:\n{err}"))?;
+    let light = shaders::light::create_light_render_pipeline(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating light render pipeline:\n{err}"))?;
+    let light_object =
+        shaders::light::create_light_object_render_pipeline(registry, gpu_state, bind_groups)
+            .map_err(|err| format!("Error creating light object render pipeline:\n{err}"))?;
+
+    let skybox = shaders::skybox::create_skybox_render_pipeline(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating skybox render pipeline:\n{err}"))?;
+
+    let mipmap = shaders::mipmap::create_mipmap_pipelines(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating mipmap render pipelines:\n{err}"))?;
+
+    let shadow = shaders::shadow::create_shadow_render_pipeline(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating shadow render pipeline:\n{err}"))?;
+
+    let light_culling =
+        shaders::light::create_light_culling_pipeline(registry, gpu_state, bind_groups)
+            .map_err(|err| format!("Error creating light culling pipeline:\n{err}"))?;
+
+    let tonemap =
+        shaders::tonemap::create_tonemap_render_pipeline(registry, gpu_state, bind_groups)
+            .map_err(|err| format!("Error creating tonemap render pipeline:\n{err}"))?;
+    let exposure = shaders::tonemap::create_exposure_pipeline(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating exposure pipeline:\n{err}"))?;
+
+    let shaders::post::BloomPipelines {
+        downsample: bloom_downsample,
+        blur: bloom_blur,
+        composite: bloom_composite,
+    } = shaders::post::create_bloom_pipelines(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating bloom render pipelines:\n{err}"))?;
+    let fxaa = shaders::post::create_fxaa_pipeline(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating fxaa render pipeline:\n{err}"))?;
+    let color_grade = shaders::post::create_color_grade_pipeline(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating color grade render pipeline:\n{err}"))?;
+
+    let shaders::environment::EnvironmentPipelines {
+        equirect_to_cube,
+        irradiance: environment_irradiance,
+        prefilter: environment_prefilter,
+        brdf_lut: environment_brdf_lut,
+    } = shaders::environment::create_environment_pipelines(registry, gpu_state, bind_groups)
+        .map_err(|err| format!("Error creating environment pipelines:\n{err}"))?;
 
-    RenderPipelines {
-        object,
+    Ok(RenderPipelines {
+        transparent,
         light,
         light_object,
+        skybox,
+        mipmap,
+        shadow,
+        light_culling,
+        tonemap,
+        exposure,
+        bloom_downsample,
+        bloom_blur,
+        bloom_composite,
+        fxaa,
+        color_grade,
+        equirect_to_cube,
+        environment_irradiance,
+        environment_prefilter,
+        environment_brdf_lut,
+    })
+}
+
+/// Creates the shader registry and every permutation of the built in pipelines in it.
+pub fn initialize_shader_registry(
+    gpu_state: &GpuState,
+    bind_groups: &BindGroups,
+) -> shaders::Registry {
+    let mut registry = shaders::Registry::new().unwrap_or_else(|err| panic!("{err}"));
+    prepare_builtin_pipelines(&mut registry, gpu_state, bind_groups)
+        .unwrap_or_else(|err| panic!("{err}"));
+    registry
+}
+
+fn prepare_builtin_pipelines(
+    registry: &mut shaders::Registry,
+    gpu_state: &GpuState,
+    bind_groups: &BindGroups,
+) -> Result<(), String> {
+    for permutation in shaders::object::Permutation::all() {
+        shaders::object::prepare_render_pipeline(registry, gpu_state, bind_groups, permutation)
+            .map_err(|err| format!("Error creating object render pipeline:\n{err}"))?;
     }
+    Ok(())
 }
 
 impl State {
     pub async fn new(window: std::sync::Arc<winit::window::Window>) -> Self {
         let gpu_state = GpuState::new(window).await;
         let bind_groups = initialize_bind_group_layouts(&gpu_state);
-        let pipelines = initialize_render_pipelines(&gpu_state, &bind_groups);
+        let mut shaders = initialize_shader_registry(&gpu_state, &bind_groups);
+        let pipelines = initialize_render_pipelines(&gpu_state, &bind_groups, &mut shaders);
 
         State {
             wgpu: gpu_state,
             bind_groups,
             pipelines,
+            shaders,
         }
     }
 
+    /// Creates a render state that draws into an offscreen texture instead of a window surface.
+    /// Falls back to a software adapter if no hardware adapter is available.
+    ///
+    /// This still needs an adapter with the features wormhole uses.
+    /// Use [`crate::scene::add_simulation_plugins`] to run scenes on machines without one.
+    pub async fn new_headless(width: u32, height: u32) -> Self {
+        let gpu_state = GpuState::new_headless(width, height).await;
+        let bind_groups = initialize_bind_group_layouts(&gpu_state);
+        let mut shaders = initialize_shader_registry(&gpu_state, &bind_groups);
+        let pipelines = initialize_render_pipelines(&gpu_state, &bind_groups, &mut shaders);
+
+        State {
+            wgpu: gpu_state,
+            bind_groups,
+            pipelines,
+            shaders,
+        }
+    }
+
+    /// Recompiles every shader and recreates the render pipelines.
+    /// The current pipelines are kept if any shader fails to compile.
+    pub fn reload_pipelines(&mut self) -> Result<(), String> {
+        // naga_oil validates the shaders, but wgpu may still reject the pipelines
+        self.wgpu
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        let result = self
+            .shaders
+            .reloaded()
+            .map_err(|err| err.to_string())
+            .and_then(|mut shaders| {
+                prepare_builtin_pipelines(&mut shaders, &self.wgpu, &self.bind_groups)?;
+                let pipelines =
+                    try_initialize_render_pipelines(&self.wgpu, &self.bind_groups, &mut shaders)?;
+                Ok((shaders, pipelines))
+            });
+        if let Some(error) = pollster::block_on(self.wgpu.device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+
+        (self.shaders, self.pipelines) = result?;
+        Ok(())
+    }
+
+    /// Creates every object pipeline permutation the shader registry doesn't have cached.
+    /// Permutations that failed to be created are only retried once the registry changes.
+    pub fn prepare_object_pipelines(&mut self) -> Result<(), String> {
+        prepare_builtin_pipelines(&mut self.shaders, &self.wgpu, &self.bind_groups)
+    }
+
     pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
         if size.width > 0 && size.height > 0 {
             self.wgpu.surface_config.width = size.width;
             self.wgpu.surface_config.height = size.height;
 
-            self.wgpu
-                .surface
-                .configure(&self.wgpu.device, &self.wgpu.surface_config)
+            self.wgpu.configure_target();
         }
     }
 }
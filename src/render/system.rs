@@ -16,22 +16,31 @@
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 use crate::assets;
 use crate::components;
+use crate::input;
 use crate::player;
 use crate::render;
 use crate::scene;
+use crate::shaders;
+use crate::time;
 
 use bevy_ecs::prelude::*;
 
 use itertools::Itertools;
 
+#[allow(clippy::too_many_arguments)]
 pub fn render(
     render_state: Res<render::State>,
     mut buffers: ResMut<scene::Buffers>,
     mut meshes: ResMut<scene::Meshes>,
     mut assets: ResMut<assets::Loader>,
     player: Res<player::Player>,
-    object_query: Query<(&components::Transform, &components::MeshRenderer)>,
-    light_query: Query<(&components::Transform, &components::Light)>,
+    tonemap_settings: Res<render::tonemap::Settings>,
+    post_settings: Res<render::post::Settings>,
+    environment: Res<render::environment::Environment>,
+    skybox: Option<Res<render::skybox::Skybox>>,
+    time: Option<Res<time::Time>>,
+    object_query: Query<(&components::GlobalTransform, &components::MeshRenderer)>,
+    light_query: Query<(&components::GlobalTransform, &components::Light)>,
 ) {
     let mut encoder =
         render_state
@@ -51,15 +60,18 @@ pub fn render(
 
     let (vertex_buffers, index_buffer) = meshes.as_bind_group_index_buffer();
 
-    let default_texture_sampler = &buffers.gbuffer.sampler;
+    assets.textures.update_sampler_buffer(&render_state);
     let material_buffer = assets
         .materials
         .get_or_update_buffer(&render_state, &assets.textures);
+    let texture_samplers = assets.textures.get_samplers();
     let texture_views = assets.textures.get_texture_views();
+    let sampler_buffer = assets.textures.sampler_buffer();
     let material_data = render::BindGroupBuilder::new()
-        .append_sampler(default_texture_sampler)
+        .append_sampler_array(&texture_samplers)
         .append_texture_view_array(&texture_views)
         .append_buffer(material_buffer)
+        .append_buffer(sampler_buffer)
         .build(
             &render_state.wgpu.device,
             Some("wormhole material data"),
@@ -70,36 +82,100 @@ pub fn render(
         transforms: buffers.transforms.start_write(),
         lights: buffers.lights.start_write(),
         instances: buffers.instances.start_write(),
+        object_draws: buffers.object_draws.start_write(),
+        transparent_draws: buffers.transparent_draws.start_write(),
         assets,
     };
 
+    let camera_data = player.camera.as_camera_data(player.transform);
+
     let prepared_objects = object_query
         .iter()
         .map(|(transform, object)| {
             let transform_index = resources.transforms.push(transform) as u32;
-            object.prepare(transform_index, &mut resources)
+            object.prepare(transform, transform_index)
         })
         .collect_vec();
+    let object_pipeline_draws = components::mesh_renderer::PreparedMesh::push_batched(
+        prepared_objects,
+        camera_data.view_pos,
+        &mut resources,
+    );
+
+    // The material data of every custom material type drawn this frame
+    let custom_material_data = object_pipeline_draws
+        .iter()
+        .filter_map(|(pipeline, _)| match pipeline {
+            render::material::Pipeline::Custom(type_id, _) => Some(*type_id),
+            render::material::Pipeline::Object(_) => None,
+        })
+        .unique()
+        .filter_map(|type_id| {
+            let buffer = resources.assets.materials.custom_buffer(type_id)?;
+            let bind_group = render::BindGroupBuilder::new().append_buffer(buffer).build(
+                &render_state.wgpu.device,
+                Some("wormhole custom material data"),
+                &render_state.bind_groups.custom_material,
+            );
+            Some((type_id, bind_group))
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+
+    buffers.shadow_maps.start_frame();
+    let mut shadow_casters = Vec::new();
 
     let prepared_light_objects = light_query
         .iter()
         .map(|(transform, light)| {
             let transform_index = resources.transforms.push(transform) as u32;
-            light.prepare_light(transform.position, &mut resources);
+
+            let shadow_index = light.shadow_settings().and_then(|settings| {
+                let index = buffers
+                    .shadow_maps
+                    .allocate(&render_state, settings.resolution)?;
+                shadow_casters.push((index, transform.translation(), settings));
+                Some(index)
+            });
+
+            light.prepare_light(transform, shadow_index, &mut resources);
             light.prepare_object(transform_index, &mut resources)
         })
         .collect_vec();
 
     let instance_buffer = resources.instances.finish(&render_state);
 
+    let object_draw_count = resources.object_draws.draw_count();
+    let object_draw_buffer = resources.object_draws.finish(&render_state);
+    let transparent_draw_count = resources.transparent_draws.draw_count();
+    let transparent_draw_buffer = resources.transparent_draws.finish(&render_state);
+
+    let light_count = prepared_light_objects.len() as u32;
     let light_buffer = resources.lights.finish(&render_state);
+
+    buffers.light_tiles.resize_to_screen(&render_state);
+    let tile_light_indices = &buffers.light_tiles.tile_light_indices;
+
     let light_data = render::BindGroupBuilder::new()
         .append_buffer(light_buffer)
+        .append_buffer(tile_light_indices)
+        .append_sampler(&environment.sampler)
+        .append_texture_view(&environment.irradiance.view)
+        .append_texture_view(&environment.prefiltered.view)
+        .append_texture_view(&environment.brdf_lut.view)
         .build(
             &render_state.wgpu.device,
             Some("wormhole light data"),
             &render_state.bind_groups.light_data,
         );
+    let light_culling_data = render::BindGroupBuilder::new()
+        .append_buffer(light_buffer)
+        .append_buffer(tile_light_indices)
+        .append_texture_view(&buffers.gbuffer.depth.view)
+        .build(
+            &render_state.wgpu.device,
+            Some("wormhole light culling data"),
+            &render_state.bind_groups.light_culling,
+        );
 
     let transform_buffer = resources.transforms.finish(&render_state);
     let object_data = render::BindGroupBuilder::new()
@@ -115,7 +191,65 @@ pub fn render(
             &render_state.bind_groups.object_data,
         );
 
-    let camera_data = player.camera.as_camera_data(player.transform);
+    encoder.pop_debug_group();
+
+    encoder.push_debug_group("wormhole shadow pass");
+
+    for (shadow_index, light_position, settings) in shadow_casters {
+        let shadow_map = buffers.shadow_maps.get(shadow_index);
+        let view_projs =
+            render::shadow::ShadowMap::face_view_projs(light_position, settings.near, settings.far);
+
+        for (face_view, view_proj) in shadow_map.face_views.iter().zip(view_projs) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("wormhole shadow pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: face_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        // Anything that wasn't drawn to is as far away as possible
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &shadow_map.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            if object_draw_count == 0 {
+                continue;
+            }
+
+            render_pass.set_pipeline(&render_state.pipelines.shadow);
+
+            render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+            render_pass.set_bind_group(0, &object_data, &[]);
+
+            render_pass.set_push_constants(
+                wgpu::ShaderStages::VERTEX_FRAGMENT,
+                0,
+                bytemuck::bytes_of(&render::shadow::ShadowConstants {
+                    view_proj,
+                    light_position,
+                    far: settings.far,
+                }),
+            );
+
+            render_pass.multi_draw_indexed_indirect(object_draw_buffer, 0, object_draw_count);
+        }
+    }
+
+    let shadow_map_data = buffers.shadow_maps.finish(&render_state);
 
     encoder.pop_debug_group();
 
@@ -129,54 +263,107 @@ pub fn render(
         occlusion_query_set: None,
     });
 
-    render_pass.set_pipeline(&render_state.pipelines.object);
+    for (pipeline, draws) in object_pipeline_draws {
+        match pipeline {
+            render::material::Pipeline::Object(permutation) => {
+                // Falls back to the default permutation if this one failed to compile
+                let Some(pipeline) = render_state
+                    .shaders
+                    .pipeline(&permutation.pipeline_key())
+                    .or_else(|| {
+                        let key = shaders::object::Permutation::default().pipeline_key();
+                        render_state.shaders.pipeline(&key)
+                    })
+                else {
+                    continue;
+                };
+                render_pass.set_pipeline(pipeline);
+            }
+            render::material::Pipeline::Custom(type_id, index) => {
+                let Some(pipeline) = assets
+                    .materials
+                    .custom_pipeline_key(type_id, index)
+                    .and_then(|key| render_state.shaders.pipeline(key))
+                else {
+                    continue;
+                };
+                let Some(custom_material_data) = custom_material_data.get(&type_id) else {
+                    continue;
+                };
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(2, custom_material_data, &[]);
+            }
+        }
 
-    render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
-    render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
 
-    render_pass.set_bind_group(0, &object_data, &[]);
-    render_pass.set_bind_group(1, &material_data, &[]);
+        render_pass.set_bind_group(0, &object_data, &[]);
+        render_pass.set_bind_group(1, &material_data, &[]);
 
-    render_pass.set_push_constants(
-        wgpu::ShaderStages::VERTEX,
-        0,
-        bytemuck::bytes_of(&camera_data.view_proj),
-    );
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX,
+            0,
+            bytemuck::bytes_of(&camera_data.view_proj),
+        );
 
-    for prepared in prepared_objects {
-        prepared.draw(&mut render_pass);
+        let draw_size = std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>();
+        render_pass.multi_draw_indexed_indirect(
+            object_draw_buffer,
+            (draws.start as usize * draw_size) as wgpu::BufferAddress,
+            draws.len() as u32,
+        );
     }
 
     drop(render_pass);
 
     encoder.pop_debug_group();
 
-    encoder.push_debug_group("wormhole lighting pass");
+    encoder.push_debug_group("wormhole light culling pass");
 
-    let output = match render_state.wgpu.surface.get_current_texture() {
-        Ok(texture) => texture,
-        Err(error @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
-            render_state
-                .wgpu
-                .surface
-                .configure(&render_state.wgpu.device, &render_state.wgpu.surface_config);
+    let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("wormhole light culling pass"),
+        timestamp_writes: None,
+    });
 
-            eprintln!("surface error: {error:#?}");
+    compute_pass.set_pipeline(&render_state.pipelines.light_culling);
+    compute_pass.set_bind_group(0, &light_culling_data, &[]);
 
-            return;
-        }
-        Err(wgpu::SurfaceError::Timeout) => return,
-        Err(wgpu::SurfaceError::OutOfMemory) => panic!("out of gpu memory. exiting"),
-    };
+    // FIXME: clunky
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+    struct LightCullingPushConstants {
+        inv_view_proj: glam::Mat4,
+        screen_size: glam::UVec2,
+        light_count: u32,
+        _padding: u32,
+    }
+    let surface_config = &render_state.wgpu.surface_config;
+    compute_pass.set_push_constants(
+        0,
+        bytemuck::bytes_of(&LightCullingPushConstants {
+            inv_view_proj: camera_data.view_proj.inverse(),
+            screen_size: glam::uvec2(surface_config.width, surface_config.height),
+            light_count,
+            _padding: 0,
+        }),
+    );
 
-    let output_view = output
-        .texture
-        .create_view(&wgpu::TextureViewDescriptor::default());
+    let (tile_count_x, tile_count_y) =
+        render::buffer::light_tiles::Buffer::tile_count(&render_state);
+    compute_pass.dispatch_workgroups(tile_count_x, tile_count_y, 1);
+
+    drop(compute_pass);
+
+    encoder.pop_debug_group();
+
+    encoder.push_debug_group("wormhole lighting pass");
 
     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         label: Some("wormhole lighting pass"),
         color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-            view: &output_view,
+            view: &buffers.hdr.color.view,
             resolve_target: None,
             ops: wgpu::Operations {
                 load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
@@ -194,6 +381,7 @@ pub fn render(
 
     render_pass.set_bind_group(0, &light_data, &[]);
     render_pass.set_bind_group(1, &buffers.gbuffer.bind_group, &[]);
+    render_pass.set_bind_group(2, &shadow_map_data, &[]);
 
     // FIXME: clunky
     #[repr(C)]
@@ -207,7 +395,7 @@ pub fn render(
         wgpu::ShaderStages::FRAGMENT,
         0,
         bytemuck::bytes_of(&LightPushConstants {
-            light_count: prepared_light_objects.len() as u32,
+            light_count,
             view_pos: camera_data.view_pos,
         }),
     );
@@ -216,10 +404,57 @@ pub fn render(
 
     drop(render_pass);
 
+    if let Some(skybox) = skybox {
+        let skybox_data = render::BindGroupBuilder::new()
+            .append_sampler(&environment.sampler)
+            .append_texture_view(&environment.cube.view)
+            .build(
+                &render_state.wgpu.device,
+                Some("wormhole skybox data"),
+                &render_state.bind_groups.skybox,
+            );
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("wormhole skybox pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &buffers.hdr.color.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(buffers.gbuffer.depth_stencil_attachment()),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&render_state.pipelines.skybox);
+
+        render_pass.set_vertex_buffer(0, buffers.screen_vertices.slice(..));
+
+        render_pass.set_bind_group(0, &material_data, &[]);
+        render_pass.set_bind_group(1, &skybox_data, &[]);
+
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::FRAGMENT,
+            0,
+            bytemuck::bytes_of(&skybox.constants(
+                camera_data.view_proj,
+                camera_data.view_pos,
+                &assets.textures,
+            )),
+        );
+
+        render_pass.draw(0..6, 0..1);
+
+        drop(render_pass);
+    }
+
     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         label: Some("wormhole light box pass"),
         color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-            view: &output_view,
+            view: &buffers.hdr.color.view,
             resolve_target: None,
             ops: wgpu::Operations {
                 load: wgpu::LoadOp::Load,
@@ -252,6 +487,181 @@ pub fn render(
 
     encoder.pop_debug_group();
 
+    if transparent_draw_count > 0 {
+        encoder.push_debug_group("wormhole transparent pass");
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("wormhole transparent pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &buffers.hdr.color.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(buffers.gbuffer.depth_stencil_attachment()),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_pipeline(&render_state.pipelines.transparent);
+
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        render_pass.set_bind_group(0, &object_data, &[]);
+        render_pass.set_bind_group(1, &material_data, &[]);
+        render_pass.set_bind_group(2, &light_data, &[]);
+        render_pass.set_bind_group(3, &shadow_map_data, &[]);
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+        struct TransparentPushConstants {
+            view_proj: glam::Mat4,
+            view_pos: glam::Vec3,
+            tile_count_x: u32,
+        }
+        render_pass.set_push_constants(
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+            0,
+            bytemuck::bytes_of(&TransparentPushConstants {
+                view_proj: camera_data.view_proj,
+                view_pos: camera_data.view_pos,
+                tile_count_x,
+            }),
+        );
+
+        render_pass.multi_draw_indexed_indirect(transparent_draw_buffer, 0, transparent_draw_count);
+
+        drop(render_pass);
+
+        encoder.pop_debug_group();
+    }
+
+    // Bloom goes on before exposure is measured, so bright emissive surfaces darken the scene like other lights
+    if post_settings.bloom.enabled {
+        buffers.post.encode_bloom(
+            &render_state,
+            &mut encoder,
+            &buffers.screen_vertices,
+            &post_settings.bloom,
+            &buffers.gbuffer.emissive.view,
+            &buffers.hdr.color.view,
+        );
+    }
+
+    encoder.push_debug_group("wormhole tonemapping pass");
+
+    if let Some(auto_exposure) = tonemap_settings.auto_exposure {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("wormhole exposure pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&render_state.pipelines.exposure);
+        compute_pass.set_bind_group(0, &buffers.hdr.exposure_bind_group, &[]);
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+        struct ExposurePushConstants {
+            delta_time: f32,
+            speed: f32,
+            key: f32,
+            min_exposure: f32,
+            max_exposure: f32,
+        }
+        compute_pass.set_push_constants(
+            0,
+            bytemuck::bytes_of(&ExposurePushConstants {
+                delta_time: time.map_or(0.0, |t| t.delta_seconds()),
+                speed: auto_exposure.speed,
+                key: auto_exposure.key,
+                min_exposure: auto_exposure.min_exposure,
+                max_exposure: auto_exposure.max_exposure,
+            }),
+        );
+
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    let output = match render_state.wgpu.current_frame() {
+        Ok(frame) => frame,
+        Err(error @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+            if let render::state::RenderTarget::Surface(surface) = &render_state.wgpu.target {
+                surface.configure(&render_state.wgpu.device, &render_state.wgpu.surface_config);
+            }
+
+            eprintln!("surface error: {error:#?}");
+
+            return;
+        }
+        Err(wgpu::SurfaceError::Timeout) => return,
+        Err(wgpu::SurfaceError::OutOfMemory) => panic!("out of gpu memory. exiting"),
+    };
+
+    let output_view = output
+        .texture()
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let ldr_passes = post_settings.ldr_passes();
+
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("wormhole tonemapping pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: buffers.post.tonemap_target(&ldr_passes, &output_view),
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+
+    render_pass.set_pipeline(&render_state.pipelines.tonemap);
+
+    render_pass.set_vertex_buffer(0, buffers.screen_vertices.slice(..));
+
+    render_pass.set_bind_group(0, &buffers.hdr.tonemap_bind_group, &[]);
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    #[derive(bytemuck::Pod, bytemuck::Zeroable)]
+    struct TonemapPushConstants {
+        exposure: f32,
+        tonemapper: u32,
+        auto_exposure: u32,
+    }
+    render_pass.set_push_constants(
+        wgpu::ShaderStages::FRAGMENT,
+        0,
+        bytemuck::bytes_of(&TonemapPushConstants {
+            exposure: tonemap_settings.exposure,
+            tonemapper: tonemap_settings.operator as u32,
+            auto_exposure: tonemap_settings.auto_exposure.is_some() as u32,
+        }),
+    );
+
+    render_pass.draw(0..6, 0..1);
+
+    drop(render_pass);
+
+    encoder.pop_debug_group();
+
+    buffers.post.encode_ldr_passes(
+        &render_state,
+        &mut encoder,
+        &buffers.screen_vertices,
+        &post_settings,
+        &ldr_passes,
+        &output_view,
+    );
+
     render_state
         .wgpu
         .queue
@@ -259,3 +669,46 @@ pub fn render(
 
     output.present();
 }
+
+/// Resizes the render target and screen sized buffers along with the window, and keeps the camera's aspect ratio in sync.
+pub fn resize(
+    input_state: Res<input::State>,
+    mut render_state: ResMut<render::State>,
+    mut scene_buffers: ResMut<scene::Buffers>,
+    player: Option<ResMut<player::Player>>,
+) {
+    let new_size = input_state.new_window_size();
+    if let Some(size) = new_size {
+        render_state.resize(size);
+        scene_buffers.gbuffer.resize_to_screen(&render_state);
+        scene_buffers.hdr.resize_to_screen(&render_state);
+        scene_buffers.post.resize_to_screen(&render_state);
+    }
+
+    // The player plugin may have been built before this one, so its camera can't rely on the aspect ratio at build time
+    if let Some(mut player) = player {
+        if new_size.is_some() || player.is_added() {
+            let surface_config = &render_state.wgpu.surface_config;
+            player.camera.aspect = surface_config.width as f32 / surface_config.height as f32;
+        }
+    }
+}
+
+/// Creates any pipeline that isn't cached by the shader registry and rebuilds the buffers of custom materials,
+/// as that needs the render state mutably.
+pub fn prepare_pipelines(
+    mut render_state: ResMut<render::State>,
+    assets: Option<ResMut<assets::Loader>>,
+) {
+    // Replacing a shader module drops the object pipelines that import it
+    if let Err(err) = render_state.prepare_object_pipelines() {
+        log::error!("{err}");
+    }
+
+    if let Some(mut assets) = assets {
+        let assets = &mut *assets;
+        assets
+            .materials
+            .prepare_custom(&mut render_state, &assets.textures);
+    }
+}
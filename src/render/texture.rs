@@ -14,6 +14,7 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+use crate::assets;
 use crate::render;
 use wgpu::util::DeviceExt;
 
@@ -22,6 +23,7 @@ pub struct Texture {
     pub view: wgpu::TextureView,
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct TextureFormat {
     pub format: wgpu::TextureFormat,
     pub filtering: wgpu::FilterMode,
@@ -55,6 +57,13 @@ impl TextureFormat {
         compare: Some(wgpu::CompareFunction::LessEqual),
     };
 
+    pub const HDR: Self = TextureFormat {
+        format: wgpu::TextureFormat::Rgba16Float,
+        filtering: wgpu::FilterMode::Nearest,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING.union(wgpu::TextureUsages::RENDER_ATTACHMENT),
+        compare: None,
+    };
+
     pub const GBUFFER: Self = TextureFormat {
         format: wgpu::TextureFormat::Rgba16Float,
         filtering: wgpu::FilterMode::Nearest,
@@ -75,6 +84,103 @@ impl TextureFormat {
         };
 }
 
+/// How a material texture is sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerSettings {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // Only used if every filter is linear
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 16,
+        }
+    }
+}
+
+impl SamplerSettings {
+    /// Follows the sampler of a gltf texture, falling back to the defaults for anything it leaves unspecified.
+    pub fn from_gltf(sampler: gltf::texture::Sampler<'_>) -> Self {
+        use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+        let address_mode = |mode| match mode {
+            WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+            WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+        };
+
+        let default = Self::default();
+        let mag_filter = sampler
+            .mag_filter()
+            .map_or(default.mag_filter, |f| match f {
+                MagFilter::Nearest => wgpu::FilterMode::Nearest,
+                MagFilter::Linear => wgpu::FilterMode::Linear,
+            });
+        let (min_filter, mipmap_filter) =
+            sampler
+                .min_filter()
+                .map_or((default.min_filter, default.mipmap_filter), |f| match f {
+                    MinFilter::Nearest | MinFilter::NearestMipmapNearest => {
+                        (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+                    }
+                    MinFilter::Linear | MinFilter::LinearMipmapNearest => {
+                        (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest)
+                    }
+                    MinFilter::NearestMipmapLinear => {
+                        (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear)
+                    }
+                    MinFilter::LinearMipmapLinear => {
+                        (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+                    }
+                });
+
+        Self {
+            address_mode_u: address_mode(sampler.wrap_s()),
+            address_mode_v: address_mode(sampler.wrap_t()),
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+            ..default
+        }
+    }
+
+    pub fn create_sampler(&self, render_state: &render::State) -> wgpu::Sampler {
+        // wgpu only allows anisotropic filtering when everything is filtered linearly
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&f| f == wgpu::FilterMode::Linear);
+
+        render_state
+            .wgpu
+            .device
+            .create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("wormhole material texture sampler"),
+                address_mode_u: self.address_mode_u,
+                address_mode_v: self.address_mode_v,
+                mag_filter: self.mag_filter,
+                min_filter: self.min_filter,
+                mipmap_filter: self.mipmap_filter,
+                anisotropy_clamp: if all_linear {
+                    self.anisotropy_clamp.max(1)
+                } else {
+                    1
+                },
+                ..Default::default()
+            })
+    }
+}
+
 impl Texture {
     pub fn new(render_state: &render::State, size: wgpu::Extent3d, format: TextureFormat) -> Self {
         let texture = render_state
@@ -107,6 +213,8 @@ impl Texture {
         )
     }
 
+    /// Creates a texture from tightly packed rgba8 pixels.
+    /// Mip levels are generated on the gpu when the format supports it.
     pub fn from_bytes(
         render_state: &render::State,
         image: &[u8],
@@ -114,9 +222,20 @@ impl Texture {
         height: u32,
         format: TextureFormat,
     ) -> Self {
-        let texture = render_state.wgpu.device.create_texture_with_data(
-            &render_state.wgpu.queue,
-            &wgpu::TextureDescriptor {
+        let generate_mipmaps = render::mipmap::MIPMAP_FORMATS.contains(&format.format);
+        let (mip_level_count, usage) = if generate_mipmaps {
+            (
+                render::mipmap::mip_level_count(width, height),
+                format.usage | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+        } else {
+            (1, format.usage)
+        };
+
+        let texture = render_state
+            .wgpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
                 label: None,
                 size: wgpu::Extent3d {
                     width,
@@ -124,15 +243,25 @@ impl Texture {
                     depth_or_array_layers: 1,
                 },
                 dimension: wgpu::TextureDimension::D2,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 format: format.format,
-                usage: format.usage,
+                usage,
                 view_formats: &[],
-            },
-            wgpu::util::TextureDataOrder::LayerMajor,
+            });
+        render_state.wgpu.queue.write_texture(
+            texture.as_image_copy(),
             image,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            texture.size(),
         );
+        if generate_mipmaps {
+            render::mipmap::generate(render_state, &texture);
+        }
         let view = texture.create_view(&TextureFormat::DEFAULT_VIEW_DESCRIPTOR);
 
         Self { texture, view }
@@ -147,67 +276,79 @@ impl Texture {
         Self::from_bytes(render_state, &image, image.width(), image.height(), format)
     }
 
-    pub fn from_gltf(
+    /// Creates a texture from a ktx2 file, using the mip levels stored in it.
+    /// Block compressed textures are decoded on the cpu if the device can't sample them directly.
+    pub fn from_ktx2(
         render_state: &render::State,
-        gltf_texture: gltf::Texture<'_>,
-        images: &[gltf::image::Data],
-    ) -> Self {
-        let image_data = images[gltf_texture.source().index()].clone();
-        let image = match image_data.format {
-            gltf::image::Format::R8G8B8 => image::DynamicImage::ImageRgb8(
-                image::ImageBuffer::from_vec(
-                    image_data.width,
-                    image_data.height,
-                    image_data.pixels,
-                )
-                .expect("image pixels too small"),
-            ),
-            gltf::image::Format::R8G8B8A8 => image::DynamicImage::ImageRgba8(
-                image::ImageBuffer::from_vec(
-                    image_data.width,
-                    image_data.height,
-                    image_data.pixels,
-                )
-                .expect("image pixels too small"),
-            ),
-            gltf::image::Format::R8 => image::DynamicImage::ImageLuma8(
-                image::ImageBuffer::from_vec(
-                    image_data.width,
-                    image_data.height,
-                    image_data.pixels,
-                )
-                .expect("image pixels too small"),
-            ),
-            f => panic!("unhandled texture format {f:#?}"),
-        };
-        let image = image.into_rgba8();
+        bytes: &[u8],
+    ) -> Result<Self, render::compressed::Error> {
+        let device = &render_state.wgpu.device;
+        let ktx2 = render::compressed::Ktx2::load(
+            bytes,
+            device.features(),
+            device.limits().max_texture_dimension_2d,
+        )?;
+        Ok(Self::from_ktx2_data(render_state, &ktx2))
+    }
 
+    /// Uploads an already parsed ktx2 file. Its format must be supported by the device.
+    pub fn from_ktx2_data(render_state: &render::State, ktx2: &render::compressed::Ktx2) -> Self {
+        let data = ktx2.levels.concat();
         let texture = render_state.wgpu.device.create_texture_with_data(
             &render_state.wgpu.queue,
             &wgpu::TextureDescriptor {
                 label: None,
-                size: wgpu::Extent3d {
-                    width: image.width(),
-                    height: image.height(),
-                    depth_or_array_layers: 1,
-                },
+                size: ktx2.size,
                 dimension: wgpu::TextureDimension::D2,
-                mip_level_count: 1,
+                mip_level_count: ktx2.levels.len() as u32,
                 sample_count: 1,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::COPY_SRC
-                    | wgpu::TextureUsages::COPY_DST
-                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                format: ktx2.format,
+                usage: TextureFormat::GENERIC.usage,
                 view_formats: &[],
             },
-            wgpu::util::TextureDataOrder::LayerMajor,
-            &image,
+            wgpu::util::TextureDataOrder::MipMajor,
+            &data,
         );
         let view = texture.create_view(&TextureFormat::DEFAULT_VIEW_DESCRIPTOR);
 
         Self { texture, view }
     }
 
+    pub fn from_gltf(
+        render_state: &render::State,
+        gltf_texture: gltf::Texture<'_>,
+        images: &[gltf::image::Data],
+    ) -> Result<Self, assets::Error> {
+        let image_data = images[gltf_texture.source().index()].clone();
+        let (width, height) = (image_data.width, image_data.height);
+        let image = match image_data.format {
+            gltf::image::Format::R8G8B8 => {
+                image::ImageBuffer::from_vec(width, height, image_data.pixels)
+                    .map(image::DynamicImage::ImageRgb8)
+            }
+            gltf::image::Format::R8G8B8A8 => {
+                image::ImageBuffer::from_vec(width, height, image_data.pixels)
+                    .map(image::DynamicImage::ImageRgba8)
+            }
+            gltf::image::Format::R8 => {
+                image::ImageBuffer::from_vec(width, height, image_data.pixels)
+                    .map(image::DynamicImage::ImageLuma8)
+            }
+            f => {
+                return Err(assets::Error::UnsupportedFormat(format!(
+                    "gltf image format {f:?}"
+                )))
+            }
+        }
+        .ok_or_else(|| assets::Error::Decode("gltf image pixels too small".into()))?;
+
+        Ok(Self::from_image(
+            render_state,
+            &image,
+            TextureFormat::GENERIC,
+        ))
+    }
+
     pub fn resize_to_screen(&mut self, render_state: &render::State) {
         let texture = render_state
             .wgpu
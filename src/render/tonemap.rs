@@ -0,0 +1,69 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use bevy_ecs::prelude::*;
+
+/// How the HDR lighting result is mapped onto the screen.
+#[derive(Resource)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub operator: Operator,
+    // Multiplies the scene color before tonemapping, on top of auto exposure if enabled
+    pub exposure: f32,
+    pub auto_exposure: Option<AutoExposure>,
+}
+
+// Must match the constants in tonemap.wgsl
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Operator {
+    Reinhard = 0,
+    #[default]
+    Aces = 1,
+    AgX = 2,
+}
+
+/// Adjusts exposure over time so the average scene luminance ends up at `key`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoExposure {
+    pub key: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    // How quickly exposure adapts to changes in brightness, higher is faster
+    pub speed: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            operator: Operator::default(),
+            exposure: 1.0,
+            auto_exposure: None,
+        }
+    }
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            key: 0.18,
+            min_exposure: 0.1,
+            max_exposure: 10.0,
+            speed: 1.5,
+        }
+    }
+}
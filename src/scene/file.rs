@@ -0,0 +1,1256 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::assets;
+use crate::components::{self, HierarchyCommands};
+use crate::physics;
+use crate::render;
+
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemState;
+use itertools::Itertools;
+use rapier3d::na::UnitQuaternion;
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A scene stored on disk as json.
+///
+/// Materials are referenced by name, and everything else (textures, models) is referenced by path.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct SceneFile {
+    #[serde(default)]
+    pub materials: HashMap<String, MaterialData>,
+    #[serde(default)]
+    pub entities: Vec<EntityData>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityData {
+    // Relative to the parent, if there is one
+    pub transform: TransformData,
+    /// Index of the parent in the scene's entities.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh_renderer: Option<MeshRendererData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub light: Option<LightData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rigid_body: Option<RigidBodyData>,
+    // Attached to the rigid body if there is one, otherwise placed in the world at the entity transform
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub colliders: Vec<ColliderData>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformData {
+    pub position: [f32; 3],
+    // xyzw
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct MeshRendererData {
+    pub model: ModelSource,
+    // Index into the meshes of the model
+    #[serde(default)]
+    pub mesh: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSource {
+    Obj {
+        path: String,
+        // Name of a material in the scene file
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<String>,
+    },
+    Gltf {
+        path: String,
+        // Index of the mesh in the gltf document
+        mesh: usize,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_color: Option<[f32; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_color_texture: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emissive: Option<[f32; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emissive_texture: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metallic: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roughness: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metallic_roughness_texture: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal_texture: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occlusion_texture: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_cutoff: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_mode: Option<AlphaModeData>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlphaModeData {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub light_type: Option<LightTypeData>,
+    // In radians, only used by spot lights
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inner_cone_angle: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outer_cone_angle: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constant: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linear: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quadratic: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diffuse: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub specular: Option<[f32; 3]>,
+
+    // Present if the light casts shadows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadows: Option<ShadowData>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightTypeData {
+    Point,
+    Directional,
+    Spot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowData {
+    pub resolution: u32,
+    pub near: f32,
+    pub far: f32,
+    pub bias: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct RigidBodyData {
+    pub kind: RigidBodyKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub additional_mass: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gravity_scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linear_damping: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub angular_damping: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub linear_velocity: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub angular_velocity: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RigidBodyKind {
+    Dynamic,
+    Fixed,
+    KinematicPositionBased,
+    KinematicVelocityBased,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ColliderData {
+    pub shape: ColliderShape,
+    // Offset from the entity (or rigid body)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<[f32; 3]>,
+    // xyzw
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[f32; 4]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restitution: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub friction: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub density: Option<f32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sensor: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColliderShape {
+    Cuboid { half_extents: [f32; 3] },
+    Ball { radius: f32 },
+    // Along the y axis, use the collider rotation for other orientations
+    Capsule { half_height: f32, radius: f32 },
+    Cylinder { half_height: f32, radius: f32 },
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "failed to read scene file: {e}"),
+            Error::Json(e) => write!(f, "failed to parse scene file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl Default for TransformData {
+    fn default() -> Self {
+        components::Transform::default().into()
+    }
+}
+
+impl From<components::Transform> for TransformData {
+    fn from(value: components::Transform) -> Self {
+        Self {
+            position: value.position.to_array(),
+            rotation: value.rotation.to_array(),
+            scale: value.scale.to_array(),
+        }
+    }
+}
+
+impl From<TransformData> for components::Transform {
+    fn from(value: TransformData) -> Self {
+        Self {
+            position: glam::Vec3::from_array(value.position),
+            rotation: glam::Quat::from_array(value.rotation).normalize(),
+            scale: glam::Vec3::from_array(value.scale),
+        }
+    }
+}
+
+impl SceneFile {
+    pub fn load(path: impl AsRef<camino::Utf8Path>) -> Result<Self, Error> {
+        let data = std::fs::read(path.as_ref())?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<camino::Utf8Path>) -> Result<(), Error> {
+        let data = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path.as_ref(), data)?;
+        Ok(())
+    }
+
+    /// Loads every asset referenced by this scene and spawns its entities into the world.
+    /// Returns the spawned entities, in the same order as they appear in the file.
+    ///
+    /// Worlds without the render and asset plugins (like headless worlds without a gpu)
+    /// only get the transforms and physics of the scene, materials, mesh renderers and lights are skipped.
+    pub fn spawn_into(&self, world: &mut World) -> Vec<Entity> {
+        let mut system_state = SystemState::<(
+            Option<ResMut<'_, assets::Loader>>,
+            Option<Res<'_, render::State>>,
+            Option<ResMut<'_, super::Meshes>>,
+            ResMut<'_, physics::State>,
+            Commands<'_, '_>,
+        )>::from_world(world);
+
+        let (mut assets, render_state, mut meshes, mut physics_state, mut commands) =
+            system_state.get_mut(world);
+
+        let mut render_resources = match (
+            assets.as_deref_mut(),
+            render_state.as_deref(),
+            meshes.as_deref_mut(),
+        ) {
+            (Some(assets), Some(render_state), Some(meshes)) => Some(RenderResources {
+                assets,
+                render_state,
+                meshes,
+            }),
+            _ => {
+                let has_render_data = !self.materials.is_empty()
+                    || self
+                        .entities
+                        .iter()
+                        .any(|e| e.mesh_renderer.is_some() || e.light.is_some());
+                if has_render_data {
+                    log::warn!("no render or asset plugin, skipping the materials, mesh renderers and lights of this scene");
+                }
+                None
+            }
+        };
+
+        if let Some(render) = &mut render_resources {
+            for (name, material) in &self.materials {
+                let material = material.load(render.assets, render.render_state);
+                render.assets.materials.insert_named(name, material);
+            }
+        }
+
+        let entities = self
+            .entities
+            .iter()
+            .zip(self.global_transforms())
+            .map(|(entity, global_transform)| {
+                entity.spawn(
+                    global_transform,
+                    render_resources.as_mut(),
+                    &mut physics_state,
+                    &mut commands,
+                )
+            })
+            .collect_vec();
+
+        for (data, &entity) in self.entities.iter().zip(&entities) {
+            let Some(parent) = data.parent else {
+                continue;
+            };
+            match entities.get(parent) {
+                Some(&parent) => {
+                    commands.entity(entity).set_parent(parent);
+                }
+                None => log::warn!("unable to attach {entity:?} to nonexistent entity {parent}"),
+            }
+        }
+
+        system_state.apply(world);
+
+        entities
+    }
+
+    /// Composes the transforms of every entity with the transforms of its parents.
+    /// Entities with a missing parent or that are part of a cycle are treated as roots.
+    fn global_transforms(&self) -> Vec<components::GlobalTransform> {
+        let mut globals: Vec<Option<components::GlobalTransform>> = vec![None; self.entities.len()];
+
+        for index in 0..self.entities.len() {
+            // Walk up to the first ancestor that is already resolved (or the root)
+            let mut chain = vec![index];
+            let mut parent_global = components::GlobalTransform::default();
+            while let Some(parent) = self.entities[*chain.last().unwrap()].parent {
+                if let Some(global) = globals.get(parent).copied().flatten() {
+                    parent_global = global;
+                    break;
+                }
+                if parent >= self.entities.len() || chain.contains(&parent) {
+                    break;
+                }
+                chain.push(parent);
+            }
+
+            for &index in chain.iter().rev() {
+                let transform = components::Transform::from(self.entities[index].transform);
+                parent_global = parent_global.mul_transform(transform);
+                globals[index] = Some(parent_global);
+            }
+        }
+
+        globals.into_iter().flatten().collect()
+    }
+}
+
+impl MaterialData {
+    pub fn load(
+        &self,
+        assets: &mut assets::Loader,
+        render_state: &render::State,
+    ) -> render::Material {
+        // Textures that fail to load keep their id, so they are drawn with the null texture
+        let mut load_texture = |path: &Option<String>, format: render::TextureFormat| {
+            path.as_ref().map(|path| {
+                assets
+                    .textures
+                    .load_from_path_with_format(render_state, path, format)
+                    .unwrap_or_else(|error| {
+                        log::warn!("failed to load texture {path}: {error}");
+                        assets::TextureId::from_path(path)
+                    })
+            })
+        };
+        let default = render::Material::default();
+
+        render::Material {
+            base_color: self.base_color.map_or(default.base_color, Into::into),
+            base_color_texture: load_texture(
+                &self.base_color_texture,
+                render::TextureFormat::GENERIC,
+            ),
+
+            emissive: self.emissive.map_or(default.emissive, Into::into),
+            emissive_texture: load_texture(&self.emissive_texture, render::TextureFormat::GENERIC),
+
+            metallic: self.metallic.unwrap_or(default.metallic),
+            roughness: self.roughness.unwrap_or(default.roughness),
+            metallic_roughness_texture: load_texture(
+                &self.metallic_roughness_texture,
+                render::TextureFormat::NORMAL,
+            ),
+
+            normal_texture: load_texture(&self.normal_texture, render::TextureFormat::NORMAL),
+            occlusion_texture: load_texture(&self.occlusion_texture, render::TextureFormat::NORMAL),
+
+            alpha_cutoff: self.alpha_cutoff,
+            alpha_mode: self.alpha_mode.map_or(default.alpha_mode, Into::into),
+        }
+    }
+}
+
+// Everything needed to spawn mesh renderers and lights
+struct RenderResources<'a> {
+    assets: &'a mut assets::Loader,
+    render_state: &'a render::State,
+    meshes: &'a mut super::Meshes,
+}
+
+impl EntityData {
+    fn spawn(
+        &self,
+        global_transform: components::GlobalTransform,
+        render: Option<&mut RenderResources<'_>>,
+        physics_state: &mut physics::State,
+        commands: &mut Commands<'_, '_>,
+    ) -> Entity {
+        let transform = components::Transform::from(self.transform);
+        let mut entity_builder = commands.spawn(transform);
+        let entity = entity_builder.id();
+
+        if let Some(RenderResources {
+            assets,
+            render_state,
+            meshes,
+        }) = render
+        {
+            self.spawn_render_components(&mut entity_builder, assets, render_state, meshes);
+        }
+
+        // Physics happens in world space, so parented entities need their parent's transform applied
+        let (scale, rotation, position) = global_transform.to_scale_rotation_translation();
+        let isometry = transform_to_isometry(components::Transform {
+            position,
+            rotation,
+            scale,
+        });
+        if let Some(rigid_body) = &self.rigid_body {
+            let rigid_body = rigid_body.build(isometry);
+            let rigid_body = physics::RigidBody::new(physics_state, entity, rigid_body);
+
+            for collider in &self.colliders {
+                let collider = collider.builder().position(collider.offset()).build();
+                physics_state.collider_set.insert_with_parent(
+                    collider,
+                    rigid_body.handle,
+                    &mut physics_state.rigid_body_set,
+                );
+            }
+
+            entity_builder.insert(rigid_body);
+        } else {
+            for collider in &self.colliders {
+                let position = isometry * collider.offset();
+                // Used to find the entity this collider belongs to when taking a snapshot
+                let collider = collider
+                    .builder()
+                    .position(position)
+                    .user_data(entity.to_bits() as u128)
+                    .build();
+                physics_state.collider_set.insert(collider);
+            }
+        }
+
+        entity
+    }
+
+    fn spawn_render_components(
+        &self,
+        entity_builder: &mut bevy_ecs::system::EntityCommands<'_>,
+        assets: &mut assets::Loader,
+        render_state: &render::State,
+        meshes: &mut super::Meshes,
+    ) {
+        let entity = entity_builder.id();
+
+        if let Some(mesh_renderer) = &self.mesh_renderer {
+            match mesh_renderer.model.load(assets, render_state) {
+                Ok(model_id)
+                    if assets
+                        .models
+                        .get(model_id)
+                        .is_some_and(|model| mesh_renderer.mesh < model.meshes.len()) =>
+                {
+                    entity_builder.insert(components::MeshRenderer::from_model(
+                        meshes,
+                        &assets.models,
+                        model_id,
+                        mesh_renderer.mesh,
+                    ));
+                }
+                Ok(model_id) => log::warn!(
+                    "unable to spawn mesh renderer of {entity:?}: {model_id:?} has no mesh {}",
+                    mesh_renderer.mesh
+                ),
+                Err(error) => {
+                    log::warn!("unable to spawn mesh renderer of {entity:?}: {error}");
+                }
+            }
+        }
+
+        if let Some(light) = &self.light {
+            let mut component = components::Light::new(assets, meshes);
+            light.apply(&mut component);
+            entity_builder.insert(component);
+        }
+    }
+}
+
+impl ModelSource {
+    pub fn load(
+        &self,
+        assets: &mut assets::Loader,
+        render_state: &render::State,
+    ) -> Result<assets::ModelId, assets::Error> {
+        match self {
+            ModelSource::Obj { path, material } => {
+                let material_id = material
+                    .as_ref()
+                    .map_or(assets::MaterialId::Path(0), assets::MaterialId::from_path);
+                assets.models.load_tobj(path, material_id)
+            }
+            ModelSource::Gltf { path, mesh } => {
+                let gltf_id = assets.load_gltf(render_state, path)?;
+                Ok(assets::ModelId::Gltf(gltf_id, *mesh))
+            }
+        }
+    }
+}
+
+impl LightData {
+    pub fn apply(&self, light: &mut components::Light) {
+        if let Some(light_type) = self.light_type {
+            light.light_type = light_type.into();
+        }
+        if let Some(inner_cone_angle) = self.inner_cone_angle {
+            light.inner_cone_angle = inner_cone_angle;
+        }
+        if let Some(outer_cone_angle) = self.outer_cone_angle {
+            light.outer_cone_angle = outer_cone_angle;
+        }
+        if let Some(constant) = self.constant {
+            light.constant = constant;
+        }
+        if let Some(linear) = self.linear {
+            light.linear = linear;
+        }
+        if let Some(quadratic) = self.quadratic {
+            light.quadratic = quadratic;
+        }
+        if let Some(diffuse) = self.diffuse {
+            light.diffuse = diffuse.into();
+        }
+        if let Some(specular) = self.specular {
+            light.specular = specular.into();
+        }
+        light.shadows = self
+            .shadows
+            .map(|shadows| components::light::ShadowSettings {
+                resolution: shadows.resolution,
+                near: shadows.near,
+                far: shadows.far,
+                bias: shadows.bias,
+            });
+        if light.shadows.is_some() && light.light_type == components::light::LightType::Directional
+        {
+            log::warn!(
+                "ignoring shadows of a directional light: only point and spot lights cast shadows"
+            );
+            light.shadows = None;
+        }
+    }
+}
+
+impl From<LightTypeData> for components::light::LightType {
+    fn from(value: LightTypeData) -> Self {
+        match value {
+            LightTypeData::Point => Self::Point,
+            LightTypeData::Directional => Self::Directional,
+            LightTypeData::Spot => Self::Spot,
+        }
+    }
+}
+
+impl From<components::light::LightType> for LightTypeData {
+    fn from(value: components::light::LightType) -> Self {
+        match value {
+            components::light::LightType::Point => Self::Point,
+            components::light::LightType::Directional => Self::Directional,
+            components::light::LightType::Spot => Self::Spot,
+        }
+    }
+}
+
+impl From<AlphaModeData> for render::AlphaMode {
+    fn from(value: AlphaModeData) -> Self {
+        match value {
+            AlphaModeData::Opaque => Self::Opaque,
+            AlphaModeData::Mask => Self::Mask,
+            AlphaModeData::Blend => Self::Blend,
+        }
+    }
+}
+
+impl From<render::AlphaMode> for AlphaModeData {
+    fn from(value: render::AlphaMode) -> Self {
+        match value {
+            render::AlphaMode::Opaque => Self::Opaque,
+            render::AlphaMode::Mask => Self::Mask,
+            render::AlphaMode::Blend => Self::Blend,
+        }
+    }
+}
+
+impl Default for ShadowData {
+    fn default() -> Self {
+        let settings = components::light::ShadowSettings::default();
+        Self {
+            resolution: settings.resolution,
+            near: settings.near,
+            far: settings.far,
+            bias: settings.bias,
+        }
+    }
+}
+
+impl RigidBodyData {
+    pub fn build(&self, position: Isometry<Real>) -> rapier3d::dynamics::RigidBody {
+        let mut builder = match self.kind {
+            RigidBodyKind::Dynamic => RigidBodyBuilder::dynamic(),
+            RigidBodyKind::Fixed => RigidBodyBuilder::fixed(),
+            RigidBodyKind::KinematicPositionBased => RigidBodyBuilder::kinematic_position_based(),
+            RigidBodyKind::KinematicVelocityBased => RigidBodyBuilder::kinematic_velocity_based(),
+        }
+        .position(position);
+
+        if let Some(additional_mass) = self.additional_mass {
+            builder = builder.additional_mass(additional_mass);
+        }
+        if let Some(gravity_scale) = self.gravity_scale {
+            builder = builder.gravity_scale(gravity_scale);
+        }
+        if let Some(linear_damping) = self.linear_damping {
+            builder = builder.linear_damping(linear_damping);
+        }
+        if let Some(angular_damping) = self.angular_damping {
+            builder = builder.angular_damping(angular_damping);
+        }
+        if let Some([x, y, z]) = self.linear_velocity {
+            builder = builder.linvel(vector![x, y, z]);
+        }
+        if let Some([x, y, z]) = self.angular_velocity {
+            builder = builder.angvel(vector![x, y, z]);
+        }
+
+        builder.build()
+    }
+}
+
+impl ColliderData {
+    /// The position of this collider relative to its entity (or rigid body).
+    pub fn offset(&self) -> Isometry<Real> {
+        let [x, y, z] = self.translation.unwrap_or_default();
+        let rotation = self
+            .rotation
+            .map_or(UnitQuaternion::identity(), |[x, y, z, w]| {
+                UnitQuaternion::from_quaternion(rapier3d::na::Quaternion::new(w, x, y, z))
+            });
+        Isometry::from_parts(vector![x, y, z].into(), rotation)
+    }
+
+    /// Creates a collider builder with the shape and material properties of this collider.
+    /// Does not set the position of the collider.
+    pub fn builder(&self) -> ColliderBuilder {
+        let mut builder = match self.shape {
+            ColliderShape::Cuboid {
+                half_extents: [x, y, z],
+            } => ColliderBuilder::cuboid(x, y, z),
+            ColliderShape::Ball { radius } => ColliderBuilder::ball(radius),
+            ColliderShape::Capsule {
+                half_height,
+                radius,
+            } => ColliderBuilder::capsule_y(half_height, radius),
+            ColliderShape::Cylinder {
+                half_height,
+                radius,
+            } => ColliderBuilder::cylinder(half_height, radius),
+        }
+        .sensor(self.sensor);
+
+        if let Some(restitution) = self.restitution {
+            builder = builder.restitution(restitution);
+        }
+        if let Some(friction) = self.friction {
+            builder = builder.friction(friction);
+        }
+        if let Some(density) = self.density {
+            builder = builder.density(density);
+        }
+
+        builder
+    }
+}
+
+pub fn transform_to_isometry(transform: components::Transform) -> Isometry<Real> {
+    let translation = vector![
+        transform.position.x,
+        transform.position.y,
+        transform.position.z
+    ];
+    let rotation = rapier3d::na::UnitQuaternion::from_quaternion(rapier3d::na::Quaternion::new(
+        transform.rotation.w,
+        transform.rotation.x,
+        transform.rotation.y,
+        transform.rotation.z,
+    ));
+    Isometry::from_parts(translation.into(), rotation)
+}
+
+impl SceneFile {
+    /// Captures the current state of the world as a scene file.
+    ///
+    /// Only assets that were loaded from a path (and named materials) can be referenced,
+    /// so mesh renderers created from anything else are left out.
+    pub fn snapshot(world: &mut World) -> Self {
+        let mut system_state = SystemState::<(
+            Option<Res<'_, assets::Loader>>,
+            Res<'_, physics::State>,
+            Query<
+                '_,
+                '_,
+                (
+                    Entity,
+                    &components::Transform,
+                    Option<&components::MeshRenderer>,
+                    Option<&components::Light>,
+                    Option<&physics::RigidBody>,
+                    Option<&components::Parent>,
+                ),
+            >,
+        )>::from_world(world);
+        let (assets, physics_state, query) = system_state.get(world);
+
+        let assets = assets.as_deref();
+
+        // Worlds without an asset loader can't have any materials or mesh renderers
+        let materials = assets
+            .into_iter()
+            .flat_map(|assets| {
+                assets.materials.iter().filter_map(|(id, material)| {
+                    let name = assets.materials.name(id)?;
+                    let material = MaterialData::snapshot(material, &assets.textures);
+                    Some((name.to_string(), material))
+                })
+            })
+            .collect();
+
+        // Colliders without a parent are tied to their entity through user data.
+        // Games can set user data themselves, so anything that isn't an entity is ignored
+        let mut standalone_colliders: HashMap<Entity, Vec<&Collider>> = HashMap::new();
+        for (_, collider) in physics_state.collider_set.iter() {
+            if collider.parent().is_none() && collider.user_data != 0 {
+                let Ok(bits) = u64::try_from(collider.user_data) else {
+                    continue;
+                };
+                let Ok(entity) = Entity::try_from_bits(bits) else {
+                    continue;
+                };
+                standalone_colliders
+                    .entry(entity)
+                    .or_default()
+                    .push(collider);
+            }
+        }
+
+        // Parents are saved as indices into the entity list
+        let query_items = query.iter().collect_vec();
+        let entity_indices: HashMap<Entity, usize> = query_items
+            .iter()
+            .enumerate()
+            .map(|(index, (entity, ..))| (*entity, index))
+            .collect();
+
+        // GlobalTransform is only updated in PostUpdate, so compose the transforms here instead
+        let local_transforms: HashMap<Entity, (components::Transform, Option<Entity>)> =
+            query_items
+                .iter()
+                .map(|(entity, transform, .., parent)| {
+                    (*entity, (**transform, parent.map(|p| p.get())))
+                })
+                .collect();
+        let world_isometry = |mut entity: Entity| {
+            let mut global = components::GlobalTransform::default();
+            let mut chain = Vec::new();
+            while let Some(&(transform, parent)) = local_transforms.get(&entity) {
+                chain.push(transform);
+                match parent {
+                    // Hierarchies can't have cycles, but don't hang if one somehow does
+                    Some(parent) if chain.len() <= local_transforms.len() => entity = parent,
+                    _ => break,
+                }
+            }
+            for transform in chain.into_iter().rev() {
+                global = global.mul_transform(transform);
+            }
+            let (scale, rotation, position) = global.to_scale_rotation_translation();
+            transform_to_isometry(components::Transform {
+                position,
+                rotation,
+                scale,
+            })
+        };
+
+        let entities = query_items
+            .into_iter()
+            .map(|(entity, transform, mesh_renderer, light, rigid_body, parent)| {
+                let parent = parent.and_then(|parent| {
+                    let index = entity_indices.get(&parent.get()).copied();
+                    if index.is_none() {
+                        log::warn!("unable to save the parent of {entity:?}: it has no transform");
+                    }
+                    index
+                });
+
+                let mesh_renderer = mesh_renderer.zip(assets).and_then(|(mesh_renderer, assets)| {
+                    let snapshot = MeshRendererData::snapshot(mesh_renderer, assets);
+                    if snapshot.is_none() {
+                        log::warn!("unable to save mesh renderer of {entity:?}: it was not loaded from a path");
+                    }
+                    snapshot
+                });
+                let light = light.map(LightData::snapshot);
+
+                let (rigid_body, colliders) = match rigid_body
+                    .and_then(|r| physics_state.rigid_body_set.get(r.handle))
+                {
+                    Some(rigid_body) => {
+                        let colliders = rigid_body
+                            .colliders()
+                            .iter()
+                            .filter_map(|&handle| physics_state.collider_set.get(handle))
+                            .filter_map(|collider| {
+                                let offset = collider
+                                    .position_wrt_parent()
+                                    .copied()
+                                    .unwrap_or_else(Isometry::identity);
+                                ColliderData::snapshot(collider, offset)
+                            })
+                            .collect();
+                        (Some(RigidBodyData::snapshot(rigid_body)), colliders)
+                    }
+                    None => {
+                        // Standalone colliders are placed in world space
+                        let inverse = world_isometry(entity).inverse();
+                        let colliders = standalone_colliders
+                            .get(&entity)
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|collider| {
+                                ColliderData::snapshot(collider, inverse * collider.position())
+                            })
+                            .collect();
+                        (None, colliders)
+                    }
+                };
+
+                EntityData {
+                    transform: (*transform).into(),
+                    parent,
+                    mesh_renderer,
+                    light,
+                    rigid_body,
+                    colliders,
+                }
+            })
+            .collect();
+
+        Self {
+            materials,
+            entities,
+        }
+    }
+}
+
+impl MaterialData {
+    pub fn snapshot(material: &render::Material, textures: &assets::Textures) -> Self {
+        let texture_path = |id: Option<assets::TextureId>| {
+            id.and_then(|id| textures.path(id)).map(ToString::to_string)
+        };
+
+        Self {
+            base_color: Some(glam::Vec4::from(material.base_color).to_array()),
+            base_color_texture: texture_path(material.base_color_texture),
+
+            emissive: Some(glam::Vec4::from(material.emissive).to_array()),
+            emissive_texture: texture_path(material.emissive_texture),
+
+            metallic: Some(material.metallic),
+            roughness: Some(material.roughness),
+            metallic_roughness_texture: texture_path(material.metallic_roughness_texture),
+
+            normal_texture: texture_path(material.normal_texture),
+            occlusion_texture: texture_path(material.occlusion_texture),
+
+            alpha_cutoff: material.alpha_cutoff,
+            alpha_mode: Some(material.alpha_mode.into()),
+        }
+    }
+}
+
+impl MeshRendererData {
+    pub fn snapshot(
+        mesh_renderer: &components::MeshRenderer,
+        assets: &assets::Loader,
+    ) -> Option<Self> {
+        let (model_id, mesh) = mesh_renderer.source?;
+
+        let model = match model_id {
+            assets::ModelId::Path(_) => ModelSource::Obj {
+                path: assets.models.path(model_id)?.to_string(),
+                material: assets
+                    .materials
+                    .name(mesh_renderer.mesh_index.material_id)
+                    .map(ToString::to_string),
+            },
+            assets::ModelId::Gltf(gltf_id, mesh) => ModelSource::Gltf {
+                path: assets.gltf.path(gltf_id)?.to_string(),
+                mesh,
+            },
+        };
+
+        Some(Self { model, mesh })
+    }
+}
+
+impl LightData {
+    pub fn snapshot(light: &components::Light) -> Self {
+        Self {
+            light_type: Some(light.light_type.into()),
+            inner_cone_angle: Some(light.inner_cone_angle),
+            outer_cone_angle: Some(light.outer_cone_angle),
+
+            constant: Some(light.constant),
+            linear: Some(light.linear),
+            quadratic: Some(light.quadratic),
+
+            diffuse: Some(glam::Vec3::from(light.diffuse).to_array()),
+            specular: Some(glam::Vec3::from(light.specular).to_array()),
+
+            shadows: light.shadows.map(|shadows| ShadowData {
+                resolution: shadows.resolution,
+                near: shadows.near,
+                far: shadows.far,
+                bias: shadows.bias,
+            }),
+        }
+    }
+}
+
+impl RigidBodyData {
+    pub fn snapshot(rigid_body: &rapier3d::dynamics::RigidBody) -> Self {
+        let kind = match rigid_body.body_type() {
+            RigidBodyType::Dynamic => RigidBodyKind::Dynamic,
+            RigidBodyType::Fixed => RigidBodyKind::Fixed,
+            RigidBodyType::KinematicPositionBased => RigidBodyKind::KinematicPositionBased,
+            RigidBodyType::KinematicVelocityBased => RigidBodyKind::KinematicVelocityBased,
+        };
+
+        let additional_mass = match rigid_body
+            .mass_properties()
+            .additional_local_mprops
+            .as_deref()
+        {
+            Some(RigidBodyAdditionalMassProps::Mass(mass)) => Some(*mass),
+            _ => None,
+        };
+
+        let linvel = rigid_body.linvel();
+        let angvel = rigid_body.angvel();
+
+        Self {
+            kind,
+            additional_mass,
+            gravity_scale: Some(rigid_body.gravity_scale()),
+            linear_damping: Some(rigid_body.linear_damping()),
+            angular_damping: Some(rigid_body.angular_damping()),
+            linear_velocity: Some([linvel.x, linvel.y, linvel.z]),
+            angular_velocity: Some([angvel.x, angvel.y, angvel.z]),
+        }
+    }
+}
+
+impl ColliderData {
+    /// Returns `None` for shapes that scene files can't describe.
+    pub fn snapshot(collider: &Collider, mut offset: Isometry<Real>) -> Option<Self> {
+        let shape = collider.shape();
+        let shape = if let Some(cuboid) = shape.as_cuboid() {
+            ColliderShape::Cuboid {
+                half_extents: cuboid.half_extents.into(),
+            }
+        } else if let Some(ball) = shape.as_ball() {
+            ColliderShape::Ball {
+                radius: ball.radius,
+            }
+        } else if let Some(capsule) = shape.as_capsule() {
+            // Scene files only describe capsules along y, so move the segment into the collider offset
+            let axis = capsule.segment.b - capsule.segment.a;
+            let center = capsule
+                .segment
+                .a
+                .coords
+                .lerp(&capsule.segment.b.coords, 0.5);
+            let rotation = UnitQuaternion::rotation_between(&Vector::y(), &axis)
+                // Only fails for capsules pointing straight down
+                .unwrap_or_else(|| {
+                    UnitQuaternion::from_axis_angle(&Vector::x_axis(), std::f32::consts::PI)
+                });
+            offset *= Isometry::from_parts(center.into(), rotation);
+            ColliderShape::Capsule {
+                half_height: capsule.half_height(),
+                radius: capsule.radius,
+            }
+        } else if let Some(cylinder) = shape.as_cylinder() {
+            ColliderShape::Cylinder {
+                half_height: cylinder.half_height,
+                radius: cylinder.radius,
+            }
+        } else {
+            log::warn!(
+                "unable to save collider: unsupported shape {:?}",
+                shape.shape_type()
+            );
+            return None;
+        };
+
+        let translation = offset.translation.vector;
+        let rotation = offset.rotation;
+        Some(Self {
+            shape,
+            translation: (translation != Vector::zeros()).then(|| translation.into()),
+            rotation: (rotation != UnitQuaternion::identity())
+                .then(|| [rotation.i, rotation.j, rotation.k, rotation.w]),
+            restitution: Some(collider.restitution()),
+            friction: Some(collider.friction()),
+            density: Some(collider.density()),
+            sensor: collider.is_sensor(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close<const N: usize>(actual: [f32; N], expected: [f32; N]) {
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-4),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    fn collider(shape: ColliderShape, translation: [f32; 3], rotation: [f32; 4]) -> ColliderData {
+        ColliderData {
+            shape,
+            translation: Some(translation),
+            rotation: Some(rotation),
+            restitution: None,
+            friction: None,
+            density: None,
+            sensor: false,
+        }
+    }
+
+    #[test]
+    fn parented_physics_round_trip() {
+        let mut builder = super::super::WorldBuilder::new();
+        super::super::add_simulation_plugins(&mut builder);
+        let mut world = builder.build();
+
+        let quarter_turn_y = glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_2).to_array();
+        let quarter_turn_x = glam::Quat::from_rotation_x(std::f32::consts::FRAC_PI_2).to_array();
+        let file = SceneFile {
+            materials: HashMap::new(),
+            entities: vec![
+                EntityData {
+                    transform: TransformData {
+                        position: [0.0, 5.0, 0.0],
+                        rotation: quarter_turn_y,
+                        scale: [1.0; 3],
+                    },
+                    ..Default::default()
+                },
+                EntityData {
+                    transform: TransformData {
+                        position: [1.0, 0.0, 0.0],
+                        ..Default::default()
+                    },
+                    parent: Some(0),
+                    rigid_body: Some(RigidBodyData {
+                        kind: RigidBodyKind::Fixed,
+                        additional_mass: None,
+                        gravity_scale: None,
+                        linear_damping: None,
+                        angular_damping: None,
+                        linear_velocity: None,
+                        angular_velocity: None,
+                    }),
+                    colliders: vec![collider(
+                        ColliderShape::Capsule {
+                            half_height: 1.0,
+                            radius: 0.5,
+                        },
+                        [0.0, 1.0, 0.0],
+                        quarter_turn_x,
+                    )],
+                    ..Default::default()
+                },
+                EntityData {
+                    transform: TransformData {
+                        position: [0.0, 0.0, 2.0],
+                        ..Default::default()
+                    },
+                    parent: Some(0),
+                    colliders: vec![collider(
+                        ColliderShape::Ball { radius: 1.0 },
+                        [1.0, 0.0, 0.0],
+                        quarter_turn_x,
+                    )],
+                    ..Default::default()
+                },
+            ],
+        };
+        let entities = file.spawn_into(&mut world);
+
+        // The parent turns the child's offset from +x to -z
+        let physics_state = world.resource::<physics::State>();
+        let rigid_body = world.get::<physics::RigidBody>(entities[1]).unwrap();
+        let translation = physics_state.rigid_body_set[rigid_body.handle].translation();
+        assert_close((*translation).into(), [0.0, 5.0, -1.0]);
+
+        let snapshot = SceneFile::snapshot(&mut world);
+        let find = |has_rigid_body: bool, has_colliders: bool| {
+            snapshot
+                .entities
+                .iter()
+                .find(|e| {
+                    e.rigid_body.is_some() == has_rigid_body
+                        && e.colliders.is_empty() != has_colliders
+                })
+                .unwrap()
+        };
+
+        let parent = find(false, false);
+        assert_close(parent.transform.position, [0.0, 5.0, 0.0]);
+        assert_close(parent.transform.rotation, quarter_turn_y);
+
+        for (entity, original) in [
+            (find(true, true), &file.entities[1]),
+            (find(false, true), &file.entities[2]),
+        ] {
+            let parent_index = entity.parent.expect("child lost its parent");
+            assert_eq!(&snapshot.entities[parent_index], parent);
+            assert_close(entity.transform.position, original.transform.position);
+
+            let [collider] = &entity.colliders[..] else {
+                panic!("expected one collider, got {:?}", entity.colliders);
+            };
+            assert_eq!(collider.shape, original.colliders[0].shape);
+            assert_close(
+                collider.translation.unwrap_or_default(),
+                original.colliders[0].translation.unwrap(),
+            );
+            assert_close(
+                collider.rotation.unwrap(),
+                original.colliders[0].rotation.unwrap(),
+            );
+        }
+    }
+}
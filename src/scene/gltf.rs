@@ -0,0 +1,123 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::assets;
+use crate::components;
+use crate::render;
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemState;
+
+/// Spawns a scene from a gltf file as a hierarchy of entities, loading the file if it hasn't been loaded yet.
+///
+/// Every node becomes an entity with its local transform, parented to a root entity.
+/// Nodes with a single primitive get a [`components::MeshRenderer`] directly, nodes with several get one child entity per primitive.
+/// If `scene` is `None`, the default scene (or the first scene if there is no default) is spawned.
+///
+/// Returns the root entity, or an error if the file couldn't be loaded or doesn't have the scene.
+pub fn spawn_gltf_scene(
+    world: &mut World,
+    path: impl AsRef<camino::Utf8Path>,
+    scene: Option<usize>,
+) -> Result<Entity, assets::Error> {
+    let path = path.as_ref();
+
+    let mut system_state = SystemState::<(
+        ResMut<'_, assets::Loader>,
+        Res<'_, render::State>,
+        ResMut<'_, super::Meshes>,
+        Commands<'_, '_>,
+    )>::from_world(world);
+    let (mut assets, render_state, mut meshes, mut commands) = system_state.get_mut(world);
+
+    let gltf_id = assets.load_gltf(&render_state, path)?;
+
+    let assets = &*assets;
+    let document = &assets.gltf.get_expect(gltf_id).document;
+    let gltf_scene = match scene {
+        Some(index) => document.scenes().nth(index),
+        None => document
+            .default_scene()
+            .or_else(|| document.scenes().next()),
+    }
+    .ok_or(assets::Error::MissingScene(scene))?;
+
+    let root = commands.spawn(components::Transform::default()).id();
+    let children = gltf_scene
+        .nodes()
+        .map(|node| spawn_node(node, root, gltf_id, assets, &mut meshes, &mut commands))
+        .collect();
+    commands.entity(root).insert(components::Children(children));
+
+    system_state.apply(world);
+
+    Ok(root)
+}
+
+fn spawn_node(
+    node: gltf::Node<'_>,
+    parent: Entity,
+    gltf_id: assets::GltfId,
+    assets: &assets::Loader,
+    meshes: &mut super::Meshes,
+    commands: &mut Commands<'_, '_>,
+) -> Entity {
+    let transform = components::Transform::from_gltf(node.transform());
+    let entity = commands.spawn((transform, components::Parent(parent))).id();
+
+    let mut children = Vec::new();
+
+    if let Some(mesh) = node.mesh() {
+        let model_id = assets::ModelId::Gltf(gltf_id, mesh.index());
+        let primitive_count = assets.models.get_expect(model_id).meshes.len();
+
+        if primitive_count == 1 {
+            let mesh_renderer =
+                components::MeshRenderer::from_model(meshes, &assets.models, model_id, 0);
+            commands.entity(entity).insert(mesh_renderer);
+        } else {
+            children.extend((0..primitive_count).map(|primitive| {
+                let mesh_renderer = components::MeshRenderer::from_model(
+                    meshes,
+                    &assets.models,
+                    model_id,
+                    primitive,
+                );
+                commands
+                    .spawn((
+                        components::Transform::default(),
+                        components::Parent(entity),
+                        mesh_renderer,
+                    ))
+                    .id()
+            }));
+        }
+    }
+
+    children.extend(
+        node.children()
+            .map(|child| spawn_node(child, entity, gltf_id, assets, meshes, commands)),
+    );
+
+    if !children.is_empty() {
+        commands
+            .entity(entity)
+            .insert(components::Children(children));
+    }
+
+    entity
+}
@@ -16,9 +16,10 @@
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::assets;
+use crate::components;
 use crate::render;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use bevy_ecs::prelude::*;
@@ -30,6 +31,16 @@ pub struct Meshes {
     index_buffer: Buffer<u32>,
 
     seen_meshes: HashMap<MeshRef, MeshIndex>,
+    uploaded_meshes: BTreeMap<MeshIndex, UploadedMesh>,
+
+    // Which mesh every MeshRenderer entity was last seen using
+    users: HashMap<Entity, MeshIndex>,
+}
+
+#[derive(Debug)]
+struct UploadedMesh {
+    mesh: Arc<render::Mesh>,
+    ref_count: usize,
 }
 
 #[derive(Debug)]
@@ -44,10 +55,18 @@ struct VertexBuffers {
 #[derive(Debug)]
 struct Buffer<T> {
     internal_buffer: wgpu::Buffer,
+    allocator: Allocator,
 
-    written_len: wgpu::BufferAddress,
+    unwritten: Vec<(wgpu::BufferAddress, Vec<T>)>,
+}
 
-    unwritten: Vec<T>,
+// Hands out byte ranges of a buffer, reusing freed ranges before growing it
+#[derive(Debug, Default)]
+struct Allocator {
+    // The end of the furthest allocation in bytes
+    len: wgpu::BufferAddress,
+    // Freed byte ranges below `len`, kept sorted and merged
+    free_ranges: Vec<std::ops::Range<wgpu::BufferAddress>>,
 }
 
 struct MeshRef(Arc<render::Mesh>);
@@ -110,29 +129,43 @@ where
 
         Self {
             internal_buffer,
-            written_len: 0,
-            unwritten: Vec::with_capacity(2048),
+            allocator: Allocator::default(),
+            unwritten: Vec::with_capacity(16),
         }
     }
 
     pub fn queue_write(&mut self, values: &[T]) -> wgpu::BufferAddress {
-        // Calculate offset based on current written len + the amount of unwritten values * the size of a value
         // Offset is in bytes
-        let offset = self.written_len + (self.unwritten.len() as wgpu::BufferAddress * Self::SIZE);
-        self.unwritten.extend(values);
+        let offset = self
+            .allocator
+            .allocate(values.len() as wgpu::BufferAddress * Self::SIZE);
+        if !values.is_empty() {
+            self.unwritten.push((offset, values.to_vec()));
+        }
         offset
     }
 
+    pub fn free(&mut self, offset: wgpu::BufferAddress, count: usize) {
+        let size = count as wgpu::BufferAddress * Self::SIZE;
+        if size == 0 {
+            return;
+        }
+
+        // Don't write data that was freed before it even made it to the gpu
+        self.unwritten
+            .retain(|(unwritten_offset, _)| *unwritten_offset != offset);
+
+        self.allocator.free(offset, size);
+    }
+
     // returns true if the buffer has been resized (this is used to recreate bind groups)
     pub fn write_unwritten(
         &mut self,
         render_state: &render::State,
         encoder: &mut wgpu::CommandEncoder,
     ) -> bool {
-        // Calculate the final buffer size in bytes
-        let final_buffer_size =
-            self.written_len + (self.unwritten.len() as wgpu::BufferAddress * Self::SIZE);
-        let needs_resize = final_buffer_size > self.internal_buffer.size();
+        let len = self.allocator.len;
+        let needs_resize = len > self.internal_buffer.size();
         // If the buffer is too small, create a new one and copy the original data over
         if needs_resize {
             let new_buffer = render_state
@@ -140,10 +173,7 @@ where
                 .device
                 .create_buffer(&wgpu::BufferDescriptor {
                     label: Some("wormhole scene meshes internal buffer"),
-                    size: wgpu::util::align_to(
-                        final_buffer_size / 2 + final_buffer_size,
-                        wgpu::COPY_BUFFER_ALIGNMENT,
-                    ),
+                    size: wgpu::util::align_to(len / 2 + len, wgpu::COPY_BUFFER_ALIGNMENT),
                     usage: self.internal_buffer.usage(),
                     mapped_at_creation: false,
                 });
@@ -152,24 +182,74 @@ where
                 0,
                 &new_buffer,
                 0,
-                self.written_len,
+                self.internal_buffer.size(),
             );
             self.internal_buffer = new_buffer;
         }
 
-        render_state.wgpu.queue.write_buffer(
-            &self.internal_buffer,
-            self.written_len,
-            bytemuck::cast_slice(&self.unwritten),
-        );
-
-        self.unwritten.clear();
-        self.written_len = final_buffer_size;
+        for (offset, values) in self.unwritten.drain(..) {
+            render_state.wgpu.queue.write_buffer(
+                &self.internal_buffer,
+                offset,
+                bytemuck::cast_slice(&values),
+            );
+        }
 
         needs_resize
     }
 }
 
+impl Allocator {
+    // Finds room for `size` bytes, reusing freed ranges before growing the buffer
+    fn allocate(&mut self, size: wgpu::BufferAddress) -> wgpu::BufferAddress {
+        // Empty allocations would share their offset with the next real one
+        if size == 0 {
+            return 0;
+        }
+
+        let free_range = self
+            .free_ranges
+            .iter()
+            .position(|range| range.end - range.start >= size);
+
+        if let Some(index) = free_range {
+            let range = &mut self.free_ranges[index];
+            let offset = range.start;
+            range.start += size;
+            if range.is_empty() {
+                self.free_ranges.remove(index);
+            }
+            return offset;
+        }
+
+        let offset = self.len;
+        self.len += size;
+        offset
+    }
+
+    fn free(&mut self, offset: wgpu::BufferAddress, size: wgpu::BufferAddress) {
+        let mut index = self
+            .free_ranges
+            .partition_point(|range| range.start < offset);
+        self.free_ranges.insert(index, offset..offset + size);
+
+        // Merge with the neighbouring ranges so large allocations can reuse them
+        if index + 1 < self.free_ranges.len()
+            && self.free_ranges[index].end == self.free_ranges[index + 1].start
+        {
+            self.free_ranges[index].end = self.free_ranges.remove(index + 1).end;
+        }
+        if index > 0 && self.free_ranges[index - 1].end == self.free_ranges[index].start {
+            self.free_ranges[index - 1].end = self.free_ranges.remove(index).end;
+            index -= 1;
+        }
+
+        if self.free_ranges[index].end == self.len {
+            self.len = self.free_ranges.remove(index).start;
+        }
+    }
+}
+
 impl VertexBuffers {
     pub fn new(render_state: &render::State) -> Self {
         Self {
@@ -205,11 +285,19 @@ impl Meshes {
             index_buffer: Buffer::new(render_state, wgpu::BufferUsages::INDEX),
 
             seen_meshes: HashMap::with_capacity(16),
+            uploaded_meshes: BTreeMap::new(),
+
+            users: HashMap::with_capacity(16),
         }
     }
 
+    /// Uploads a mesh, or returns the existing index if this exact `Arc` was uploaded before.
+    ///
+    /// Meshes are reference counted by the [`components::MeshRenderer`]s using them (see [`track_mesh_renderers`]),
+    /// and freed once the last one is gone.
+    /// Meshes used by anything else should be kept alive with [`Meshes::retain_mesh`].
     pub fn upload_mesh(&mut self, mesh: Arc<render::Mesh>) -> MeshIndex {
-        let mesh_ref = MeshRef(mesh.clone()); // FIXME: avoid extra clone
+        let mesh_ref = MeshRef(mesh.clone());
         if let Some(index) = self.seen_meshes.get(&mesh_ref).copied() {
             return index;
         }
@@ -246,7 +334,7 @@ impl Meshes {
             0
         };
 
-        MeshIndex {
+        let mesh_index = MeshIndex {
             position_offset,
             normal_offset,
             tex_coord_offset,
@@ -256,7 +344,64 @@ impl Meshes {
             index_count,
             material_id: mesh.material_id,
             mesh_flags: mesh.parts.vertex_format(),
+        };
+
+        self.seen_meshes.insert(mesh_ref, mesh_index);
+        self.uploaded_meshes
+            .insert(mesh_index, UploadedMesh { mesh, ref_count: 0 });
+
+        mesh_index
+    }
+
+    pub fn retain_mesh(&mut self, mesh_index: MeshIndex) {
+        if let Some(uploaded) = self.uploaded_meshes.get_mut(&mesh_index) {
+            uploaded.ref_count += 1;
+        }
+    }
+
+    /// Frees the mesh's vertex and index ranges once nothing references it anymore.
+    pub fn release_mesh(&mut self, mesh_index: MeshIndex) {
+        let Some(uploaded) = self.uploaded_meshes.get_mut(&mesh_index) else {
+            return;
+        };
+        uploaded.ref_count = uploaded.ref_count.saturating_sub(1);
+        if uploaded.ref_count > 0 {
+            return;
+        }
+
+        let UploadedMesh { mesh, .. } = self
+            .uploaded_meshes
+            .remove(&mesh_index)
+            .expect("uploaded mesh should exist");
+        let vertex_count = mesh.parts.positions.len();
+
+        self.vertex_buffers
+            .position
+            .free(mesh_index.position_offset, vertex_count);
+        self.index_buffer
+            .free(mesh_index.index_offset, mesh.indices.len());
+        if mesh.parts.normals.is_some() {
+            self.vertex_buffers
+                .normal
+                .free(mesh_index.normal_offset, vertex_count);
         }
+        if mesh.parts.tex_coords.is_some() {
+            self.vertex_buffers
+                .tex_coord
+                .free(mesh_index.tex_coord_offset, vertex_count);
+        }
+        if mesh.parts.colors.is_some() {
+            self.vertex_buffers
+                .color
+                .free(mesh_index.color_offset, vertex_count);
+        }
+        if mesh.parts.tangents.is_some() {
+            self.vertex_buffers
+                .tangent
+                .free(mesh_index.tangent_offset, vertex_count);
+        }
+
+        self.seen_meshes.remove(&MeshRef(mesh));
     }
 
     pub fn get_mesh_index(&self, mesh: Arc<render::Mesh>) -> Option<MeshIndex> {
@@ -287,3 +432,194 @@ impl Meshes {
         )
     }
 }
+
+/// Keeps the reference counts in [`Meshes`] in sync with the [`components::MeshRenderer`]s in the world.
+pub fn track_mesh_renderers(
+    mut meshes: ResMut<Meshes>,
+    changed: Query<(Entity, &components::MeshRenderer), Changed<components::MeshRenderer>>,
+    renderers: Query<(), With<components::MeshRenderer>>,
+    mut removed: RemovedComponents<components::MeshRenderer>,
+) {
+    for (entity, renderer) in &changed {
+        let previous = meshes.users.insert(entity, renderer.mesh_index);
+        if previous != Some(renderer.mesh_index) {
+            meshes.retain_mesh(renderer.mesh_index);
+            if let Some(previous) = previous {
+                meshes.release_mesh(previous);
+            }
+        }
+    }
+
+    for entity in removed.read() {
+        // The component may have been removed and inserted again since the last run
+        if renderers.contains(entity) {
+            continue;
+        }
+        // Only entities retained by a previous run hold a reference,
+        // renderers added and removed again in between were never counted
+        if let Some(mesh_index) = meshes.users.remove(&entity) {
+            meshes.release_mesh(mesh_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_empty() {
+        let mut allocator = Allocator::default();
+        allocator.allocate(32);
+        allocator.free(0, 16);
+        assert_eq!(allocator.allocate(0), 0);
+        assert_eq!(allocator.len, 32);
+        assert_eq!(allocator.free_ranges.len(), 1);
+        assert_eq!(allocator.free_ranges[0], 0..16);
+    }
+
+    #[test]
+    fn allocate_appends() {
+        let mut allocator = Allocator::default();
+        assert_eq!(allocator.allocate(16), 0);
+        assert_eq!(allocator.allocate(8), 16);
+        assert_eq!(allocator.len, 24);
+        assert!(allocator.free_ranges.is_empty());
+    }
+
+    #[test]
+    fn allocate_splits_free_range() {
+        let mut allocator = Allocator::default();
+        allocator.allocate(32);
+        allocator.allocate(8);
+        allocator.free(0, 32);
+
+        assert_eq!(allocator.allocate(8), 0);
+        assert_eq!(allocator.free_ranges.len(), 1);
+        assert_eq!(allocator.free_ranges[0], 8..32);
+        assert_eq!(allocator.allocate(24), 8);
+        assert!(allocator.free_ranges.is_empty());
+        assert_eq!(allocator.len, 40);
+    }
+
+    #[test]
+    fn allocate_skips_small_free_ranges() {
+        let mut allocator = Allocator::default();
+        allocator.allocate(8);
+        allocator.allocate(8);
+        allocator.free(0, 8);
+
+        assert_eq!(allocator.allocate(16), 16);
+        assert_eq!(allocator.free_ranges.len(), 1);
+        assert_eq!(allocator.free_ranges[0], 0..8);
+    }
+
+    #[test]
+    fn free_merges_with_previous() {
+        let mut allocator = Allocator::default();
+        for _ in 0..4 {
+            allocator.allocate(8);
+        }
+        allocator.free(0, 8);
+        allocator.free(8, 8);
+
+        assert_eq!(allocator.free_ranges.len(), 1);
+        assert_eq!(allocator.free_ranges[0], 0..16);
+        assert_eq!(allocator.len, 32);
+    }
+
+    #[test]
+    fn free_merges_with_next() {
+        let mut allocator = Allocator::default();
+        for _ in 0..4 {
+            allocator.allocate(8);
+        }
+        allocator.free(8, 8);
+        allocator.free(0, 8);
+
+        assert_eq!(allocator.free_ranges.len(), 1);
+        assert_eq!(allocator.free_ranges[0], 0..16);
+    }
+
+    #[test]
+    fn free_merges_with_both_neighbours() {
+        let mut allocator = Allocator::default();
+        for _ in 0..4 {
+            allocator.allocate(8);
+        }
+        allocator.free(0, 8);
+        allocator.free(16, 8);
+        assert_eq!(allocator.free_ranges, [0..8, 16..24]);
+
+        allocator.free(8, 8);
+        assert_eq!(allocator.free_ranges.len(), 1);
+        assert_eq!(allocator.free_ranges[0], 0..24);
+        assert_eq!(allocator.allocate(24), 0);
+    }
+
+    #[test]
+    fn free_at_end_shrinks() {
+        let mut allocator = Allocator::default();
+        for _ in 0..3 {
+            allocator.allocate(8);
+        }
+        allocator.free(8, 8);
+        allocator.free(16, 8);
+
+        // Both trailing ranges are given back, so the next allocation reuses them
+        assert!(allocator.free_ranges.is_empty());
+        assert_eq!(allocator.len, 8);
+        assert_eq!(allocator.allocate(16), 8);
+    }
+
+    #[test]
+    fn reuse_after_free() {
+        let mut allocator = Allocator::default();
+        let a = allocator.allocate(8);
+        allocator.allocate(8);
+        allocator.free(a, 8);
+
+        assert_eq!(allocator.allocate(8), a);
+        assert_eq!(allocator.len, 16);
+    }
+
+    #[test]
+    #[ignore = "needs a gpu or a software adapter"]
+    fn renderer_spawned_and_despawned_between_runs() {
+        let render_state = pollster::block_on(render::State::new_headless(64, 64));
+        let mut world = World::new();
+        world.insert_resource(Meshes::new(&render_state));
+
+        let parts = render::MeshParts {
+            positions: vec![glam::Vec3::ZERO, glam::Vec3::X, glam::Vec3::Y],
+            normals: None,
+            tex_coords: None,
+            colors: None,
+            tangents: None,
+        };
+        let mesh = Arc::new(render::Mesh::new(
+            &parts,
+            &[0, 1, 2],
+            assets::MaterialId::Path(0),
+        ));
+
+        let mut track = IntoSystem::into_system(track_mesh_renderers);
+        track.initialize(&mut world);
+
+        let renderer =
+            components::MeshRenderer::new(&mut world.resource_mut::<Meshes>(), mesh.clone());
+        let mesh_index = renderer.mesh_index;
+        world.spawn(renderer);
+        track.run((), &mut world);
+
+        // Never seen by the system, so it must not give up the reference of the first renderer
+        let renderer = components::MeshRenderer::new(&mut world.resource_mut::<Meshes>(), mesh);
+        let entity = world.spawn(renderer).id();
+        world.despawn(entity);
+        track.run((), &mut world);
+
+        let meshes = world.resource::<Meshes>();
+        assert_eq!(meshes.uploaded_meshes[&mesh_index].ref_count, 1);
+        assert!(!meshes.users.contains_key(&entity));
+    }
+}
@@ -24,19 +24,26 @@ use crate::render;
 use crate::time;
 
 use bevy_ecs::prelude::*;
-use rapier3d::prelude::*;
 
 use bevy_ecs::schedule::ScheduleLabel;
 
 mod schedules;
-use bevy_ecs::system::SystemState;
 pub use schedules::*;
 
 mod world_builder;
 pub use world_builder::WorldBuilder;
 
+mod plugin;
+pub use plugin::{Plugin, PluginId};
+
+pub mod file;
+pub use file::SceneFile;
+
+mod gltf;
+pub use gltf::spawn_gltf_scene;
+
 mod meshes;
-pub use meshes::{MeshIndex, Meshes};
+pub use meshes::{track_mesh_renderers, MeshIndex, Meshes};
 
 pub struct Scene {
     pub world: World,
@@ -44,10 +51,18 @@ pub struct Scene {
 
 #[derive(Resource)]
 pub struct Buffers {
-    pub transforms: render::buffer::dynamic::Buffer<components::Transform>,
+    pub transforms: render::buffer::dynamic::Buffer<components::GlobalTransform>,
     pub lights: render::buffer::dynamic::Buffer<components::light::PreparedLight>,
 
     pub instances: render::buffer::instances::Buffer,
+    pub object_draws: render::buffer::indirect::Buffer,
+    // Alpha blended objects, sorted back to front every frame
+    pub transparent_draws: render::buffer::indirect::Buffer,
+
+    pub shadow_maps: render::shadow::ShadowMaps,
+    pub light_tiles: render::buffer::light_tiles::Buffer,
+    pub hdr: render::buffer::hdr::Buffer,
+    pub post: render::post::Buffer,
 
     pub gbuffer: render::buffer::geometry::Buffer,
     pub screen_vertices: wgpu::Buffer,
@@ -64,6 +79,16 @@ impl Buffers {
         let instances =
             render::buffer::instances::Buffer::new(render_state, wgpu::BufferUsages::empty());
 
+        let object_draws =
+            render::buffer::indirect::Buffer::new(render_state, wgpu::BufferUsages::empty());
+        let transparent_draws =
+            render::buffer::indirect::Buffer::new(render_state, wgpu::BufferUsages::empty());
+
+        let shadow_maps = render::shadow::ShadowMaps::new(render_state);
+        let light_tiles = render::buffer::light_tiles::Buffer::new(render_state);
+        let hdr = render::buffer::hdr::Buffer::new(render_state);
+        let post = render::post::Buffer::new(render_state);
+
         let gbuffer = render::buffer::geometry::Buffer::new(render_state);
 
         let screen_vertices = create_screen_vertex_buffer(render_state);
@@ -72,6 +97,12 @@ impl Buffers {
             transforms,
             lights,
             instances,
+            object_draws,
+            transparent_draws,
+            shadow_maps,
+            light_tiles,
+            hdr,
+            post,
             gbuffer,
             screen_vertices,
         }
@@ -79,110 +110,127 @@ impl Buffers {
 }
 
 pub struct PrepareResources<'buf> {
-    pub transforms: render::buffer::dynamic::Writer<'buf, components::Transform>,
+    pub transforms: render::buffer::dynamic::Writer<'buf, components::GlobalTransform>,
     pub lights: render::buffer::dynamic::Writer<'buf, components::light::PreparedLight>,
     pub instances: render::buffer::instances::Writer<'buf>,
+    pub object_draws: render::buffer::indirect::Writer<'buf>,
+    pub transparent_draws: render::buffer::indirect::Writer<'buf>,
     pub assets: &'buf assets::Loader,
 }
 
 impl Scene {
     pub fn new(render_state: render::State) -> Self {
         let mut builder = WorldBuilder::new();
-
-        builder
-            .insert_resource(Meshes::new(&render_state))
-            .insert_resource(Buffers::new(&render_state));
-
-        schedules::init_into(&mut builder);
-        time::init_into(&mut builder);
-        physics::init_into(&mut builder);
-        input::init_into(&mut builder);
-        assets::init_into(&render_state, &mut builder);
-        player::init_into(&render_state, &mut builder);
-        render::init_into(render_state, &mut builder);
+        add_default_plugins(&mut builder, render_state);
 
         let mut world = builder.build();
 
-        let mut system_state = SystemState::<(
-            ResMut<assets::Loader>,
-            Res<render::State>,
-            ResMut<Meshes>,
-            ResMut<physics::State>,
-            Commands,
-        )>::from_world(&mut world);
-
-        let (mut assets, render_state, mut meshes, mut physics_state, mut commands) =
-            system_state.get_mut(&mut world);
-        let physics_state = &mut *physics_state;
-
-        let diffuse_id = assets
-            .textures
-            .load_from_path(&render_state, "assets/textures/cube-diffuse.jpg");
-        let normal_id = assets
-            .textures
-            .load_from_path(&render_state, "assets/textures/cube-normal.png");
-
-        let material_id = assets::MaterialId::from_path("cube_material");
-        assets.materials.insert(
-            material_id,
-            render::Material {
-                base_color_texture: Some(diffuse_id),
-                normal_texture: Some(normal_id),
-                ..Default::default()
-            },
-        );
+        file::SceneFile::load("assets/scenes/demo.json")
+            .expect("failed to load demo scene")
+            .spawn_into(&mut world);
 
-        let model_id = assets
-            .models
-            .load_tobj("assets/meshes/cube.obj", material_id);
-        let model = assets.models.get_expect(model_id);
-        let mesh = model.meshes[0].clone();
-
-        let light = components::Light::new(&mut assets, &mut meshes);
-        commands.spawn((
-            components::Transform::from_position(glam::vec3(0.0, 5.0, 0.0)),
-            light,
-        ));
-
-        let mesh_renderer = components::MeshRenderer::new(&mut meshes, mesh.clone());
-        let mut entity_builder = commands.spawn((components::Transform::default(), mesh_renderer));
-
-        let rigid_body = RigidBodyBuilder::dynamic().additional_mass(1.0).build();
-        let rigid_body = physics::RigidBody::new(physics_state, entity_builder.id(), rigid_body);
-
-        let collider = ColliderBuilder::cuboid(1.1, 1.1, 1.1)
-            .restitution(0.9)
-            .build();
-        physics_state.collider_set.insert_with_parent(
-            collider,
-            rigid_body.handle,
-            &mut physics_state.rigid_body_set,
-        );
-        entity_builder.insert(rigid_body);
+        Self { world }
+    }
 
-        let mesh_renderer = components::MeshRenderer::new(&mut meshes, mesh);
-        commands.spawn((
-            components::Transform::from_position_scale(
-                glam::vec3(0.0, -4.0, 0.0),
-                glam::vec3(100.0, 0.1, 100.0),
-            ),
-            mesh_renderer,
-        ));
+    /// Loads a scene file and spawns its entities into this scene.
+    pub fn spawn_file(
+        &mut self,
+        path: impl AsRef<camino::Utf8Path>,
+    ) -> Result<Vec<Entity>, file::Error> {
+        let file = SceneFile::load(path)?;
+        Ok(file.spawn_into(&mut self.world))
+    }
 
-        let collider = ColliderBuilder::cuboid(100.0, 0.1, 100.0)
-            .translation(vector![0.0, -4.0, 0.0])
-            .build();
-        physics_state.collider_set.insert(collider);
+    /// Spawns the default scene of a gltf file, returning the root entity of the spawned hierarchy.
+    pub fn spawn_gltf(
+        &mut self,
+        path: impl AsRef<camino::Utf8Path>,
+    ) -> Result<Entity, assets::Error> {
+        spawn_gltf_scene(&mut self.world, path, None)
+    }
 
-        system_state.apply(&mut world);
+    /// Saves the current state of this scene to a scene file.
+    pub fn save_file(&mut self, path: impl AsRef<camino::Utf8Path>) -> Result<(), file::Error> {
+        SceneFile::snapshot(&mut self.world).save(path)
+    }
 
-        Self { world }
+    /// Creates a scene from a custom set of plugins, without spawning anything into it.
+    pub fn from_builder(builder: WorldBuilder) -> Self {
+        Self {
+            world: builder.build(),
+        }
     }
 
     pub fn update(&mut self) {
         self.world.run_schedule(Main.intern());
         self.world.clear_trackers();
     }
+
+    /// Renders the current state of the world without advancing it, and reads the result back.
+    /// Only works with a headless render state and the asset and player plugins, returns `None` otherwise.
+    pub fn render_to_image(&mut self) -> Option<image::RgbaImage> {
+        use bevy_ecs::system::RunSystemOnce;
+
+        if !self
+            .world
+            .get_resource::<render::State>()?
+            .wgpu
+            .is_headless()
+        {
+            return None;
+        }
+
+        // Make sure freshly spawned or moved entities are drawn where they are
+        self.world
+            .run_system_once(components::hierarchy::insert_global_transforms);
+        self.world
+            .run_system_once(components::hierarchy::propagate_transforms);
+
+        // The same chain the render plugin runs in Last, so new meshes and materials are ready to draw
+        if self.world.contains_resource::<assets::Loader>() {
+            self.world.run_system_once(assets::finish_loads);
+        }
+        self.world.run_system_once(track_mesh_renderers);
+        self.world
+            .run_system_once(render::system::prepare_pipelines);
+        if !self.world.contains_resource::<assets::Loader>()
+            || !self.world.contains_resource::<player::Player>()
+        {
+            return None;
+        }
+        self.world.run_system_once(render::system::render);
+
+        render::capture::read_render_target(self.world.get_resource::<render::State>()?)
+    }
+}
+
+/// Adds every plugin wormhole ships with.
+/// Use [`WorldBuilder::add_plugin`] directly to pick and choose instead.
+pub fn add_default_plugins(builder: &mut WorldBuilder, render_state: render::State) {
+    // Added first so the player plugin sees the render state and picks up its aspect ratio
+    builder
+        .add_plugin(render::Plugin::new(render_state))
+        .add_plugin(assets::Plugin);
+    add_simulation_plugins(builder);
+
+    // Only useful while iterating on assets and shaders
+    if cfg!(debug_assertions) {
+        builder.add_plugin(assets::hot_reload::Plugin);
+    }
+}
+
+/// Adds the plugins that don't need a gpu, for running scenes without rendering them (tests, servers).
+///
+/// Assets are uploaded as soon as they are loaded, so the asset plugin needs the render plugin and is left out too.
+/// Scene files spawned into such a world only get their transforms and physics.
+pub fn add_simulation_plugins(builder: &mut WorldBuilder) {
+    builder
+        .add_plugin(MainSchedulePlugin)
+        .add_plugin(time::Plugin)
+        .add_plugin(physics::Plugin)
+        .add_plugin(input::Plugin)
+        .add_plugin(components::hierarchy::Plugin)
+        .add_plugin(player::Plugin);
 }
 
 fn create_screen_vertex_buffer(render_state: &render::State) -> wgpu::Buffer {
@@ -237,3 +285,82 @@ fn create_screen_vertex_buffer(render_state: &render::State) -> wgpu::Buffer {
             usage: wgpu::BufferUsages::VERTEX,
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headless_scene() -> Scene {
+        let render_state = pollster::block_on(render::State::new_headless(64, 64));
+        let mut builder = WorldBuilder::new();
+        add_default_plugins(&mut builder, render_state);
+        Scene::from_builder(builder)
+    }
+
+    #[test]
+    fn simulation_plugins_run_without_a_gpu() {
+        let mut builder = WorldBuilder::new();
+        add_simulation_plugins(&mut builder);
+        let mut scene = Scene::from_builder(builder);
+
+        let entity = scene
+            .world
+            .spawn(components::Transform::from_xyz(1.0, 2.0, 3.0))
+            .id();
+        scene.update();
+
+        let global_transform = scene.world.get::<components::GlobalTransform>(entity);
+        assert_eq!(
+            global_transform.map(|g| g.translation()),
+            Some(glam::vec3(1.0, 2.0, 3.0))
+        );
+        assert!(scene.render_to_image().is_none());
+    }
+
+    #[test]
+    #[ignore = "needs a gpu or a software adapter"]
+    fn render_to_image_draws_custom_materials() {
+        let mut scene = headless_scene();
+        let empty = scene.render_to_image().expect("render state is headless");
+
+        let material_id = assets::MaterialId::from_path("hologram");
+        scene
+            .world
+            .resource_mut::<assets::Loader>()
+            .materials
+            .insert_custom(material_id, render::Hologram::default());
+
+        // A quad facing the player, big enough to cover the middle of the image
+        let parts = render::MeshParts {
+            positions: vec![
+                glam::vec3(-4.0, -4.0, 0.0),
+                glam::vec3(4.0, -4.0, 0.0),
+                glam::vec3(4.0, 4.0, 0.0),
+                glam::vec3(-4.0, 4.0, 0.0),
+            ],
+            normals: Some(vec![glam::Vec3::Z; 4]),
+            tex_coords: Some(vec![
+                glam::vec2(0.0, 1.0),
+                glam::vec2(1.0, 1.0),
+                glam::vec2(1.0, 0.0),
+                glam::vec2(0.0, 0.0),
+            ]),
+            colors: None,
+            tangents: None,
+        };
+        let mesh = render::Mesh::new(&parts, &[0, 1, 2, 0, 2, 3], material_id);
+        let renderer = components::MeshRenderer::new(
+            &mut scene.world.resource_mut::<Meshes>(),
+            std::sync::Arc::new(mesh),
+        );
+        scene
+            .world
+            .spawn((components::Transform::default(), renderer));
+
+        let image = scene.render_to_image().expect("render state is headless");
+        assert!(
+            image.pixels().zip(empty.pixels()).any(|(a, b)| a != b),
+            "the custom material mesh wasn't drawn"
+        );
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::WorldBuilder;
+
+use std::any::TypeId;
+
+/// A self contained chunk of functionality (resources, events, systems) that can be added to a [`WorldBuilder`].
+///
+/// Plugins are built when [`WorldBuilder::build`] is called, after all of their dependencies have been built.
+pub trait Plugin: 'static {
+    fn build(self: Box<Self>, builder: &mut WorldBuilder);
+
+    /// Plugins that must be built before this one.
+    fn dependencies(&self) -> Vec<PluginId> {
+        Vec::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PluginId {
+    type_id: TypeId,
+    name: &'static str,
+}
+
+impl PluginId {
+    pub fn of<P: Plugin>() -> Self {
+        Self {
+            type_id: TypeId::of::<P>(),
+            name: std::any::type_name::<P>(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl std::fmt::Display for PluginId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
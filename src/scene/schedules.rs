@@ -147,7 +147,16 @@ impl FixedMain {
     }
 }
 
-pub fn init_into(builder: &mut super::WorldBuilder) {
+/// Sets up the main and fixed main schedules that drive every other schedule.
+pub struct MainSchedulePlugin;
+
+impl super::Plugin for MainSchedulePlugin {
+    fn build(self: Box<Self>, builder: &mut super::WorldBuilder) {
+        init_into(builder);
+    }
+}
+
+fn init_into(builder: &mut super::WorldBuilder) {
     let mut main_schedule = Schedule::new(Main);
     main_schedule.set_executor_kind(ExecutorKind::SingleThreaded);
 
@@ -18,8 +18,15 @@
 use bevy_ecs::prelude::*;
 use bevy_ecs::schedule::ScheduleLabel;
 
+use std::collections::HashSet;
+
+use super::{Plugin, PluginId};
+
 pub struct WorldBuilder {
     world: World,
+
+    plugins: Vec<(PluginId, Box<dyn Plugin>)>,
+    built_plugins: HashSet<PluginId>,
 }
 
 impl WorldBuilder {
@@ -27,7 +34,45 @@ impl WorldBuilder {
         let mut world = World::new();
         world.init_resource::<Schedules>();
 
-        Self { world }
+        Self {
+            world,
+
+            plugins: Vec::new(),
+            built_plugins: HashSet::new(),
+        }
+    }
+
+    /// Queues a plugin to be built when the world is built.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a plugin of the same type has already been added.
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut Self {
+        let id = PluginId::of::<P>();
+        assert!(
+            !self.has_plugin::<P>(),
+            "plugin {id} has already been added"
+        );
+
+        self.plugins.push((id, Box::new(plugin)));
+        self
+    }
+
+    pub fn has_plugin<P: Plugin>(&self) -> bool {
+        let id = PluginId::of::<P>();
+        self.built_plugins.contains(&id) || self.plugins.iter().any(|(i, _)| *i == id)
+    }
+
+    pub fn resource<R: Resource>(&self) -> &R {
+        self.world.resource::<R>()
+    }
+
+    pub fn get_resource<R: Resource>(&self) -> Option<&R> {
+        self.world.get_resource::<R>()
+    }
+
+    pub fn contains_resource<R: Resource>(&self) -> bool {
+        self.world.contains_resource::<R>()
     }
 
     pub fn add_schedule(&mut self, schedule: Schedule) -> &mut Self {
@@ -97,7 +142,49 @@ impl WorldBuilder {
         }
     }
 
-    pub fn build(self) -> World {
+    /// Builds all added plugins in dependency order (falling back to the order they were added in) and returns the finished world.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a plugin depends on a plugin that was never added, or if plugins depend on each other in a cycle.
+    pub fn build(mut self) -> World {
+        // Plugins may add other plugins while being built, so we can't just sort them once.
+        while !self.plugins.is_empty() {
+            let ready = self.plugins.iter().position(|(_, plugin)| {
+                plugin
+                    .dependencies()
+                    .iter()
+                    .all(|dependency| self.built_plugins.contains(dependency))
+            });
+
+            let Some(index) = ready else {
+                self.panic_unresolved_plugins();
+            };
+
+            let (id, plugin) = self.plugins.remove(index);
+            plugin.build(&mut self);
+            self.built_plugins.insert(id);
+        }
+
         self.world
     }
+
+    fn panic_unresolved_plugins(&self) -> ! {
+        let mut message = String::from("unable to resolve plugin dependencies:");
+        for (id, plugin) in &self.plugins {
+            for dependency in plugin.dependencies() {
+                if self.built_plugins.contains(&dependency) {
+                    continue;
+                }
+
+                let reason = if self.plugins.iter().any(|(i, _)| *i == dependency) {
+                    "cyclic dependency"
+                } else {
+                    "missing plugin"
+                };
+                message.push_str(&format!("\n  {id} depends on {dependency} ({reason})"));
+            }
+        }
+        panic!("{message}")
+    }
 }
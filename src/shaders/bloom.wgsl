@@ -0,0 +1,61 @@
+#import wormhole::fullscreen as Fullscreen
+
+@vertex
+fn vs_main(model: Fullscreen::VertexInput) -> Fullscreen::VertexOutput {
+    return Fullscreen::vertex(model);
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+struct Constants {
+    // Offset between blur taps in uv space, zero for the other passes
+    direction: vec2<f32>,
+    intensity: f32,
+}
+var<push_constant> constants: Constants;
+
+@group(0) @binding(0)
+var source_sampler: sampler;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+// Halves the emissive target, the bilinear sampler averages 4 pixels for us
+@fragment
+fn fs_downsample(in: Fullscreen::VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(textureSample(source, source_sampler, in.tex_coords).rgb, 1.0);
+
+    return out;
+}
+
+// One direction of a separable 9 tap gaussian blur
+@fragment
+fn fs_blur(in: Fullscreen::VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+    var color = textureSample(source, source_sampler, in.tex_coords).rgb * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = constants.direction * f32(i);
+        color += textureSample(source, source_sampler, in.tex_coords + offset).rgb * weights[i];
+        color += textureSample(source, source_sampler, in.tex_coords - offset).rgb * weights[i];
+    }
+
+    out.color = vec4<f32>(color, 1.0);
+
+    return out;
+}
+
+// Additively blended onto the hdr target
+@fragment
+fn fs_composite(in: Fullscreen::VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = vec4<f32>(textureSample(source, source_sampler, in.tex_coords).rgb * constants.intensity, 1.0);
+
+    return out;
+}
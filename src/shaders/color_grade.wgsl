@@ -0,0 +1,51 @@
+#import wormhole::fullscreen as Fullscreen
+
+@vertex
+fn vs_main(model: Fullscreen::VertexInput) -> Fullscreen::VertexOutput {
+    return Fullscreen::vertex(model);
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+struct Constants {
+    // How much of the graded color to mix in
+    strength: f32,
+    lut_size: f32,
+}
+var<push_constant> constants: Constants;
+
+@group(0) @binding(0)
+var source_sampler: sampler;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+@group(1) @binding(0)
+var lut_sampler: sampler;
+@group(1) @binding(1)
+var lut: texture_3d<f32>;
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let cutoff = color <= vec3<f32>(0.0031308);
+    let lower = color * 12.92;
+    let higher = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, cutoff);
+}
+
+@fragment
+fn fs_main(in: Fullscreen::VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    let color = textureSampleLevel(source, source_sampler, in.tex_coords, 0.0).rgb;
+
+    // LUTs are authored against display encoded colors, and sampled at texel centers
+    let encoded = clamp(linear_to_srgb(color), vec3<f32>(0.0), vec3<f32>(1.0));
+    let scale = (constants.lut_size - 1.0) / constants.lut_size;
+    let offset = 0.5 / constants.lut_size;
+    let graded = textureSampleLevel(lut, lut_sampler, encoded * scale + offset, 0.0).rgb;
+
+    out.color = vec4<f32>(mix(color, graded, constants.strength), 1.0);
+
+    return out;
+}
@@ -0,0 +1,20 @@
+#define_import_path wormhole::cubemap
+
+const PI = 3.14159265359;
+
+// The direction through the center of a texel of a cube map face, faces are ordered +X, -X, +Y, -Y, +Z, -Z
+fn face_direction(face: u32, texel: vec2<u32>, size: u32) -> vec3<f32> {
+    let uv = (vec2<f32>(texel) + 0.5) / f32(size) * 2.0 - 1.0;
+
+    var direction: vec3<f32>;
+    switch face {
+        case 0u: { direction = vec3<f32>(1.0, -uv.y, -uv.x); }
+        case 1u: { direction = vec3<f32>(-1.0, -uv.y, uv.x); }
+        case 2u: { direction = vec3<f32>(uv.x, 1.0, uv.y); }
+        case 3u: { direction = vec3<f32>(uv.x, -1.0, -uv.y); }
+        case 4u: { direction = vec3<f32>(uv.x, -uv.y, 1.0); }
+        default: { direction = vec3<f32>(-uv.x, -uv.y, -1.0); }
+    }
+
+    return normalize(direction);
+}
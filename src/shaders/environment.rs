@@ -0,0 +1,115 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+use crate::shaders;
+
+pub struct EnvironmentPipelines {
+    pub equirect_to_cube: wgpu::ComputePipeline,
+    pub irradiance: wgpu::ComputePipeline,
+    pub prefilter: wgpu::ComputePipeline,
+    pub brdf_lut: wgpu::ComputePipeline,
+}
+
+pub fn create_environment_pipelines(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+) -> Result<EnvironmentPipelines, shaders::registry::Error> {
+    let equirect_shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/equirect_to_cube.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+    let environment_shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/environment.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+
+    let create_pipeline =
+        |label: &str,
+         shader: &wgpu::ShaderModule,
+         entry_point: &str,
+         bind_group_layouts: &[&wgpu::BindGroupLayout],
+         push_constant_ranges: &[wgpu::PushConstantRange]| {
+            let layout = gpu_state
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some(label),
+                    bind_group_layouts,
+                    push_constant_ranges,
+                });
+
+            gpu_state
+                .device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    module: shader,
+                    entry_point,
+                })
+        };
+
+    let equirect_to_cube = create_pipeline(
+        "equirect to cube pipeline",
+        &equirect_shader,
+        "main",
+        &[
+            &bind_groups.environment_target,
+            &bind_groups.environment_equirect,
+        ],
+        &[],
+    );
+    let irradiance = create_pipeline(
+        "environment irradiance pipeline",
+        &environment_shader,
+        "irradiance",
+        &[
+            &bind_groups.environment_target,
+            &bind_groups.environment_source,
+        ],
+        &[],
+    );
+    let prefilter = create_pipeline(
+        "environment prefilter pipeline",
+        &environment_shader,
+        "prefilter",
+        &[
+            &bind_groups.environment_target,
+            &bind_groups.environment_source,
+        ],
+        &[wgpu::PushConstantRange {
+            stages: wgpu::ShaderStages::COMPUTE,
+            range: 0..4,
+        }],
+    );
+    let brdf_lut = create_pipeline(
+        "environment brdf lut pipeline",
+        &environment_shader,
+        "brdf_lut",
+        &[&bind_groups.environment_target],
+        &[],
+    );
+
+    Ok(EnvironmentPipelines {
+        equirect_to_cube,
+        irradiance,
+        prefilter,
+        brdf_lut,
+    })
+}
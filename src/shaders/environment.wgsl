@@ -0,0 +1,148 @@
+#import wormhole::cubemap as Cubemap
+
+@group(0) @binding(0)
+var target_faces: texture_storage_2d_array<rgba16float, write>;
+
+@group(1) @binding(0)
+var source_sampler: sampler;
+@group(1) @binding(1)
+var source: texture_cube<f32>;
+
+struct Constants {
+    // Roughness of the mip level being prefiltered
+    roughness: f32,
+}
+var<push_constant> constants: Constants;
+
+const SAMPLE_COUNT = 512u;
+
+// Diffuse light arriving from every direction of the hemisphere around each texel's direction
+@compute @workgroup_size(8, 8, 1)
+fn irradiance(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(target_faces).x;
+    if id.x >= size || id.y >= size {
+        return;
+    }
+
+    let normal = Cubemap::face_direction(id.z, id.xy, size);
+    let basis = tangent_basis(normal);
+
+    let sample_delta = 0.05;
+    var irradiance = vec3<f32>(0.0);
+    var sample_count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * Cubemap::PI; phi += sample_delta) {
+        for (var theta = 0.0; theta < 0.5 * Cubemap::PI; theta += sample_delta) {
+            let tangent_sample = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let direction = basis * tangent_sample;
+
+            irradiance += textureSampleLevel(source, source_sampler, direction, 0.0).rgb * cos(theta) * sin(theta);
+            sample_count += 1.0;
+        }
+    }
+    irradiance = Cubemap::PI * irradiance / sample_count;
+
+    textureStore(target_faces, id.xy, id.z, vec4<f32>(irradiance, 1.0));
+}
+
+// Specular reflections for one roughness, assuming the view direction is the reflection direction
+@compute @workgroup_size(8, 8, 1)
+fn prefilter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(target_faces).x;
+    if id.x >= size || id.y >= size {
+        return;
+    }
+
+    let normal = Cubemap::face_direction(id.z, id.xy, size);
+    let view = normal;
+
+    var color = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let xi = hammersley(i, SAMPLE_COUNT);
+        let half_vector = importance_sample_ggx(xi, normal, constants.roughness);
+        let light = normalize(2.0 * dot(view, half_vector) * half_vector - view);
+
+        let n_dot_l = dot(normal, light);
+        if n_dot_l > 0.0 {
+            color += textureSampleLevel(source, source_sampler, light, 0.0).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+
+    textureStore(target_faces, id.xy, id.z, vec4<f32>(color / max(total_weight, 0.0001), 1.0));
+}
+
+// Scale (r) and bias (g) applied to f0 by the split sum approximation, by n_dot_v (x) and roughness (y)
+@compute @workgroup_size(8, 8, 1)
+fn brdf_lut(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(target_faces);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    let n_dot_v = (f32(id.x) + 0.5) / f32(size.x);
+    let roughness = (f32(id.y) + 0.5) / f32(size.y);
+
+    let view = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let xi = hammersley(i, SAMPLE_COUNT);
+        let half_vector = importance_sample_ggx(xi, normal, roughness);
+        let light = normalize(2.0 * dot(view, half_vector) * half_vector - view);
+
+        let n_dot_l = max(light.z, 0.0);
+        let n_dot_h = max(half_vector.z, 0.0);
+        let v_dot_h = max(dot(view, half_vector), 0.0);
+
+        if n_dot_l > 0.0 {
+            let g = geometry_smith_ibl(n_dot_v, n_dot_l, roughness);
+            let g_vis = (g * v_dot_h) / (n_dot_h * n_dot_v);
+            let fc = pow(1.0 - v_dot_h, 5.0);
+
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+
+    let result = vec2<f32>(scale, bias) / f32(SAMPLE_COUNT);
+    textureStore(target_faces, id.xy, 0u, vec4<f32>(result, 0.0, 1.0));
+}
+
+fn tangent_basis(normal: vec3<f32>) -> mat3x3<f32> {
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if abs(normal.y) > 0.999 {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+
+    return mat3x3<f32>(tangent, bitangent, normal);
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+
+    let phi = 2.0 * Cubemap::PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+
+    let half_vector = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    return normalize(tangent_basis(normal) * half_vector);
+}
+
+// Image based lighting uses a different k than direct lighting
+fn geometry_smith_ibl(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness * roughness) / 2.0;
+    let ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+    return ggx_v * ggx_l;
+}
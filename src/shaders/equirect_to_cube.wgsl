@@ -0,0 +1,43 @@
+#import wormhole::cubemap as Cubemap
+
+@group(0) @binding(0)
+var target_faces: texture_storage_2d_array<rgba16float, write>;
+
+// Rgba32Float isn't filterable, so this is sampled by hand
+@group(1) @binding(0)
+var equirect: texture_2d<f32>;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(target_faces).x;
+    if id.x >= size || id.y >= size {
+        return;
+    }
+
+    let direction = Cubemap::face_direction(id.z, id.xy, size);
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * Cubemap::PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / Cubemap::PI,
+    );
+
+    textureStore(target_faces, id.xy, id.z, vec4<f32>(sample_bilinear(uv), 1.0));
+}
+
+// Wraps around horizontally and clamps vertically
+fn sample_bilinear(uv: vec2<f32>) -> vec3<f32> {
+    let dimensions = vec2<i32>(textureDimensions(equirect));
+
+    let position = uv * vec2<f32>(dimensions) - 0.5;
+    let base = floor(position);
+    let t = position - base;
+
+    let x0 = (i32(base.x) % dimensions.x + dimensions.x) % dimensions.x;
+    let x1 = (x0 + 1) % dimensions.x;
+    let y0 = clamp(i32(base.y), 0, dimensions.y - 1);
+    let y1 = clamp(i32(base.y) + 1, 0, dimensions.y - 1);
+
+    let top = mix(textureLoad(equirect, vec2<i32>(x0, y0), 0).rgb, textureLoad(equirect, vec2<i32>(x1, y0), 0).rgb, t.x);
+    let bottom = mix(textureLoad(equirect, vec2<i32>(x0, y1), 0).rgb, textureLoad(equirect, vec2<i32>(x1, y1), 0).rgb, t.x);
+
+    return mix(top, bottom, t.y);
+}
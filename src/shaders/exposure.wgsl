@@ -0,0 +1,68 @@
+struct Constants {
+    delta_time: f32,
+    speed: f32,
+    key: f32,
+    min_exposure: f32,
+    max_exposure: f32,
+}
+var<push_constant> constants: Constants;
+
+struct ExposureState {
+    adapted_luminance: f32,
+    exposure: f32,
+}
+
+@group(0) @binding(0)
+var hdr_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var<storage, read_write> exposure_state: ExposureState;
+
+const WORKGROUP_SIZE = 256u;
+// Only every nth pixel in each direction is measured
+const SAMPLE_STRIDE = 4u;
+
+var<workgroup> log_luminance_sums: array<f32, WORKGROUP_SIZE>;
+var<workgroup> sample_counts: array<u32, WORKGROUP_SIZE>;
+
+// Dispatched as a single workgroup that measures the average luminance of the whole screen
+@compute @workgroup_size(256, 1, 1)
+fn cs_main(@builtin(local_invocation_index) local_index: u32) {
+    let size = textureDimensions(hdr_texture) / SAMPLE_STRIDE;
+    let total_samples = size.x * size.y;
+
+    var log_luminance_sum = 0.0;
+    var sample_count = 0u;
+    for (var i = local_index; i < total_samples; i += WORKGROUP_SIZE) {
+        let pixel = vec2<u32>(i % size.x, i / size.x) * SAMPLE_STRIDE;
+        let color = textureLoad(hdr_texture, pixel, 0).rgb;
+        let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+        log_luminance_sum += log(max(luminance, 0.0001));
+        sample_count += 1u;
+    }
+    log_luminance_sums[local_index] = log_luminance_sum;
+    sample_counts[local_index] = sample_count;
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if local_index < stride {
+            log_luminance_sums[local_index] += log_luminance_sums[local_index + stride];
+            sample_counts[local_index] += sample_counts[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if local_index == 0u {
+        let average = exp(log_luminance_sums[0] / f32(max(sample_counts[0], 1u)));
+
+        // Nothing has been measured yet on the first frame, so start out fully adapted
+        var adapted = exposure_state.adapted_luminance;
+        if adapted <= 0.0 {
+            adapted = average;
+        } else {
+            adapted += (average - adapted) * (1.0 - exp(-constants.delta_time * constants.speed));
+        }
+
+        exposure_state.adapted_luminance = adapted;
+        exposure_state.exposure = clamp(constants.key / adapted, constants.min_exposure, constants.max_exposure);
+    }
+}
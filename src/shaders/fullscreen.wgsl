@@ -0,0 +1,21 @@
+#define_import_path wormhole::fullscreen
+
+// Matches the vertices in scene::Buffers::screen_vertices
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+fn vertex(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.tex_coords = model.tex_coords;
+
+    return out;
+}
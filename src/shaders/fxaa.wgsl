@@ -0,0 +1,69 @@
+#import wormhole::fullscreen as Fullscreen
+
+@vertex
+fn vs_main(model: Fullscreen::VertexInput) -> Fullscreen::VertexOutput {
+    return Fullscreen::vertex(model);
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+struct Constants {
+    texel_size: vec2<f32>,
+    span_max: f32,
+    reduce_mul: f32,
+    reduce_min: f32,
+}
+var<push_constant> constants: Constants;
+
+@group(0) @binding(0)
+var source_sampler: sampler;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+// The source is linear, edges are found on (roughly) perceptual luma
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(sqrt(color), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample_at(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: Fullscreen::VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    let texel = constants.texel_size;
+    let uv = in.tex_coords;
+
+    let rgb_m = sample_at(uv);
+    let luma_nw = luma(sample_at(uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_at(uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_at(uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_at(uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_m = luma(rgb_m);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Blur along the edge, perpendicular to the luma gradient
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * constants.reduce_mul, constants.reduce_min);
+    let inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_direction_min, vec2<f32>(-constants.span_max), vec2<f32>(constants.span_max)) * texel;
+
+    let rgb_a = 0.5 * (sample_at(uv + direction * (1.0 / 3.0 - 0.5)) + sample_at(uv + direction * (2.0 / 3.0 - 0.5)));
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample_at(uv + direction * -0.5) + sample_at(uv + direction * 0.5));
+    let luma_b = luma(rgb_b);
+
+    // Sampling too far along the edge picked up something else, fall back to the shorter blur
+    let color = select(rgb_b, rgb_a, luma_b < luma_min || luma_b > luma_max);
+    out.color = vec4<f32>(color, 1.0);
+
+    return out;
+}
@@ -0,0 +1,54 @@
+// Drawn by render::material::Hologram, a custom material
+#import wormhole::vertex_fetch as Fetch
+#import wormhole::mesh as Mesh
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: Fetch::InstanceInput,
+) -> Mesh::VertexOutput {
+    return Mesh::vertex(vertex_index, instance);
+}
+
+struct Hologram {
+    color: vec4<f32>,
+    texture: u32,
+    line_spacing: f32,
+    line_width: f32,
+}
+
+#ifdef HAS_TEXTURE
+@group(1) @binding(0)
+var samplers: binding_array<sampler>;
+@group(1) @binding(1)
+var textures: binding_array<texture_2d<f32>>;
+@group(1) @binding(3)
+var<storage> texture_samplers: array<u32>;
+#endif
+
+@group(2) @binding(0)
+var<storage> materials: array<Hologram>;
+
+@fragment
+fn fs_main(in: Mesh::VertexOutput) -> Mesh::FragmentOutput {
+    var out: Mesh::FragmentOutput;
+
+    let material = materials[in.material_index];
+
+    var color = material.color.rgb * in.base_color.rgb;
+#ifdef HAS_TEXTURE
+    let sampler_index = texture_samplers[material.texture];
+    color *= textureSample(textures[material.texture], samplers[sampler_index], in.tex_coords).rgb;
+#endif
+
+    // Horizontal stripes in world space, so they line up across meshes
+    let line = step(1.0 - material.line_width, fract(in.position.y / material.line_spacing));
+
+    // Black and fully rough, so all of the color comes from emission
+    out.color_roughness = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    out.normal_metallicity = vec4<f32>(in.world_normal, 0.0);
+    out.position_occlusion = vec4<f32>(in.position, 1.0);
+    out.emissive = vec4<f32>(color * line, 1.0);
+
+    return out;
+}
@@ -15,43 +15,25 @@
 // You should have received a copy of the GNU General Public License
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 use crate::render;
+use crate::shaders;
 
 const ATTRS: &[wgpu::VertexAttribute] = &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
-const SCREEN_VERTEX_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+pub const SCREEN_VERTEX_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
     array_stride: 20 as wgpu::BufferAddress,
     step_mode: wgpu::VertexStepMode::Vertex,
     attributes: ATTRS,
 };
 
 pub fn create_light_object_render_pipeline(
-    composer: &mut naga_oil::compose::Composer,
+    registry: &mut shaders::Registry,
     gpu_state: &render::state::GpuState,
     bind_groups: &render::state::BindGroups,
-) -> Result<wgpu::RenderPipeline, naga_oil::compose::ComposerError> {
-    composer.add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
-        source: include_str!("util.wgsl"),
-        file_path: "util.wgsl",
-        ..Default::default()
-    })?;
-    composer.add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
-        source: include_str!("vertex_fetch.wgsl"),
-        file_path: "vertex_fetch.wgsl",
-        ..Default::default()
-    })?;
-
-    let module = composer.make_naga_module(naga_oil::compose::NagaModuleDescriptor {
-        source: include_str!("light_object.wgsl"),
-        file_path: "light_object.wgsl",
-        ..Default::default()
-    })?;
-    let module = std::borrow::Cow::Owned(module);
-
-    let shader = gpu_state
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("light object render pipeline"),
-            source: wgpu::ShaderSource::Naga(module),
-        });
+) -> Result<wgpu::RenderPipeline, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/light_object.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
 
     let layout = gpu_state
         .device
@@ -78,7 +60,7 @@ pub fn create_light_object_render_pipeline(
                 module: &shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: gpu_state.surface_config.format,
+                    format: render::TextureFormat::HDR.format,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -109,29 +91,25 @@ pub fn create_light_object_render_pipeline(
 }
 
 pub fn create_light_render_pipeline(
-    composer: &mut naga_oil::compose::Composer,
+    registry: &mut shaders::Registry,
     gpu_state: &render::state::GpuState,
     bind_groups: &render::state::BindGroups,
-) -> Result<wgpu::RenderPipeline, naga_oil::compose::ComposerError> {
-    let module = composer.make_naga_module(naga_oil::compose::NagaModuleDescriptor {
-        source: include_str!("light.wgsl"),
-        file_path: "light.wgsl",
-        ..Default::default()
-    })?;
-    let module = std::borrow::Cow::Owned(module);
-
-    let shader = gpu_state
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("light render pipeline"),
-            source: wgpu::ShaderSource::Naga(module),
-        });
+) -> Result<wgpu::RenderPipeline, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/light.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
 
     let layout = gpu_state
         .device
         .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("lighting render pipeline layout"),
-            bind_group_layouts: &[&bind_groups.light_data, &bind_groups.gbuffer],
+            bind_group_layouts: &[
+                &bind_groups.light_data,
+                &bind_groups.gbuffer,
+                &bind_groups.shadow_maps,
+            ],
             push_constant_ranges: &[wgpu::PushConstantRange {
                 stages: wgpu::ShaderStages::FRAGMENT,
                 range: 0..16,
@@ -152,7 +130,7 @@ pub fn create_light_render_pipeline(
                 module: &shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: gpu_state.surface_config.format,
+                    format: render::TextureFormat::HDR.format,
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -175,3 +153,35 @@ pub fn create_light_render_pipeline(
             multiview: None,
         }))
 }
+
+pub fn create_light_culling_pipeline(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+) -> Result<wgpu::ComputePipeline, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/light_cull.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+
+    let layout = gpu_state
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("light culling pipeline layout"),
+            bind_group_layouts: &[&bind_groups.light_culling],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::COMPUTE,
+                range: 0..80,
+            }],
+        });
+
+    Ok(gpu_state
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("light culling pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: "cs_main",
+        }))
+}
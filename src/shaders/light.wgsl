@@ -1,4 +1,7 @@
 // Vertex shader
+#import wormhole::lights as Lights
+#import wormhole::pbr as Pbr
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
@@ -37,19 +40,18 @@ struct Constants {
 
 var<push_constant> constants: Constants;
 
-struct Light {
-    constant: f32,
-    linear: f32,
-    quadratic: f32,
-
-    ambient: vec4<f32>,
-    diffuse: vec4<f32>,
-    specular: vec4<f32>,
-
-    position: vec3<f32>,
-}
 @group(0) @binding(0)
-var<storage> lights: array<Light>;
+var<storage> lights: array<Lights::Light>;
+@group(0) @binding(1)
+var<storage> tile_light_indices: array<u32>;
+@group(0) @binding(2)
+var environment_sampler: sampler;
+@group(0) @binding(3)
+var irradiance_map: texture_cube<f32>;
+@group(0) @binding(4)
+var prefiltered_map: texture_cube<f32>;
+@group(0) @binding(5)
+var brdf_lut: texture_2d<f32>;
 
 @group(1) @binding(0)
 var g_buffer_sampler: sampler;
@@ -62,7 +64,10 @@ var g_position_occlusion: texture_2d<f32>;
 @group(1) @binding(4)
 var g_emissive: texture_2d<f32>;
 
-const PI = 3.14159265359;
+@group(2) @binding(0)
+var shadow_sampler: sampler;
+@group(2) @binding(1)
+var shadow_maps: binding_array<texture_cube<f32>>;
 
 @fragment
 fn fs_main(in: VertexOutput) -> FragmentOutput {
@@ -73,38 +78,32 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
     let position_occlusion = textureSample(g_position_occlusion, g_buffer_sampler, in.tex_coords);
     let emissive = textureSample(g_emissive, g_buffer_sampler, in.tex_coords);
 
-    let n = normalize(normal_metallicity.rgb);
-    let v = normalize(constants.camera.view_pos.rgb - position_occlusion.rgb);
-
-    let f0 = mix(vec3<f32>(0.04), color_roughness.rgb, normal_metallicity.a);
+    var surface: Pbr::Surface;
+    surface.position = position_occlusion.rgb;
+    surface.normal = normalize(normal_metallicity.rgb);
+    surface.view = normalize(constants.camera.view_pos.rgb - position_occlusion.rgb);
+    surface.albedo = color_roughness.rgb;
+    surface.roughness = color_roughness.a;
+    surface.metallic = normal_metallicity.a;
 
     var l_o = vec3<f32>(0.0);
-    for (var i = 0u; i < constants.light_count; i++) {
-        let light = lights[i];
-
-        let l = normalize(light.position - position_occlusion.rgb);
-        let h = normalize(v + l);
-
-        let distance = length(light.position - position_occlusion.rgb);
-        let attenuation = 1.0 / (light.constant + light.linear * distance + light.quadratic * (distance * distance));
-
-        let radiance = light.diffuse.rgb * attenuation;
-
-        let ndf = distributionGGX(n, h, color_roughness.a);
-        let g = geometrySmith(n, v, l, color_roughness.a);
-        let f = fresnelSchlick(max(dot(h, v), 0.0), f0);
-
-        let numerator = ndf * g * f;
-        let denominator = 4.0 * max(dot(n, v), 0.0) * max(dot(n, l), 0.0) + 0.0001;
-        let specular = numerator / denominator;
-
-        let k_s = f;
-        let k_d = (vec3(1.0) - k_s) * 1.0 - normal_metallicity.a;
-
-        let n_dot_l = max(dot(n, l), 0.0);
-        l_o += (k_d * color_roughness.rgb / PI + specular) * radiance * n_dot_l;
+    // Only go over the lights the culling pass found for this tile
+    let tile = vec2<u32>(in.clip_position.xy) / Lights::TILE_SIZE;
+    let tile_count_x = (textureDimensions(g_color_roughness).x + Lights::TILE_SIZE - 1u) / Lights::TILE_SIZE;
+    let tile_offset = (tile.y * tile_count_x + tile.x) * Lights::TILE_STRIDE;
+    let tile_light_count = tile_light_indices[tile_offset];
+
+    for (var i = 0u; i < tile_light_count; i++) {
+        let light = lights[tile_light_indices[tile_offset + 1u + i]];
+        l_o += Pbr::light_contribution(light, surface, shadow_factor(light, surface.position));
     }
-    let ambient = vec3<f32>(0.03) * color_roughness.rgb * position_occlusion.a;
+    // Image based lighting from the environment
+    let reflection = reflect(-surface.view, surface.normal);
+    let max_mip = f32(textureNumLevels(prefiltered_map) - 1u);
+    let irradiance = textureSampleLevel(irradiance_map, environment_sampler, surface.normal, 0.0).rgb;
+    let prefiltered = textureSampleLevel(prefiltered_map, environment_sampler, reflection, surface.roughness * max_mip).rgb;
+    let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(max(dot(surface.normal, surface.view), 0.0), surface.roughness), 0.0).rg;
+    let ambient = Pbr::ambient_contribution(surface, irradiance, prefiltered, brdf) * position_occlusion.a;
 
     var color = ambient + l_o + emissive.rgb;
     // color = color / (color + vec3(1.0));
@@ -115,39 +114,18 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
     return out;
 }
 
-fn fresnelSchlick(cosTheta: f32, f0: vec3<f32>) -> vec3<f32> {
-    return f0 + (1.0 - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
-}
-
-
-fn distributionGGX(n: vec3<f32>, h: vec3<f32>, roughness: f32) -> f32 {
-    let a = roughness * roughness;
-    let a2 = a * a;
-    let n_dot_h = max(dot(n, h), 0.0);
-    let n_dot_h2 = n_dot_h * n_dot_h;
-
-    let num = a2;
-    var denom = (n_dot_h2 * (a2 - 1.0) + 1.0);
-    denom = PI * denom * denom;
-
-    return num / denom;
-}
-
-fn geometrySchlickGGX(n_dot_v: f32, roughness: f32) -> f32 {
-    let r = (roughness + 1.0);
-    let k = (r * r) / 8.0;
+// 0.0 if the position is hidden from the light, 1.0 otherwise
+fn shadow_factor(light: Lights::Light, position: vec3<f32>) -> f32 {
+    if light.shadow_index < 0 {
+        return 1.0;
+    }
 
-    let num = n_dot_v;
-    let denom = n_dot_v * (1.0 - k) + k;
+    let light_to_position = position - light.position;
+    let distance = length(light_to_position);
+    if distance >= light.shadow_far {
+        return 1.0;
+    }
 
-    return num / denom;
+    let closest = textureSampleLevel(shadow_maps[light.shadow_index], shadow_sampler, light_to_position, 0.0).r * light.shadow_far;
+    return select(1.0, 0.0, distance - light.shadow_bias > closest);
 }
-
-fn geometrySmith(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, roughness: f32) -> f32 {
-    let n_dot_v = max(dot(n, v), 0.0);
-    let n_dot_l = max(dot(n, l), 0.0);
-    let ggx2 = geometrySchlickGGX(n_dot_v, roughness);
-    let ggx1 = geometrySchlickGGX(n_dot_l, roughness);
-
-    return ggx1 * ggx2;
-}
\ No newline at end of file
@@ -0,0 +1,97 @@
+#import wormhole::lights as Lights
+
+struct Constants {
+    inv_view_proj: mat4x4<f32>,
+    screen_size: vec2<u32>,
+    light_count: u32,
+}
+var<push_constant> constants: Constants;
+
+@group(0) @binding(0)
+var<storage> lights: array<Lights::Light>;
+@group(0) @binding(1)
+var<storage, read_write> tile_light_indices: array<u32>;
+@group(0) @binding(2)
+var depth_texture: texture_depth_2d;
+
+const TILE_PIXELS = 256u; // TILE_SIZE * TILE_SIZE
+
+var<workgroup> max_depth: atomic<u32>;
+var<workgroup> visible_light_count: atomic<u32>;
+var<workgroup> visible_lights: array<u32, Lights::MAX_LIGHTS_PER_TILE>;
+
+// One workgroup per tile, one invocation per pixel
+@compute @workgroup_size(16, 16, 1)
+fn cs_main(
+    @builtin(global_invocation_id) pixel: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) tile: vec3<u32>,
+    @builtin(num_workgroups) tile_count: vec3<u32>,
+) {
+    if local_index == 0u {
+        atomicStore(&max_depth, 0u);
+        atomicStore(&visible_light_count, 0u);
+    }
+    workgroupBarrier();
+
+    // Depth is never negative, so its bits sort the same way as the float does
+    if all(pixel.xy < constants.screen_size) {
+        let depth = bitcast<u32>(textureLoad(depth_texture, pixel.xy, 0));
+        atomicMax(&max_depth, depth);
+    }
+    workgroupBarrier();
+
+    // The box starts at the near plane instead of the closest opaque surface,
+    // so the transparent pass can use the same lists for surfaces in front of it.
+    // Anything behind the furthest opaque surface is hidden by the depth test either way
+    let tile_min_depth = 0.0;
+    let tile_max_depth = bitcast<f32>(atomicLoad(&max_depth));
+
+    // Bound the part of the view frustum covered by this tile with a world space box
+    let screen_size = vec2<f32>(constants.screen_size);
+    let pixel_min = vec2<f32>(tile.xy * Lights::TILE_SIZE);
+    let pixel_max = min(vec2<f32>((tile.xy + 1u) * Lights::TILE_SIZE), screen_size);
+    let ndc_min = vec2<f32>(pixel_min.x / screen_size.x * 2.0 - 1.0, 1.0 - pixel_max.y / screen_size.y * 2.0);
+    let ndc_max = vec2<f32>(pixel_max.x / screen_size.x * 2.0 - 1.0, 1.0 - pixel_min.y / screen_size.y * 2.0);
+
+    var box_min = vec3<f32>(3.40282347e+38);
+    var box_max = vec3<f32>(-3.40282347e+38);
+    for (var corner = 0u; corner < 8u; corner++) {
+        let ndc = vec3<f32>(
+            select(ndc_min.x, ndc_max.x, (corner & 1u) != 0u),
+            select(ndc_min.y, ndc_max.y, (corner & 2u) != 0u),
+            select(tile_min_depth, tile_max_depth, (corner & 4u) != 0u),
+        );
+        let world = constants.inv_view_proj * vec4<f32>(ndc, 1.0);
+        let position = world.xyz / world.w;
+        box_min = min(box_min, position);
+        box_max = max(box_max, position);
+    }
+
+    for (var i = local_index; i < constants.light_count; i += TILE_PIXELS) {
+        let light = lights[i];
+
+        var visible = light.light_type == Lights::LIGHT_DIRECTIONAL;
+        if !visible {
+            let closest = clamp(light.position, box_min, box_max) - light.position;
+            visible = dot(closest, closest) <= light.range * light.range;
+        }
+
+        if visible {
+            let slot = atomicAdd(&visible_light_count, 1u);
+            if slot < Lights::MAX_LIGHTS_PER_TILE {
+                visible_lights[slot] = i;
+            }
+        }
+    }
+    workgroupBarrier();
+
+    let light_count = min(atomicLoad(&visible_light_count), Lights::MAX_LIGHTS_PER_TILE);
+    let tile_offset = (tile.y * tile_count.x + tile.x) * Lights::TILE_STRIDE;
+    for (var i = local_index; i < light_count; i += TILE_PIXELS) {
+        tile_light_indices[tile_offset + 1u + i] = visible_lights[i];
+    }
+    if local_index == 0u {
+        tile_light_indices[tile_offset] = light_count;
+    }
+}
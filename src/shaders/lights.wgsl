@@ -0,0 +1,33 @@
+#define_import_path wormhole::lights
+
+// Must match components::light::PreparedLight
+struct Light {
+    constant: f32,
+    linear: f32,
+    quadratic: f32,
+
+    diffuse: vec4<f32>,
+    specular: vec4<f32>,
+
+    position: vec3<f32>,
+    shadow_index: i32,
+    shadow_far: f32,
+    shadow_bias: f32,
+
+    direction: vec3<f32>,
+    light_type: u32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    // Distance past which the light contributes nothing
+    range: f32,
+}
+
+const LIGHT_POINT       = 1u;
+const LIGHT_DIRECTIONAL = 2u;
+const LIGHT_SPOT        = 3u;
+
+// Must match render::buffer::light_tiles
+const TILE_SIZE           = 16u;
+const MAX_LIGHTS_PER_TILE = 255u;
+// Every tile stores its light count followed by the light indices
+const TILE_STRIDE         = 256u;
@@ -0,0 +1,77 @@
+#define_import_path wormhole::mesh
+
+#import wormhole::util as Util
+#import wormhole::vertex_fetch as Fetch
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) position: vec3<f32>,
+
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+
+    @location(5) base_color: vec4<f32>,
+
+    @location(6) @interpolate(flat) material_index: u32,
+};
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+var<push_constant> camera: Camera;
+
+struct Transform {
+    obj_proj: mat4x4<f32>,
+    normal_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<storage> transforms: array<Transform>;
+
+// Shared by every shader drawing meshes into the gbuffer, so they can be placed the same way
+fn vertex(vertex_index: u32, instance: Fetch::InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let transform = transforms[instance.transform_index];
+
+    let model_position = Fetch::read_vertex_position(vertex_index, instance.position_offset);
+    let world_position = transform.obj_proj * vec4<f32>(model_position, 1.0);
+
+    let tex_coords = Fetch::read_vertex_tex_coords(vertex_index, instance.tex_coord_offset);
+    out.tex_coords = tex_coords;
+
+    out.position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+
+    let normal_matrix = mat3x3<f32>(transform.normal_proj[0].xyz, transform.normal_proj[1].xyz, transform.normal_proj[2].xyz);
+
+    let model_normal = Fetch::read_vertex_normal(vertex_index, instance.normal_offset);
+    let model_tangent = Fetch::read_vertex_tangent(vertex_index, instance.tangent_offset);
+    let model_bitangent = cross(model_normal, model_tangent.xyz) * model_tangent.w;
+
+    out.world_normal = normalize(normal_matrix * model_normal);
+    out.world_tangent = normalize(normal_matrix * model_tangent.xyz);
+    out.world_bitangent = normalize(normal_matrix * model_bitangent);
+
+    out.base_color = select(
+        vec4<f32>(1.0),
+        Fetch::read_vertex_color(vertex_index, instance.color_offset),
+        Util::extract_flag(instance.format_flags, Fetch::HAS_VTX_COLOR)
+    );
+
+    out.material_index = instance.material_index;
+
+    return out;
+}
+
+// Written to the gbuffer by fragment shaders
+struct FragmentOutput {
+    @location(0) color_roughness: vec4<f32>,
+    @location(1) normal_metallicity: vec4<f32>,
+    @location(2) position_occlusion: vec4<f32>,
+    @location(3) emissive: vec4<f32>,
+}
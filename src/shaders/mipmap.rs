@@ -0,0 +1,84 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+use crate::shaders;
+
+/// One pipeline per format in [`render::mipmap::MIPMAP_FORMATS`], in the same order.
+pub fn create_mipmap_pipelines(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+) -> Result<Vec<wgpu::RenderPipeline>, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/mipmap.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+
+    let layout = gpu_state
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("mipmap render pipeline layout"),
+            bind_group_layouts: &[&bind_groups.post_process],
+            push_constant_ranges: &[],
+        });
+
+    let pipelines = render::mipmap::MIPMAP_FORMATS
+        .iter()
+        .map(|&format| {
+            gpu_state
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("mipmap render pipeline"),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend: Some(wgpu::BlendState::REPLACE),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: None,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState {
+                        count: 1,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                })
+        })
+        .collect();
+
+    Ok(pipelines)
+}
@@ -0,0 +1,36 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// Textures are uploaded before any scene buffers exist, so this draws a single triangle covering the screen
+// without a vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let tex_coords = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    out.clip_position = vec4<f32>(tex_coords * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.tex_coords = tex_coords;
+
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var source_sampler: sampler;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+// The previous mip level is twice the size, so a bilinear sample averages 4 of its pixels
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    out.color = textureSample(source, source_sampler, in.tex_coords);
+
+    return out;
+}
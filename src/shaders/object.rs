@@ -15,60 +15,124 @@
 // You should have received a copy of the GNU General Public License
 // along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
 use crate::render;
+use crate::shaders;
+
+use naga_oil::compose::ShaderDefValue;
+
+/// Variants of the object shader, compiled with different shader defs so unused features cost nothing.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Permutation {
+    pub normal_map: bool,
+}
+
+impl Permutation {
+    pub fn from_material(material: &render::Material) -> Self {
+        Self {
+            normal_map: material.normal_texture.is_some(),
+        }
+    }
+
+    pub fn shader_defs(self) -> shaders::ShaderDefs {
+        let mut shader_defs = shaders::ShaderDefs::new();
+        if self.normal_map {
+            shader_defs.insert("HAS_NORMAL_MAP".to_string(), ShaderDefValue::Bool(true));
+        }
+        shader_defs
+    }
+
+    pub fn pipeline_key(self) -> shaders::PipelineKey {
+        shaders::PipelineKey {
+            name: "object".into(),
+            shader_defs: self.shader_defs(),
+        }
+    }
+
+    /// Every permutation, created up front so broken shaders are caught early.
+    pub fn all() -> [Self; 2] {
+        [Self { normal_map: false }, Self { normal_map: true }]
+    }
+}
+
+pub fn prepare_render_pipeline(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+    permutation: Permutation,
+) -> Result<(), shaders::registry::Error> {
+    registry.prepare_pipeline(&permutation.pipeline_key(), |registry| {
+        create_render_pipeline(registry, gpu_state, bind_groups, &permutation.shader_defs())
+    })
+}
 
 pub fn create_render_pipeline(
-    composer: &mut naga_oil::compose::Composer,
+    registry: &mut shaders::Registry,
     gpu_state: &render::state::GpuState,
     bind_groups: &render::state::BindGroups,
-) -> Result<wgpu::RenderPipeline, naga_oil::compose::ComposerError> {
-    composer.add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
-        source: include_str!("../shaders/util.wgsl"),
-        file_path: "shaders/util.wgsl",
-        ..Default::default()
-    })?;
-    composer.add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
-        source: include_str!("../shaders/vertex_fetch.wgsl"),
-        file_path: "shaders/vertex_fetch.wgsl",
-        ..Default::default()
-    })?;
-
-    let module = composer.make_naga_module(naga_oil::compose::NagaModuleDescriptor {
-        source: include_str!("../shaders/object.wgsl"),
-        file_path: "shaders/object.wgsl",
-        ..Default::default()
-    })?;
-    let module = std::borrow::Cow::Owned(module);
-
-    let shader = gpu_state
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("object render pipeline"),
-            source: wgpu::ShaderSource::Naga(module),
-        });
+    shader_defs: &shaders::ShaderDefs,
+) -> Result<wgpu::RenderPipeline, shaders::registry::Error> {
+    let shader =
+        registry.create_shader_module(&gpu_state.device, "shaders/object.wgsl", shader_defs)?;
+
+    Ok(create_gbuffer_pipeline(
+        gpu_state,
+        "object render pipeline",
+        &shader,
+        &[&bind_groups.object_data, &bind_groups.materials],
+    ))
+}
+
+/// Creates the pipeline for a [`render::CustomMaterial`] from its shader in the registry.
+/// Its material data is bound as an extra bind group after the object data and built in materials.
+pub fn create_custom_material_pipeline(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+    key: &shaders::PipelineKey,
+) -> Result<wgpu::RenderPipeline, shaders::registry::Error> {
+    let shader = registry.create_shader_module(&gpu_state.device, &key.name, &key.shader_defs)?;
+
+    Ok(create_gbuffer_pipeline(
+        gpu_state,
+        &key.name,
+        &shader,
+        &[
+            &bind_groups.object_data,
+            &bind_groups.materials,
+            &bind_groups.custom_material,
+        ],
+    ))
+}
 
+// Draws meshes into the gbuffer, with the camera's view projection as a push constant
+fn create_gbuffer_pipeline(
+    gpu_state: &render::state::GpuState,
+    label: &str,
+    shader: &wgpu::ShaderModule,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+) -> wgpu::RenderPipeline {
     let layout = gpu_state
         .device
         .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("object render pipeline layout"),
-            bind_group_layouts: &[&bind_groups.object_data, &bind_groups.materials],
+            label: Some(&format!("{label} layout")),
+            bind_group_layouts,
             push_constant_ranges: &[wgpu::PushConstantRange {
                 stages: wgpu::ShaderStages::VERTEX,
                 range: 0..64,
             }],
         });
 
-    Ok(gpu_state
+    gpu_state
         .device
         .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("object render pipeline"),
+            label: Some(label),
             layout: Some(&layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vs_main",
                 buffers: &[render::MeshInstance::desc()],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: "fs_main",
                 targets: &[
                     Some(wgpu::ColorTargetState {
@@ -115,5 +179,77 @@ pub fn create_render_pipeline(
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
+        })
+}
+
+pub fn create_transparent_render_pipeline(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+) -> Result<wgpu::RenderPipeline, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/transparent.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+
+    let layout = gpu_state
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("transparent render pipeline layout"),
+            bind_group_layouts: &[
+                &bind_groups.object_data,
+                &bind_groups.materials,
+                &bind_groups.light_data,
+                &bind_groups.shadow_maps,
+            ],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                range: 0..80,
+            }],
+        });
+
+    Ok(gpu_state
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("transparent render pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[render::MeshInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render::TextureFormat::HDR.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            // Tested against the opaque geometry, but not written so overlapping surfaces all blend
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
         }))
 }
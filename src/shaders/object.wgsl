@@ -1,73 +1,14 @@
 // Vertex shader
 #import wormhole::util as Util
 #import wormhole::vertex_fetch as Fetch
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-
-    @location(0) tex_coords: vec2<f32>,
-    @location(1) position: vec3<f32>,
-
-    @location(2) world_normal: vec3<f32>,
-    @location(3) world_tangent: vec3<f32>,
-    @location(4) world_bitangent: vec3<f32>,
-
-    @location(5) base_color: vec4<f32>,
-
-    @location(6) @interpolate(flat) material_index: u32,
-};
-
-struct Camera {
-    view_proj: mat4x4<f32>,
-}
-
-var<push_constant> camera: Camera;
-
-struct Transform {
-    obj_proj: mat4x4<f32>,
-    normal_proj: mat4x4<f32>,
-}
-
-@group(0) @binding(0)
-var<storage> transforms: array<Transform>;
+#import wormhole::mesh as Mesh
 
 @vertex
 fn vs_main(
     @builtin(vertex_index) vertex_index: u32,
     instance: Fetch::InstanceInput,
-) -> VertexOutput {
-    var out: VertexOutput;
-
-    let transform = transforms[instance.transform_index];
-
-    let model_position = Fetch::read_vertex_position(vertex_index, instance.position_offset);
-    let world_position = transform.obj_proj * vec4<f32>(model_position, 1.0);
-
-    let tex_coords = Fetch::read_vertex_tex_coords(vertex_index, instance.tex_coord_offset);
-    out.tex_coords = tex_coords;
-
-    out.position = world_position.xyz;
-    out.clip_position = camera.view_proj * world_position;
-
-    let normal_matrix = mat3x3<f32>(transform.normal_proj[0].xyz, transform.normal_proj[1].xyz, transform.normal_proj[2].xyz);
-
-    let model_normal = Fetch::read_vertex_normal(vertex_index, instance.normal_offset);
-    let model_tangent = Fetch::read_vertex_tangent(vertex_index, instance.tangent_offset);
-    let model_bitangent = cross(model_normal, model_tangent.xyz) * model_tangent.w;
-
-    out.world_normal = normalize(normal_matrix * model_normal);
-    out.world_tangent = normalize(normal_matrix * model_tangent.xyz);
-    out.world_bitangent = normalize(normal_matrix * model_bitangent);
-
-    out.base_color = select(
-        vec4<f32>(1.0),
-        Fetch::read_vertex_color(vertex_index, instance.color_offset),
-        Util::extract_flag(instance.format_flags, Fetch::HAS_VTX_COLOR)
-    );
-
-    out.material_index = instance.material_index;
-
-    return out;
+) -> Mesh::VertexOutput {
+    return Mesh::vertex(vertex_index, instance);
 }
 
 // Fragment shader
@@ -98,30 +39,31 @@ const HAS_NORMAL_MAP                 = 0x0010u;
 const HAS_ALPHA_CUTOFF               = 0x0020u;
 
 @group(1) @binding(0)
-var material_sampler: sampler;
+var samplers: binding_array<sampler>;
 @group(1) @binding(1)
 var textures: binding_array<texture_2d<f32>>;
 @group(1) @binding(2)
 var<storage> materials: array<Material>;
+@group(1) @binding(3)
+var<storage> texture_samplers: array<u32>;
 
-struct FragmentOutput {
-    @location(0) color_roughness: vec4<f32>,
-    @location(1) normal_metallicity: vec4<f32>,
-    @location(2) position_occlusion: vec4<f32>,
-    @location(3) emissive: vec4<f32>,
+fn sample_material_texture(index: u32, tex_coords: vec2<f32>) -> vec4<f32> {
+    return textureSample(textures[index], samplers[texture_samplers[index]], tex_coords);
 }
 
 @fragment
-fn fs_main(in: VertexOutput) -> FragmentOutput {
-    var out: FragmentOutput;
+fn fs_main(in: Mesh::VertexOutput) -> Mesh::FragmentOutput {
+    var out: Mesh::FragmentOutput;
 
     let material = materials[in.material_index];
 
-    let base_color_texture = textureSample(textures[material.base_color_texture], material_sampler, in.tex_coords);
-    let normal_map_texture = textureSample(textures[material.normal_texture], material_sampler, in.tex_coords);
-    let metallic_roughness_texture = textureSample(textures[material.metallic_roughness_texture], material_sampler, in.tex_coords);
-    let emissive_texture = textureSample(textures[material.emissive_texture], material_sampler, in.tex_coords);
-    let occlusion_texture = textureSample(textures[material.occlusion_texture], material_sampler, in.tex_coords);
+    let base_color_texture = sample_material_texture(material.base_color_texture, in.tex_coords);
+#ifdef HAS_NORMAL_MAP
+    let normal_map_texture = sample_material_texture(material.normal_texture, in.tex_coords);
+#endif
+    let metallic_roughness_texture = sample_material_texture(material.metallic_roughness_texture, in.tex_coords);
+    let emissive_texture = sample_material_texture(material.emissive_texture, in.tex_coords);
+    let occlusion_texture = sample_material_texture(material.occlusion_texture, in.tex_coords);
 
     if Util::extract_flag(material.flags, HAS_ALPHA_CUTOFF) && base_color_texture.a < material.alpha_cutoff {
         discard;
@@ -133,6 +75,7 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
     }
 
     var normal = in.world_normal;
+#ifdef HAS_NORMAL_MAP
     if Util::extract_flag(material.flags, HAS_NORMAL_MAP) {
         let tangent_matrix = mat3x3<f32>(
             in.world_tangent,
@@ -142,6 +85,7 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
         let normal_map = normal_map_texture.rgb * 2.0 - 1.0;
         normal = normalize(tangent_matrix * normal_map);
     }
+#endif
 
     var metallicity = material.metallic;
     var roughness = material.roughness;
@@ -155,7 +99,8 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
         emissive = emissive_texture.xyz;
     }
 
-    var occlusion = 0.0;
+    // Unoccluded unless the material says otherwise
+    var occlusion = 1.0;
     if Util::extract_flag(material.flags, HAS_OCCLUSION_TEXTURE) {
         occlusion = occlusion_texture.r;
     }
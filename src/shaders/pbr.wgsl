@@ -0,0 +1,109 @@
+#define_import_path wormhole::pbr
+#import wormhole::lights as Lights
+
+const PI = 3.14159265359;
+
+struct Surface {
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    // Direction from the surface towards the camera
+    view: vec3<f32>,
+    albedo: vec3<f32>,
+    roughness: f32,
+    metallic: f32,
+}
+
+// Light reflected towards the camera by a single light, `shadow` scales the incoming radiance
+fn light_contribution(light: Lights::Light, surface: Surface, shadow: f32) -> vec3<f32> {
+    let n = surface.normal;
+    let v = surface.view;
+
+    let f0 = mix(vec3<f32>(0.04), surface.albedo, surface.metallic);
+
+    var l = normalize(light.position - surface.position);
+    var attenuation = 1.0;
+    if light.light_type == Lights::LIGHT_DIRECTIONAL {
+        // The sun is far enough away to not fall off
+        l = -normalize(light.direction);
+    } else {
+        let distance = length(light.position - surface.position);
+        attenuation = 1.0 / (light.constant + light.linear * distance + light.quadratic * (distance * distance));
+    }
+    if light.light_type == Lights::LIGHT_SPOT {
+        let theta = dot(-l, normalize(light.direction));
+        attenuation *= smoothstep(light.outer_cone_cos, light.inner_cone_cos, theta);
+    }
+
+    let h = normalize(v + l);
+
+    let radiance = light.diffuse.rgb * attenuation * shadow;
+
+    let ndf = distributionGGX(n, h, surface.roughness);
+    let g = geometrySmith(n, v, l, surface.roughness);
+    let f = fresnelSchlick(max(dot(h, v), 0.0), f0);
+
+    let numerator = ndf * g * f;
+    let denominator = 4.0 * max(dot(n, v), 0.0) * max(dot(n, l), 0.0) + 0.0001;
+    let specular = numerator / denominator;
+
+    let k_s = f;
+    let k_d = (vec3(1.0) - k_s) * 1.0 - surface.metallic;
+
+    let n_dot_l = max(dot(n, l), 0.0);
+    return (k_d * surface.albedo / PI + specular) * radiance * n_dot_l;
+}
+
+// Light reflected towards the camera from the environment, given samples of the environment maps:
+// `irradiance` around the normal, `prefiltered` along the reflection at the surface roughness, and the `brdf` lookup
+fn ambient_contribution(surface: Surface, irradiance: vec3<f32>, prefiltered: vec3<f32>, brdf: vec2<f32>) -> vec3<f32> {
+    let f0 = mix(vec3<f32>(0.04), surface.albedo, surface.metallic);
+    let n_dot_v = max(dot(surface.normal, surface.view), 0.0);
+
+    let f = fresnelSchlickRoughness(n_dot_v, f0, surface.roughness);
+    let k_d = (vec3(1.0) - f) * (1.0 - surface.metallic);
+
+    let diffuse = irradiance * surface.albedo;
+    let specular = prefiltered * (f * brdf.x + brdf.y);
+
+    return k_d * diffuse + specular;
+}
+
+fn fresnelSchlick(cosTheta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+fn fresnelSchlickRoughness(cosTheta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+fn distributionGGX(n: vec3<f32>, h: vec3<f32>, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let n_dot_h = max(dot(n, h), 0.0);
+    let n_dot_h2 = n_dot_h * n_dot_h;
+
+    let num = a2;
+    var denom = (n_dot_h2 * (a2 - 1.0) + 1.0);
+    denom = PI * denom * denom;
+
+    return num / denom;
+}
+
+fn geometrySchlickGGX(n_dot_v: f32, roughness: f32) -> f32 {
+    let r = (roughness + 1.0);
+    let k = (r * r) / 8.0;
+
+    let num = n_dot_v;
+    let denom = n_dot_v * (1.0 - k) + k;
+
+    return num / denom;
+}
+
+fn geometrySmith(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, roughness: f32) -> f32 {
+    let n_dot_v = max(dot(n, v), 0.0);
+    let n_dot_l = max(dot(n, l), 0.0);
+    let ggx2 = geometrySchlickGGX(n_dot_v, roughness);
+    let ggx1 = geometrySchlickGGX(n_dot_l, roughness);
+
+    return ggx1 * ggx2;
+}
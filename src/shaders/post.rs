@@ -0,0 +1,202 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::render;
+use crate::shaders;
+
+// Every post processing pass draws a screen quad with a fragment shader and push constants
+struct FullscreenPipeline<'a> {
+    label: &'a str,
+    shader: &'a wgpu::ShaderModule,
+    entry_point: &'a str,
+    bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
+    push_constant_size: u32,
+    format: wgpu::TextureFormat,
+    blend: wgpu::BlendState,
+}
+
+fn create_fullscreen_pipeline(
+    gpu_state: &render::state::GpuState,
+    desc: FullscreenPipeline<'_>,
+) -> wgpu::RenderPipeline {
+    let layout = gpu_state
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(desc.label),
+            bind_group_layouts: desc.bind_group_layouts,
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::FRAGMENT,
+                range: 0..desc.push_constant_size,
+            }],
+        });
+
+    gpu_state
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(desc.label),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: desc.shader,
+                entry_point: "vs_main",
+                buffers: &[super::light::SCREEN_VERTEX_DESC],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: desc.shader,
+                entry_point: desc.entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: desc.format,
+                    blend: Some(desc.blend),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+}
+
+pub struct BloomPipelines {
+    pub downsample: wgpu::RenderPipeline,
+    pub blur: wgpu::RenderPipeline,
+    pub composite: wgpu::RenderPipeline,
+}
+
+pub fn create_bloom_pipelines(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+) -> Result<BloomPipelines, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/bloom.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+
+    let downsample = create_fullscreen_pipeline(
+        gpu_state,
+        FullscreenPipeline {
+            label: "bloom downsample render pipeline",
+            shader: &shader,
+            entry_point: "fs_downsample",
+            bind_group_layouts: &[&bind_groups.post_process],
+            push_constant_size: 16,
+            format: render::TextureFormat::HDR.format,
+            blend: wgpu::BlendState::REPLACE,
+        },
+    );
+    let blur = create_fullscreen_pipeline(
+        gpu_state,
+        FullscreenPipeline {
+            label: "bloom blur render pipeline",
+            shader: &shader,
+            entry_point: "fs_blur",
+            bind_group_layouts: &[&bind_groups.post_process],
+            push_constant_size: 16,
+            format: render::TextureFormat::HDR.format,
+            blend: wgpu::BlendState::REPLACE,
+        },
+    );
+    // Added on top of the lit image
+    let composite = create_fullscreen_pipeline(
+        gpu_state,
+        FullscreenPipeline {
+            label: "bloom composite render pipeline",
+            shader: &shader,
+            entry_point: "fs_composite",
+            bind_group_layouts: &[&bind_groups.post_process],
+            push_constant_size: 16,
+            format: render::TextureFormat::HDR.format,
+            blend: wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::REPLACE,
+            },
+        },
+    );
+
+    Ok(BloomPipelines {
+        downsample,
+        blur,
+        composite,
+    })
+}
+
+pub fn create_fxaa_pipeline(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+) -> Result<wgpu::RenderPipeline, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/fxaa.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+
+    Ok(create_fullscreen_pipeline(
+        gpu_state,
+        FullscreenPipeline {
+            label: "fxaa render pipeline",
+            shader: &shader,
+            entry_point: "fs_main",
+            bind_group_layouts: &[&bind_groups.post_process],
+            push_constant_size: 24,
+            format: gpu_state.surface_config.format,
+            blend: wgpu::BlendState::REPLACE,
+        },
+    ))
+}
+
+pub fn create_color_grade_pipeline(
+    registry: &mut shaders::Registry,
+    gpu_state: &render::state::GpuState,
+    bind_groups: &render::state::BindGroups,
+) -> Result<wgpu::RenderPipeline, shaders::registry::Error> {
+    let shader = registry.create_shader_module(
+        &gpu_state.device,
+        "shaders/color_grade.wgsl",
+        &shaders::ShaderDefs::new(),
+    )?;
+
+    Ok(create_fullscreen_pipeline(
+        gpu_state,
+        FullscreenPipeline {
+            label: "color grade render pipeline",
+            shader: &shader,
+            entry_point: "fs_main",
+            bind_group_layouts: &[&bind_groups.post_process, &bind_groups.color_grading_lut],
+            push_constant_size: 8,
+            format: gpu_state.surface_config.format,
+            blend: wgpu::BlendState::REPLACE,
+        },
+    ))
+}
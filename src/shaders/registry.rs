@@ -0,0 +1,476 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::shaders;
+
+use indexmap::IndexMap;
+use naga_oil::compose::{Composer, ShaderDefValue};
+
+/// Shader defs a shader is compiled with, sorted so they can be used as part of a [`PipelineKey`].
+pub type ShaderDefs = BTreeMap<String, ShaderDefValue>;
+
+/// Identifies one permutation of a pipeline.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PipelineKey {
+    pub name: std::borrow::Cow<'static, str>,
+    pub shader_defs: ShaderDefs,
+}
+
+// Composable modules that every shader can import, in the order they need to be added
+const BUILTIN_MODULES: &[(&str, &str)] = &[
+    ("util.wgsl", include_str!("util.wgsl")),
+    ("vertex_fetch.wgsl", include_str!("vertex_fetch.wgsl")),
+    ("mesh.wgsl", include_str!("mesh.wgsl")),
+    ("lights.wgsl", include_str!("lights.wgsl")),
+    ("pbr.wgsl", include_str!("pbr.wgsl")),
+    ("fullscreen.wgsl", include_str!("fullscreen.wgsl")),
+    ("cubemap.wgsl", include_str!("cubemap.wgsl")),
+];
+
+// Shaders the built in pipelines and materials are created from
+const BUILTIN_SHADERS: &[(&str, &str)] = &[
+    ("object.wgsl", include_str!("object.wgsl")),
+    ("transparent.wgsl", include_str!("transparent.wgsl")),
+    ("light.wgsl", include_str!("light.wgsl")),
+    ("light_object.wgsl", include_str!("light_object.wgsl")),
+    ("light_cull.wgsl", include_str!("light_cull.wgsl")),
+    ("shadow.wgsl", include_str!("shadow.wgsl")),
+    ("skybox.wgsl", include_str!("skybox.wgsl")),
+    ("mipmap.wgsl", include_str!("mipmap.wgsl")),
+    ("tonemap.wgsl", include_str!("tonemap.wgsl")),
+    ("exposure.wgsl", include_str!("exposure.wgsl")),
+    ("bloom.wgsl", include_str!("bloom.wgsl")),
+    ("fxaa.wgsl", include_str!("fxaa.wgsl")),
+    ("color_grade.wgsl", include_str!("color_grade.wgsl")),
+    (
+        "equirect_to_cube.wgsl",
+        include_str!("equirect_to_cube.wgsl"),
+    ),
+    ("environment.wgsl", include_str!("environment.wgsl")),
+    ("hologram.wgsl", include_str!("hologram.wgsl")),
+];
+
+/// Keeps track of shader sources and the pipelines created from them.
+///
+/// Sources are either composable modules (with a `#define_import_path`) that other shaders can `#import`,
+/// or shaders that are compiled into pipelines with a set of [`ShaderDefs`].
+/// Every permutation of a pipeline is created once and cached by its [`PipelineKey`],
+/// until a source it was composed from is replaced.
+#[derive(Debug)]
+pub struct Registry {
+    composer: Composer,
+    // Every source by file path, in the order they were added
+    sources: IndexMap<String, Source>,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    // The file paths of every source a cached pipeline was composed from
+    pipeline_sources: HashMap<PipelineKey, HashSet<String>>,
+    // Sources used by create_shader_module since the last pipeline was prepared
+    used_sources: HashSet<String>,
+    // Pipelines that failed to be created, so the error is only reported once
+    failed: HashSet<PipelineKey>,
+}
+
+#[derive(Debug, Clone)]
+struct Source {
+    source: String,
+    // Where the source is reloaded from
+    path: Option<camino::Utf8PathBuf>,
+    composable: bool,
+    // Built in sources are only reloaded from disk if shaders::uses_source_dir
+    builtin: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(camino::Utf8PathBuf, std::io::Error),
+    Compose(String),
+    MissingShader(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "failed to read {path}: {err}"),
+            Error::Compose(err) => write!(f, "{err}"),
+            Error::MissingShader(file_path) => write!(f, "no shader registered as {file_path}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A composer with every capability the renderer requires.
+fn create_composer() -> Composer {
+    Composer::default().with_capabilities(
+        wgpu::naga::valid::Capabilities::PUSH_CONSTANT
+            | wgpu::naga::valid::Capabilities::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
+            | wgpu::naga::valid::Capabilities::SAMPLER_NON_UNIFORM_INDEXING,
+    )
+}
+
+impl Registry {
+    /// Creates a registry with the built in modules and shaders.
+    pub fn new() -> Result<Self, Error> {
+        let mut registry = Self::empty();
+
+        let builtin_path =
+            |file_name: &str| camino::Utf8Path::new(shaders::SOURCE_DIR).join(file_name);
+        for &(file_name, embedded) in BUILTIN_MODULES {
+            let source = shaders::source(file_name, embedded).into_owned();
+            registry.insert(
+                format!("shaders/{file_name}"),
+                Source {
+                    source,
+                    path: Some(builtin_path(file_name)),
+                    composable: true,
+                    builtin: true,
+                },
+            )?;
+        }
+        for &(file_name, embedded) in BUILTIN_SHADERS {
+            let source = shaders::source(file_name, embedded).into_owned();
+            registry.insert(
+                format!("shaders/{file_name}"),
+                Source {
+                    source,
+                    path: Some(builtin_path(file_name)),
+                    composable: false,
+                    builtin: true,
+                },
+            )?;
+        }
+
+        Ok(registry)
+    }
+
+    fn empty() -> Self {
+        Self {
+            composer: create_composer(),
+            sources: IndexMap::new(),
+            pipelines: HashMap::new(),
+            pipeline_sources: HashMap::new(),
+            used_sources: HashSet::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    /// Adds a composable module, replacing any module with the same file path.
+    /// Every module it imports must already be added.
+    ///
+    /// Object and custom material pipelines importing a replaced module are recreated the next frame,
+    /// the other built in pipelines only by [`crate::render::State::reload_pipelines`].
+    pub fn add_module(&mut self, file_path: &str, source: &str) -> Result<(), Error> {
+        self.insert(
+            file_path.to_string(),
+            Source {
+                source: source.to_string(),
+                path: None,
+                composable: true,
+                builtin: false,
+            },
+        )
+    }
+
+    /// Adds a shader that pipelines can be created from, replacing any shader with the same file path.
+    /// It has no file to be reloaded from, use [`Self::load_file`] for shaders that should be hot reloaded.
+    pub fn add_shader(&mut self, file_path: &str, source: &str) {
+        // Can't fail, shaders are only composed once a pipeline is created
+        let _ = self.insert(
+            file_path.to_string(),
+            Source {
+                source: source.to_string(),
+                path: None,
+                composable: false,
+                builtin: false,
+            },
+        );
+    }
+
+    /// Adds a `.wgsl` file, using its path as the file path, and reads it again whenever the registry is reloaded.
+    ///
+    /// A file with a `#define_import_path` is added as a composable module (every module it imports must already be added),
+    /// and any other file is added as a shader.
+    pub fn load_file(&mut self, path: impl AsRef<camino::Utf8Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).map_err(|err| Error::Io(path.to_path_buf(), err))?;
+        let (name, _, _) = naga_oil::compose::get_preprocessor_data(&source);
+
+        self.insert(
+            path.to_string(),
+            Source {
+                source,
+                path: Some(path.to_path_buf()),
+                composable: name.is_some(),
+                builtin: false,
+            },
+        )
+    }
+
+    /// Adds every `.wgsl` file in `dir`, using its path as the file path.
+    ///
+    /// Files with a `#define_import_path` are added as composable modules (in an order where imports come first),
+    /// and every other file is added as a shader.
+    pub fn load_dir(&mut self, dir: impl AsRef<camino::Utf8Path>) -> Result<(), Error> {
+        let dir = dir.as_ref();
+
+        let mut modules = Vec::new();
+        let mut shaders = Vec::new();
+        for entry in dir
+            .read_dir_utf8()
+            .map_err(|err| Error::Io(dir.to_path_buf(), err))?
+        {
+            let path = entry
+                .map_err(|err| Error::Io(dir.to_path_buf(), err))?
+                .into_path();
+            if path.extension() != Some("wgsl") {
+                continue;
+            }
+
+            let source =
+                std::fs::read_to_string(&path).map_err(|err| Error::Io(path.clone(), err))?;
+            let (name, imports, _) = naga_oil::compose::get_preprocessor_data(&source);
+            let imports = imports.into_iter().map(|i| i.import).collect::<Vec<_>>();
+            match name {
+                Some(name) => modules.push((name, imports, path, source)),
+                None => shaders.push((path, source)),
+            }
+        }
+
+        // Add modules once everything they import from this directory is added
+        while !modules.is_empty() {
+            let ready = modules.iter().position(|(_, imports, _, _)| {
+                imports
+                    .iter()
+                    .all(|import| !modules.iter().any(|(name, ..)| name == import))
+            });
+            // Import cycle, let naga_oil report it
+            let (_, _, path, source) = modules.remove(ready.unwrap_or_default());
+
+            self.insert(
+                path.to_string(),
+                Source {
+                    source,
+                    path: Some(path),
+                    composable: true,
+                    builtin: false,
+                },
+            )?;
+        }
+
+        for (path, source) in shaders {
+            self.insert(
+                path.to_string(),
+                Source {
+                    source,
+                    path: Some(path),
+                    composable: false,
+                    builtin: false,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, file_path: String, source: Source) -> Result<(), Error> {
+        if source.composable {
+            self.add_composable_module(&file_path, &source.source)?;
+        }
+        self.sources.insert(file_path.clone(), source);
+
+        // naga_oil removes every module that imports a replaced module, so add them back (imports first)
+        let removed_modules = self
+            .sources
+            .iter()
+            .filter(|(_, source)| source.composable)
+            .filter(|(_, source)| {
+                naga_oil::compose::get_preprocessor_data(&source.source)
+                    .0
+                    .is_some_and(|name| !self.composer.contains_module(&name))
+            })
+            .map(|(file_path, source)| (file_path.clone(), source.source.clone()))
+            .collect::<Vec<_>>();
+        for (file_path, source) in removed_modules {
+            self.add_composable_module(&file_path, &source)?;
+        }
+
+        // Only pipelines composed from the old source need to be recreated
+        let stale_pipelines = self
+            .pipeline_sources
+            .iter()
+            .filter(|(_, sources)| sources.contains(&file_path))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in stale_pipelines {
+            self.pipelines.remove(&key);
+            self.pipeline_sources.remove(&key);
+        }
+        // The new source may be what a failed pipeline was missing
+        self.failed.clear();
+
+        Ok(())
+    }
+
+    fn add_composable_module(&mut self, file_path: &str, source: &str) -> Result<(), Error> {
+        let result = self
+            .composer
+            .add_composable_module(naga_oil::compose::ComposableModuleDescriptor {
+                source,
+                file_path,
+                ..Default::default()
+            })
+            .map(drop);
+        result.map_err(|err| Error::Compose(err.emit_to_string(&self.composer)))
+    }
+
+    /// The file path of a source and of every module it imports, directly or through other modules.
+    fn dependencies(&self, file_path: &str) -> HashSet<String> {
+        let module_paths = self
+            .sources
+            .iter()
+            .filter(|(_, source)| source.composable)
+            .filter_map(|(file_path, source)| {
+                let (name, _, _) = naga_oil::compose::get_preprocessor_data(&source.source);
+                Some((name?, file_path.as_str()))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut dependencies = HashSet::new();
+        let mut unvisited = vec![file_path.to_string()];
+        while let Some(file_path) = unvisited.pop() {
+            let Some(source) = self.sources.get(&file_path) else {
+                continue;
+            };
+            if !dependencies.insert(file_path) {
+                continue;
+            }
+
+            let (_, imports, _) = naga_oil::compose::get_preprocessor_data(&source.source);
+            unvisited.extend(
+                imports
+                    .iter()
+                    .filter_map(|import| module_paths.get(import.import.as_str()))
+                    .map(|file_path| file_path.to_string()),
+            );
+        }
+        dependencies
+    }
+
+    /// Every file a source was loaded from.
+    pub fn paths(&self) -> impl Iterator<Item = &camino::Utf8Path> {
+        self.sources
+            .values()
+            .filter_map(|source| source.path.as_deref())
+    }
+
+    /// Creates a new registry with every source that was loaded from a file read again.
+    /// No pipelines are carried over, so they are recreated from the new sources.
+    pub fn reloaded(&self) -> Result<Self, Error> {
+        let mut registry = Self::empty();
+
+        for (file_path, source) in &self.sources {
+            let mut source = source.clone();
+            let path = source
+                .path
+                .as_ref()
+                .filter(|_| !source.builtin || shaders::uses_source_dir());
+            // Keep the old source if the file went away
+            if let Some(Ok(new_source)) = path.map(std::fs::read_to_string) {
+                source.source = new_source;
+            }
+            registry.insert(file_path.clone(), source)?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Composes the shader at `file_path` with `shader_defs` into a shader module.
+    pub fn create_shader_module(
+        &mut self,
+        device: &wgpu::Device,
+        file_path: &str,
+        shader_defs: &ShaderDefs,
+    ) -> Result<wgpu::ShaderModule, Error> {
+        let dependencies = self.dependencies(file_path);
+        self.used_sources.extend(dependencies);
+
+        let source = self
+            .sources
+            .get(file_path)
+            .filter(|source| !source.composable)
+            .ok_or_else(|| Error::MissingShader(file_path.to_string()))?;
+        let module = self
+            .composer
+            .make_naga_module(naga_oil::compose::NagaModuleDescriptor {
+                source: &source.source,
+                file_path,
+                shader_defs: shader_defs
+                    .iter()
+                    .map(|(name, value)| (name.clone(), *value))
+                    .collect(),
+                ..Default::default()
+            })
+            .map_err(|err| Error::Compose(err.emit_to_string(&self.composer)))?;
+
+        Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(file_path),
+            source: wgpu::ShaderSource::Naga(std::borrow::Cow::Owned(module)),
+        }))
+    }
+
+    /// Creates the pipeline for `key` with `create`, unless it was already created.
+    ///
+    /// If creating the pipeline fails the error is only returned once,
+    /// and [`Registry::pipeline`] returns `None` for it until the registry is reloaded.
+    pub fn prepare_pipeline(
+        &mut self,
+        key: &PipelineKey,
+        create: impl FnOnce(&mut Self) -> Result<wgpu::RenderPipeline, Error>,
+    ) -> Result<(), Error> {
+        if self.pipelines.contains_key(key) || self.failed.contains(key) {
+            return Ok(());
+        }
+
+        self.used_sources.clear();
+        match create(self) {
+            Ok(pipeline) => {
+                self.pipelines.insert(key.clone(), pipeline);
+                let sources = std::mem::take(&mut self.used_sources);
+                self.pipeline_sources.insert(key.clone(), sources);
+                Ok(())
+            }
+            Err(err) => {
+                self.failed.insert(key.clone());
+                Err(err)
+            }
+        }
+    }
+
+    pub fn pipeline(&self, key: &PipelineKey) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(key)
+    }
+}
@@ -0,0 +1,53 @@
+#import wormhole::vertex_fetch as Fetch
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+};
+
+struct Constants {
+    view_proj: mat4x4<f32>,
+    light_position: vec3<f32>,
+    far: f32,
+}
+var<push_constant> constants: Constants;
+
+struct Transform {
+    obj_proj: mat4x4<f32>,
+    normal_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<storage> transforms: array<Transform>;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: Fetch::InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let transform = transforms[instance.transform_index];
+
+    let model_position = Fetch::read_vertex_position(vertex_index, instance.position_offset);
+    let world_position = transform.obj_proj * vec4<f32>(model_position, 1.0);
+
+    out.world_position = world_position.xyz;
+    out.clip_position = constants.view_proj * world_position;
+
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) distance: f32,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    // Stored linearly so the lighting pass can compare it against the fragment's distance to the light
+    out.distance = length(in.world_position - constants.light_position) / constants.far;
+
+    return out;
+}
@@ -0,0 +1,118 @@
+#import wormhole::fullscreen as Fullscreen
+#import wormhole::cubemap as Cubemap
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// Drawn on the far plane, so the depth test keeps it behind all geometry
+@vertex
+fn vs_main(model: Fullscreen::VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(model.position.xy, 1.0, 1.0);
+    out.ndc = model.position.xy;
+
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+// Must match render::skybox::Source
+const SOURCE_ENVIRONMENT     = 0u;
+const SOURCE_EQUIRECTANGULAR = 1u;
+const SOURCE_CUBEMAP         = 2u;
+
+struct Constants {
+    inv_view_proj: mat4x4<f32>,
+    view_pos: vec3<f32>,
+    source: u32,
+    intensity: f32,
+    // Indices into textures, only the first is used for equirectangular skyboxes
+    positive_x: u32,
+    negative_x: u32,
+    positive_y: u32,
+    negative_y: u32,
+    positive_z: u32,
+    negative_z: u32,
+}
+var<push_constant> constants: Constants;
+
+@group(0) @binding(0)
+var samplers: binding_array<sampler>;
+@group(0) @binding(1)
+var textures: binding_array<texture_2d<f32>>;
+@group(0) @binding(3)
+var<storage> texture_samplers: array<u32>;
+
+@group(1) @binding(0)
+var environment_sampler: sampler;
+@group(1) @binding(1)
+var environment: texture_cube<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    let world_position = constants.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = normalize(world_position.xyz / world_position.w - constants.view_pos);
+
+    var color: vec3<f32>;
+    switch constants.source {
+        case SOURCE_EQUIRECTANGULAR: {
+            let uv = vec2<f32>(
+                atan2(direction.z, direction.x) / (2.0 * Cubemap::PI) + 0.5,
+                acos(clamp(direction.y, -1.0, 1.0)) / Cubemap::PI,
+            );
+            color = textureSampleLevel(textures[constants.positive_x], samplers[texture_samplers[constants.positive_x]], uv, 0.0).rgb;
+        }
+        case SOURCE_CUBEMAP: {
+            color = sample_faces(direction);
+        }
+        default: {
+            color = textureSampleLevel(environment, environment_sampler, direction, 0.0).rgb;
+        }
+    }
+
+    out.color = vec4<f32>(color * constants.intensity, 1.0);
+
+    return out;
+}
+
+// Picks the face the direction points through, and where it hits that face
+fn sample_faces(direction: vec3<f32>) -> vec3<f32> {
+    let abs_direction = abs(direction);
+
+    var face: u32;
+    var uv: vec2<f32>;
+    if abs_direction.x >= abs_direction.y && abs_direction.x >= abs_direction.z {
+        if direction.x > 0.0 {
+            face = constants.positive_x;
+            uv = vec2<f32>(-direction.z, -direction.y) / abs_direction.x;
+        } else {
+            face = constants.negative_x;
+            uv = vec2<f32>(direction.z, -direction.y) / abs_direction.x;
+        }
+    } else if abs_direction.y >= abs_direction.z {
+        if direction.y > 0.0 {
+            face = constants.positive_y;
+            uv = vec2<f32>(direction.x, direction.z) / abs_direction.y;
+        } else {
+            face = constants.negative_y;
+            uv = vec2<f32>(direction.x, -direction.z) / abs_direction.y;
+        }
+    } else {
+        if direction.z > 0.0 {
+            face = constants.positive_z;
+            uv = vec2<f32>(direction.x, -direction.y) / abs_direction.z;
+        } else {
+            face = constants.negative_z;
+            uv = vec2<f32>(-direction.x, -direction.y) / abs_direction.z;
+        }
+    }
+
+    return textureSampleLevel(textures[face], samplers[texture_samplers[face]], uv * 0.5 + 0.5, 0.0).rgb;
+}
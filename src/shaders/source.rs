@@ -0,0 +1,45 @@
+// Copyright (C) 2024 Lily Lyons
+//
+// This file is part of wormhole.
+//
+// wormhole is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// wormhole is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with wormhole.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Shader sources are read from here when it exists, so shaders can be edited (and hot reloaded) without recompiling.
+/// Release builds only do so once [`use_source_dir`] is called, since the directory belongs to whoever built them.
+pub const SOURCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+static USE_SOURCE_DIR: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
+/// Reads shaders from [`SOURCE_DIR`] from now on, even in release builds. Called by the hot reload plugin.
+pub fn use_source_dir() {
+    USE_SOURCE_DIR.store(true, Ordering::Relaxed);
+}
+
+pub fn uses_source_dir() -> bool {
+    USE_SOURCE_DIR.load(Ordering::Relaxed)
+}
+
+/// The source of a shader in [`SOURCE_DIR`] if [`uses_source_dir`],
+/// falling back to the copy embedded in the binary if it can't be read.
+pub fn source(file_name: &str, embedded: &'static str) -> Cow<'static, str> {
+    if !uses_source_dir() {
+        return Cow::Borrowed(embedded);
+    }
+
+    std::fs::read_to_string(camino::Utf8Path::new(SOURCE_DIR).join(file_name))
+        .map_or(Cow::Borrowed(embedded), Cow::Owned)
+}
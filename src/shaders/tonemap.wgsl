@@ -0,0 +1,126 @@
+// Vertex shader
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.tex_coords = model.tex_coords;
+
+    return out;
+}
+
+// Fragment shader
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+struct Constants {
+    exposure: f32,
+    tonemapper: u32,
+    auto_exposure: u32,
+}
+var<push_constant> constants: Constants;
+
+struct ExposureState {
+    adapted_luminance: f32,
+    exposure: f32,
+}
+
+@group(0) @binding(0)
+var hdr_sampler: sampler;
+@group(0) @binding(1)
+var hdr_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var<storage> exposure_state: ExposureState;
+
+// Must match render::tonemap::Operator
+const OPERATOR_REINHARD = 0u;
+const OPERATOR_ACES     = 1u;
+const OPERATOR_AGX      = 2u;
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    var exposure = constants.exposure;
+    if constants.auto_exposure != 0u {
+        exposure *= exposure_state.exposure;
+    }
+
+    let color = textureSample(hdr_texture, hdr_sampler, in.tex_coords).rgb * exposure;
+
+    var mapped: vec3<f32>;
+    switch constants.tonemapper {
+        case OPERATOR_REINHARD: {
+            mapped = reinhard(color);
+        }
+        case OPERATOR_AGX: {
+            mapped = agx(color);
+        }
+        default: {
+            mapped = aces(color);
+        }
+    }
+
+    // The output is an srgb target, so this stays linear
+    out.color = vec4<f32>(mapped, 1.0);
+
+    return out;
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (vec3<f32>(1.0) + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Polynomial approximation of AgX's default look by Benjamin Wrensch
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3<f32>(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var mapped = inset * color;
+    mapped = clamp(log2(max(mapped, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    mapped = (mapped - min_ev) / (max_ev - min_ev);
+    mapped = agx_contrast(mapped);
+    mapped = outset * mapped;
+
+    // AgX outputs display encoded values
+    return pow(max(mapped, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
@@ -0,0 +1,231 @@
+// Vertex shader
+#import wormhole::util as Util
+#import wormhole::vertex_fetch as Fetch
+#import wormhole::lights as Lights
+#import wormhole::pbr as Pbr
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) position: vec3<f32>,
+
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+
+    @location(5) base_color: vec4<f32>,
+
+    @location(6) @interpolate(flat) material_index: u32,
+};
+
+struct Constants {
+    view_proj: mat4x4<f32>,
+    view_pos: vec3<f32>,
+    tile_count_x: u32,
+}
+
+var<push_constant> constants: Constants;
+
+struct Transform {
+    obj_proj: mat4x4<f32>,
+    normal_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<storage> transforms: array<Transform>;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: Fetch::InstanceInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let transform = transforms[instance.transform_index];
+
+    let model_position = Fetch::read_vertex_position(vertex_index, instance.position_offset);
+    let world_position = transform.obj_proj * vec4<f32>(model_position, 1.0);
+
+    let tex_coords = Fetch::read_vertex_tex_coords(vertex_index, instance.tex_coord_offset);
+    out.tex_coords = tex_coords;
+
+    out.position = world_position.xyz;
+    out.clip_position = constants.view_proj * world_position;
+
+    let normal_matrix = mat3x3<f32>(transform.normal_proj[0].xyz, transform.normal_proj[1].xyz, transform.normal_proj[2].xyz);
+
+    let model_normal = Fetch::read_vertex_normal(vertex_index, instance.normal_offset);
+    let model_tangent = Fetch::read_vertex_tangent(vertex_index, instance.tangent_offset);
+    let model_bitangent = cross(model_normal, model_tangent.xyz) * model_tangent.w;
+
+    out.world_normal = normalize(normal_matrix * model_normal);
+    out.world_tangent = normalize(normal_matrix * model_tangent.xyz);
+    out.world_bitangent = normalize(normal_matrix * model_bitangent);
+
+    out.base_color = select(
+        vec4<f32>(1.0),
+        Fetch::read_vertex_color(vertex_index, instance.color_offset),
+        Util::extract_flag(instance.format_flags, Fetch::HAS_VTX_COLOR)
+    );
+
+    out.material_index = instance.material_index;
+
+    return out;
+}
+
+// Fragment shader
+
+struct Material {
+    base_color: vec4<f32>,
+    base_color_texture: u32,
+
+    metallic: f32,
+    roughness: f32,
+    metallic_roughness_texture: u32,
+
+    emissive: vec4<f32>,
+    emissive_texture: u32,
+
+    normal_texture: u32,
+    occlusion_texture: u32,
+
+    alpha_cutoff: f32,
+    flags: u32,
+}
+
+const HAS_BASE_COLOR_TEXTURE         = 0x0001u;
+const HAS_METALLIC_ROUGHNESS_TEXTURE = 0x0002u;
+const HAS_EMISSIVE_TEXTURE           = 0x0004u;
+const HAS_OCCLUSION_TEXTURE          = 0x0008u;
+const HAS_NORMAL_MAP                 = 0x0010u;
+const HAS_ALPHA_CUTOFF               = 0x0020u;
+
+@group(1) @binding(0)
+var samplers: binding_array<sampler>;
+@group(1) @binding(1)
+var textures: binding_array<texture_2d<f32>>;
+@group(1) @binding(2)
+var<storage> materials: array<Material>;
+@group(1) @binding(3)
+var<storage> texture_samplers: array<u32>;
+
+fn sample_material_texture(index: u32, tex_coords: vec2<f32>) -> vec4<f32> {
+    return textureSample(textures[index], samplers[texture_samplers[index]], tex_coords);
+}
+
+@group(2) @binding(0)
+var<storage> lights: array<Lights::Light>;
+@group(2) @binding(1)
+var<storage> tile_light_indices: array<u32>;
+@group(2) @binding(2)
+var environment_sampler: sampler;
+@group(2) @binding(3)
+var irradiance_map: texture_cube<f32>;
+@group(2) @binding(4)
+var prefiltered_map: texture_cube<f32>;
+@group(2) @binding(5)
+var brdf_lut: texture_2d<f32>;
+
+@group(3) @binding(0)
+var shadow_sampler: sampler;
+@group(3) @binding(1)
+var shadow_maps: binding_array<texture_cube<f32>>;
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+
+    let material = materials[in.material_index];
+
+    let base_color_texture = sample_material_texture(material.base_color_texture, in.tex_coords);
+    let normal_map_texture = sample_material_texture(material.normal_texture, in.tex_coords);
+    let metallic_roughness_texture = sample_material_texture(material.metallic_roughness_texture, in.tex_coords);
+    let emissive_texture = sample_material_texture(material.emissive_texture, in.tex_coords);
+    let occlusion_texture = sample_material_texture(material.occlusion_texture, in.tex_coords);
+
+    var base_color = material.base_color.rgb * in.base_color.rgb;
+    var alpha = material.base_color.a * in.base_color.a;
+    if Util::extract_flag(material.flags, HAS_BASE_COLOR_TEXTURE) {
+        base_color = base_color_texture.rgb;
+        alpha *= base_color_texture.a;
+    }
+
+    var normal = in.world_normal;
+    if Util::extract_flag(material.flags, HAS_NORMAL_MAP) {
+        let tangent_matrix = mat3x3<f32>(
+            in.world_tangent,
+            in.world_bitangent,
+            in.world_normal,
+        );
+        let normal_map = normal_map_texture.rgb * 2.0 - 1.0;
+        normal = normalize(tangent_matrix * normal_map);
+    }
+
+    var metallicity = material.metallic;
+    var roughness = material.roughness;
+    if Util::extract_flag(material.flags, HAS_METALLIC_ROUGHNESS_TEXTURE) {
+        metallicity = metallic_roughness_texture.b;
+        roughness = metallic_roughness_texture.g;
+    }
+
+    var emissive = material.emissive.xyz;
+    if Util::extract_flag(material.flags, HAS_EMISSIVE_TEXTURE) {
+        emissive = emissive_texture.xyz;
+    }
+
+    var occlusion = 1.0;
+    if Util::extract_flag(material.flags, HAS_OCCLUSION_TEXTURE) {
+        occlusion = occlusion_texture.r;
+    }
+
+    var surface: Pbr::Surface;
+    surface.position = in.position;
+    surface.normal = normalize(normal);
+    surface.view = normalize(constants.view_pos - in.position);
+    surface.albedo = base_color;
+    surface.roughness = roughness;
+    surface.metallic = metallicity;
+
+    var l_o = vec3<f32>(0.0);
+    // Tiles are culled from the near plane on, so their lights cover transparent surfaces too
+    let tile = vec2<u32>(in.clip_position.xy) / Lights::TILE_SIZE;
+    let tile_offset = (tile.y * constants.tile_count_x + tile.x) * Lights::TILE_STRIDE;
+    let tile_light_count = tile_light_indices[tile_offset];
+
+    for (var i = 0u; i < tile_light_count; i++) {
+        let light = lights[tile_light_indices[tile_offset + 1u + i]];
+        l_o += Pbr::light_contribution(light, surface, shadow_factor(light, surface.position));
+    }
+    // Image based lighting from the environment
+    let reflection = reflect(-surface.view, surface.normal);
+    let max_mip = f32(textureNumLevels(prefiltered_map) - 1u);
+    let irradiance = textureSampleLevel(irradiance_map, environment_sampler, surface.normal, 0.0).rgb;
+    let prefiltered = textureSampleLevel(prefiltered_map, environment_sampler, reflection, surface.roughness * max_mip).rgb;
+    let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(max(dot(surface.normal, surface.view), 0.0), surface.roughness), 0.0).rg;
+    let ambient = Pbr::ambient_contribution(surface, irradiance, prefiltered, brdf) * occlusion;
+
+    out.color = vec4<f32>(ambient + l_o + emissive, alpha);
+
+    return out;
+}
+
+// 0.0 if the position is hidden from the light, 1.0 otherwise
+fn shadow_factor(light: Lights::Light, position: vec3<f32>) -> f32 {
+    if light.shadow_index < 0 {
+        return 1.0;
+    }
+
+    let light_to_position = position - light.position;
+    let distance = length(light_to_position);
+    if distance >= light.shadow_far {
+        return 1.0;
+    }
+
+    let closest = textureSampleLevel(shadow_maps[light.shadow_index], shadow_sampler, light_to_position, 0.0).r * light.shadow_far;
+    return select(1.0, 0.0, distance - light.shadow_bias > closest);
+}
@@ -107,7 +107,19 @@ impl<T: Default> Time<T> {
     }
 }
 
-pub fn init_into(builder: &mut scene::WorldBuilder) {
+pub struct Plugin;
+
+impl scene::Plugin for Plugin {
+    fn build(self: Box<Self>, builder: &mut scene::WorldBuilder) {
+        init_into(builder);
+    }
+
+    fn dependencies(&self) -> Vec<scene::PluginId> {
+        vec![scene::PluginId::of::<scene::MainSchedulePlugin>()]
+    }
+}
+
+fn init_into(builder: &mut scene::WorldBuilder) {
     builder
         .init_resource::<Time>()
         .init_resource::<Time<Real>>()